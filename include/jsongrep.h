@@ -0,0 +1,36 @@
+/* C interface of jsongrep, built as a shared library with
+   `cargo rustc --release --lib --crate-type cdylib --features capi`. */
+#ifndef JSONGREP_H
+#define JSONGREP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes, stable across versions. */
+#define JSONGREP_OK 0
+#define JSONGREP_MATCHED 1
+#define JSONGREP_UNMATCHED 0
+#define JSONGREP_ERR_NULL (-1)
+#define JSONGREP_ERR_UTF8 (-2)
+#define JSONGREP_ERR_JSON (-3)
+#define JSONGREP_ERR_QUERY (-4)
+#define JSONGREP_ERR_EVAL (-5)
+
+typedef struct JsongrepQuery JsongrepQuery;
+
+/* Compile the json of the query into *out, released by jsongrep_free.
+   Return JSONGREP_OK or an error code. */
+int jsongrep_compile(const char *json, JsongrepQuery **out);
+
+/* Return JSONGREP_MATCHED, JSONGREP_UNMATCHED or an error code. */
+int jsongrep_select(const JsongrepQuery *query, const char *line);
+
+/* Release the query, null is allowed. */
+void jsongrep_free(JsongrepQuery *query);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,217 @@
+use crate::args::{io_err, QueryArgs};
+use crate::grep::read_line;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::pointer::Pointer;
+use jsongrep::timestamp;
+use serde_json::json;
+use serde_json::value::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+/// Count the jsons of stdin selected by the query.
+///
+/// Write {"count":N} or, grouped by `--by`, {"key":[...],"count":N} per group
+/// in order of first occurrence.
+/// The lines that failed to be selected are written to stderr and skipped.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct AggOpt {
+    #[structopt(flatten)]
+    query: QueryArgs,
+    /// Group by the values at this pointer, can be specified multiple times.
+    ///
+    /// A missing value is grouped as null.
+    #[structopt(long = "by")]
+    by: Vec<String>,
+    /// Count by the tumbling windows of this length, e.g. 1m, by the time at `ts`.
+    ///
+    /// Write {"window":"2006-01-02T15:04:05Z",...} per window and group,
+    /// a window is written when a json of a later window is read,
+    /// so the input is expected in order of time.
+    #[structopt(long = "window")]
+    window: Option<humantime::Duration>,
+    /// Pointer of the time of the json for `window`, epoch seconds or a rfc3339 string.
+    #[structopt(long = "ts")]
+    ts: Option<String>,
+}
+
+impl AggOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        if self.query.reads_stdin() {
+            return Err(Error::new(ErrorCode::InvalidOption(
+                "stdin is for the lines, give the query by query_fd".to_owned(),
+            )));
+        }
+        let selector = self.query.get_selector()?;
+        let by = self
+            .by
+            .iter()
+            .map(|x| Pointer::parse(x))
+            .collect::<Result<Vec<_>>>()?;
+        let ts = self.ts.as_deref().map(Pointer::parse).transpose()?;
+        let window: Option<Duration> = self.window.map(Into::into);
+        let window = match (ts, window) {
+            (Some(p), Some(w)) if !w.is_zero() => Some((p, w)),
+            (None, None) => None,
+            (_, Some(w)) if w.is_zero() => {
+                return Err(Error::new(ErrorCode::InvalidOption(
+                    "window should be positive".to_owned(),
+                )))
+            }
+            _ => {
+                return Err(Error::new(ErrorCode::InvalidOption(
+                    "window and ts are required together".to_owned(),
+                )))
+            }
+        };
+        let mut groups = Groups::default();
+        // open windows by the start times
+        let mut windows: BTreeMap<SystemTime, Groups> = BTreeMap::new();
+        let mut stdin = io::stdin().lock();
+        let mut buf = String::new();
+        let mut n = 0;
+        while read_line(&mut stdin, &mut buf).map_err(io_err)? {
+            n += 1;
+            let v = match selector.filter(&buf) {
+                Ok(Some(x)) => x,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("line {}: {}", n, e);
+                    continue;
+                }
+            };
+            let key = Value::Array(
+                by.iter()
+                    .map(|p| p.get(&v).cloned().unwrap_or(Value::Null))
+                    .collect(),
+            );
+            let (p, w) = match &window {
+                Some(x) => x,
+                None => {
+                    groups.add(key);
+                    continue;
+                }
+            };
+            let start = match p.get(&v).and_then(timestamp) {
+                Some(x) => window_start(x, *w),
+                None => {
+                    eprintln!("line {}: invalid timestamp at {}", n, p);
+                    continue;
+                }
+            };
+            // the windows before this are closed, assuming the input is in order of time
+            while let Some(e) = windows.first_entry().filter(|x| *x.key() < start) {
+                let (t, g) = e.remove_entry();
+                g.write(Some(t), !by.is_empty());
+            }
+            windows.entry(start).or_default().add(key);
+        }
+        if window.is_none() {
+            groups.write(None, !by.is_empty());
+        }
+        for (t, g) in windows {
+            g.write(Some(t), !by.is_empty());
+        }
+        Ok(())
+    }
+}
+
+/// Counts of the groups in order of first occurrence.
+#[derive(Default)]
+struct Groups {
+    groups: Vec<(Value, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl Groups {
+    fn add(&mut self, key: Value) {
+        let groups = &mut self.groups;
+        let i = *self.index.entry(key.to_string()).or_insert_with(|| {
+            groups.push((key, 0));
+            groups.len() - 1
+        });
+        self.groups[i].1 += 1;
+    }
+    /// Write the counts, with the keys if `keyed`, with the window if given.
+    fn write(self, window: Option<SystemTime>, keyed: bool) {
+        let window = window.map(|x| humantime::format_rfc3339(x).to_string());
+        if !keyed {
+            let count = self.groups.first().map_or(0, |x| x.1);
+            let mut v = json!({ "count": count });
+            if let Some(w) = window {
+                v["window"] = json!(w);
+            }
+            println!("{}", v);
+            return;
+        }
+        for (key, count) in self.groups {
+            let mut v = json!({ "key": key, "count": count });
+            if let Some(w) = window.as_ref() {
+                v["window"] = json!(w);
+            }
+            println!("{}", v);
+        }
+    }
+}
+
+/// Epoch seconds of 10000-01-01T00:00:00Z, the windows must be before it to be written in rfc3339.
+const TIMESTAMP_END: u64 = 253_402_300_800;
+
+/// Return the time of epoch seconds or a rfc3339 string,
+/// none if before the epoch or after the year 9999, e.g. epoch milliseconds.
+fn timestamp(v: &Value) -> Option<SystemTime> {
+    match v {
+        Value::Number(x) => x.as_f64().and_then(timestamp::to_system_time),
+        Value::String(x) => timestamp::parse_system_time(x),
+        _ => None,
+    }
+    .filter(|t| *t >= UNIX_EPOCH && *t < UNIX_EPOCH + Duration::from_secs(TIMESTAMP_END))
+}
+
+/// Return the start of the tumbling window of `w` containing `t`.
+fn window_start(t: SystemTime, w: Duration) -> SystemTime {
+    let since = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let w = w.as_nanos();
+    let start = since / w * w;
+    UNIX_EPOCH
+        + Duration::new(
+            (start / 1_000_000_000) as u64,
+            (start % 1_000_000_000) as u32,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_starts() {
+        let t = |s: u64, n: u32| UNIX_EPOCH + Duration::new(s, n);
+        let w = Duration::from_secs(60);
+        assert_eq!(t(0, 0), window_start(t(59, 999_999_999), w));
+        assert_eq!(t(60, 0), window_start(t(60, 0), w));
+        assert_eq!(t(120, 0), window_start(t(150, 5), w));
+        assert_eq!(
+            t(1, 500_000_000),
+            window_start(t(1, 700_000_000), Duration::from_millis(500))
+        );
+    }
+    #[test]
+    fn timestamps() {
+        let t = |s: u64| Some(UNIX_EPOCH + Duration::from_secs(s));
+        assert_eq!(t(1_700_000_000), timestamp(&json!(1_700_000_000)));
+        assert_eq!(t(60), timestamp(&json!("1970-01-01T01:01:00+01:00")));
+        assert_eq!(
+            t(TIMESTAMP_END - 1),
+            timestamp(&json!("9999-12-31T23:59:59Z"))
+        );
+        // epoch milliseconds
+        assert_eq!(None, timestamp(&json!(1_700_000_000_000u64)));
+        assert_eq!(None, timestamp(&json!(TIMESTAMP_END)));
+        assert_eq!(None, timestamp(&json!(1e30)));
+        assert_eq!(None, timestamp(&json!(-1)));
+        assert_eq!(None, timestamp(&json!("x")));
+        assert_eq!(None, timestamp(&json!(null)));
+    }
+}
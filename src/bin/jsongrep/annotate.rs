@@ -0,0 +1,38 @@
+use jsongrep::query::Query;
+use serde_json::json;
+use serde_json::value::Value;
+
+/// Wrap the selected jsons with the metadata of the match.
+pub(crate) struct Annotator {
+    /// The query to tell the matched pairs, none if selecting all.
+    query: Option<Query>,
+    file: String,
+    name: Option<String>,
+}
+
+impl Annotator {
+    pub(crate) fn new(query: Option<Query>, file: String, name: Option<String>) -> Annotator {
+        Annotator { query, file, name }
+    }
+    /// Return the pointers of the pairs of the query accepting `v`.
+    pub(crate) fn matched_pairs(&self, v: &Value) -> Vec<String> {
+        self.query.as_ref().map_or(Vec::new(), |q| {
+            q.matched_pointers(v)
+                .iter()
+                .map(|x| x.to_string())
+                .collect()
+        })
+    }
+    /// Return `record` of the `n`-th line with the metadata.
+    pub(crate) fn annotate(&self, n: usize, record: Value, matched_pairs: Vec<String>) -> Value {
+        json!({
+            "record": record,
+            "meta": {
+                "line": n,
+                "file": self.file,
+                "matched_pairs": matched_pairs,
+                "query": self.name,
+            },
+        })
+    }
+}
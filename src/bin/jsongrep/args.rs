@@ -0,0 +1,491 @@
+use crate::config::Config;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::expr;
+use jsongrep::pointer::Pointer;
+use jsongrep::query::Query;
+use jsongrep::raw_query::{MismatchPolicy, Query as RawQuery, QueryCondition as RawQueryCondition};
+use jsongrep::raw_sort::Sort as RawSort;
+use jsongrep::select::Query as Selector;
+use jsongrep::sort::Sort;
+use serde::de::DeserializeOwned;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use structopt::StructOpt;
+use tracing::{debug, info};
+
+pub(crate) fn io_err(x: io::Error) -> Error {
+    Error::new(ErrorCode::Io(x))
+}
+
+/// Read a file into a string.
+pub(crate) fn read_file(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(io_err)
+}
+
+/// Path to read a document from stdin.
+const STDIN: &str = "-";
+
+/// Read a query or a sort document from `path`, or from stdin if `path` is `-`.
+///
+/// Stdin and the pipes of the file descriptors can be read only once,
+/// read the query and the sort once per command and pass them down.
+fn read_document(path: &Path) -> Result<String> {
+    if path == Path::new(STDIN) {
+        let mut buf = String::new();
+        io::stdin().read_to_string(&mut buf).map_err(io_err)?;
+        Ok(buf)
+    } else {
+        read_file(path)
+    }
+}
+
+/// Parse the query or the sort document read from `path`.
+///
+/// The format is `format` if given, otherwise yaml if the extension is `yaml` or `yml`,
+/// json5 if `json5`, json with comments if else.
+fn parse_document<T>(s: &str, path: &Path, format: Option<&str>) -> Result<T>
+where
+    T: DeserializeOwned + for<'a> TryFrom<&'a str, Error = Error>,
+{
+    let format = format.or_else(|| path.extension().and_then(|x| x.to_str()));
+    let invalid = |format: &str, x: String| {
+        Error::new(ErrorCode::InvalidOption(format!(
+            "invalid {} {}: {}",
+            format,
+            path.display(),
+            x
+        )))
+    };
+    match format {
+        Some("yaml" | "yml") => serde_yaml::from_str(s).map_err(|x| invalid("yaml", x.to_string())),
+        Some("json5") => json5::from_str(s).map_err(|x| invalid("json5", x.to_string())),
+        _ => T::try_from(s),
+    }
+}
+
+/// Read the query file, `-` for stdin, in the format by the extension.
+pub(crate) fn load_query(path: &Path) -> Result<RawQuery> {
+    read_document(path).and_then(|s| parse_document(&s, path, None))
+}
+
+/// Path of the file descriptor `fd`.
+fn fd_path(fd: u32) -> PathBuf {
+    PathBuf::from(format!("/dev/fd/{}", fd))
+}
+
+/// Options to specify a query.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct QueryArgs {
+    /// Specify query on command line.
+    ///
+    /// Grep `/s` value by regex `[sS]irius`
+    ///
+    ///
+    /// {
+    ///   "query": {
+    ///     "type": "raw",
+    ///     "pair": {
+    ///       "p": "/s",
+    ///       "cond": {
+    ///         "type": "match",
+    ///         "mtype": "regex",
+    ///         "value": {
+    ///           "type": "string",
+    ///           "value": "[sS]irius"
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    ///
+    /// It accepts json like {"s":"sirius"}, {"s":"Sirius"},
+    /// writes the json to stdout.
+    ///
+    /// It does not accept string like {"s":"spica"}, {"s":null}, {"a":"sirius"}, and not json string.
+    /// If the schema is ok then no output.
+    /// Otherwise error is written to stderr.
+    ///
+    /// See [`jsongrep::select::Query`].
+    ///
+    /// The options to specify query can be given multiple times, see `combine`.
+    #[structopt(short = "r", long = "raw_query", number_of_values = 1)]
+    raw_query: Vec<String>,
+    /// Specify query by file, `-` to read it from stdin.
+    ///
+    /// Stdin is shared with the lines, give them by `--input` then.
+    #[structopt(short = "q", long = "query_file", number_of_values = 1)]
+    query: Vec<PathBuf>,
+    /// Specify query by file descriptor, e.g. `--query_fd 3 3<query.json`.
+    #[structopt(long = "query_fd", number_of_values = 1)]
+    query_fd: Vec<u32>,
+    /// Format of `query_file` and `query_fd`.
+    ///
+    /// By default, yaml if the extension is `yaml` or `yml`, json5 if `json5`, otherwise json.
+    #[structopt(long = "query_format", possible_values = &["json", "yaml", "json5"])]
+    query_format: Option<String>,
+    /// Specify query by expression.
+    ///
+    /// `pointer op value` triples combined by `and`, `or`, `not` and parentheses,
+    /// e.g. '/level eq "error" and /code >= 500'.
+    ///
+    /// op: eq (==), ne (!=), gt (>), lt (<), ge (>=), le (<=), contains, regex (=~).
+    /// value: json literal.
+    ///
+    /// See [`jsongrep::expr`].
+    #[structopt(short = "e", long = "expr", number_of_values = 1)]
+    expr: Vec<String>,
+    /// How to combine the queries if multiple `raw_query`, `query_file`, `query_fd` and `expr` are given.
+    ///
+    /// The queries of the files and the queries on command line are combined separately,
+    /// then the latter always narrow the former by `and`,
+    /// e.g. `-q base.json -e '/level eq "error"' -e '/code ge 500' --combine or`
+    /// selects the jsons of the base query that are errors or have the codes 500 and over.
+    #[structopt(long = "combine", default_value = "and", possible_values = &["and", "or"])]
+    combine: String,
+    /// Evaluate a condition against a value of the different type as false instead of an error.
+    ///
+    /// Same as `"on_mismatch":"false"` of the query,
+    /// `"on_mismatch"` in the query takes precedence.
+    #[structopt(long = "mismatch_as_false")]
+    mismatch_as_false: bool,
+    /// Query used if none of the above is specified.
+    #[structopt(skip)]
+    preset: Option<RawQuery>,
+}
+
+impl QueryArgs {
+    /// Fill the query from the config unless already specified.
+    pub(crate) fn with_config(mut self, config: &mut Config) -> Self {
+        if self.raw_query.is_empty()
+            && self.query.is_empty()
+            && self.query_fd.is_empty()
+            && self.expr.is_empty()
+            && self.preset.is_none()
+        {
+            self.expr.extend(config.expr.take());
+            self.preset = config.query.take();
+            if self.expr.is_empty() && self.preset.is_none() {
+                self.query.extend(config.query_file.take());
+            }
+        }
+        self.mismatch_as_false |= config.mismatch_as_false;
+        self
+    }
+    pub(crate) fn get_raw_query(&self) -> Option<Result<RawQuery>> {
+        let files = self
+            .query
+            .iter()
+            .cloned()
+            .chain(self.query_fd.iter().map(|x| fd_path(*x)))
+            .map(|x| {
+                read_document(&x).and_then(|s| parse_document(&s, &x, self.query_format.as_deref()))
+            })
+            .collect::<Result<Vec<_>>>();
+        let r = self.raw_query.iter().map(|x| RawQuery::try_from(x as &str));
+        let e = self.expr.iter().map(|x| expr::parse(x));
+        let (files, inline) = match (files, r.chain(e).collect::<Result<Vec<_>>>()) {
+            (Ok(x), Ok(y)) => (x, y),
+            (Err(x), _) | (_, Err(x)) => return Some(Err(x)),
+        };
+        let or = self.combine == "or";
+        let mut q = match (combine(files, or), combine(inline, or)) {
+            // layer the queries on command line on top of the base of the files
+            (Some(base), Some(x)) => combine(vec![base, x], false)?,
+            (Some(x), None) | (None, Some(x)) => x,
+            (None, None) => self.preset.clone()?,
+        };
+        if self.mismatch_as_false {
+            q.on_mismatch.get_or_insert(MismatchPolicy::False);
+        }
+        Some(Ok(q))
+    }
+    /// Return the stems of the query files separated by commas, none if no file is given.
+    pub(crate) fn query_name(&self) -> Option<String> {
+        let names: Vec<_> = self
+            .query
+            .iter()
+            .map(|x| x.file_stem().unwrap_or_default().to_string_lossy())
+            .collect();
+        Some(names.join(",")).filter(|_| !names.is_empty())
+    }
+    /// Return true if the query is read from stdin.
+    pub(crate) fn reads_stdin(&self) -> bool {
+        self.query.iter().any(|x| x == Path::new(STDIN))
+    }
+    pub(crate) fn get_query(&self) -> Option<Result<Query>> {
+        self.get_raw_query().map(|x| x.and_then(Query::try_from))
+    }
+    /// Read and compile the query, `None` if no query is specified.
+    pub(crate) fn compile(&self) -> Result<Option<Query>> {
+        let start = Instant::now();
+        let q = self.get_query().transpose()?;
+        if let Some(q) = q.as_ref() {
+            debug!(
+                query = %serde_json::to_string(q).unwrap_or_default(),
+                pointers = ?q.pointers().iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+                "compiled query"
+            );
+            info!(elapsed = ?start.elapsed(), "query compiled");
+        }
+        Ok(q)
+    }
+    /// Return the selector of the query, that selects all if no query is specified.
+    pub(crate) fn get_selector(&self) -> Result<Selector> {
+        self.compile().map(|x| selector(x.as_ref()))
+    }
+}
+
+/// Return the selector of `query`, that selects all if none.
+pub(crate) fn selector(query: Option<&Query>) -> Selector {
+    match query {
+        Some(q) => Selector::new(Box::new(q.to_closure())),
+        None => Selector::all(),
+    }
+}
+
+/// Combine the queries by `or` if `or`, otherwise by `and`.
+/// Return the query itself if only one, `None` if empty.
+fn combine(mut queries: Vec<RawQuery>, or: bool) -> Option<RawQuery> {
+    if queries.len() <= 1 {
+        return queries.pop();
+    }
+    let pair = queries
+        .into_iter()
+        .map(|mut x| {
+            // keep the default policy of each query
+            if let Some(p) = x.on_mismatch {
+                x.query.set_default_on_mismatch(p);
+            }
+            x.query
+        })
+        .collect();
+    Some(RawQuery {
+        query: if or {
+            RawQueryCondition::Or { pair }
+        } else {
+            RawQueryCondition::And { pair }
+        },
+        on_mismatch: None,
+    })
+}
+
+/// Options to write a page of the sorted lines.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct PageArgs {
+    /// Skip this number of the sorted lines, e.g. to page through them across the runs.
+    #[structopt(long = "sort_offset")]
+    sort_offset: Option<usize>,
+    /// Write at most this number of the sorted lines.
+    #[structopt(long = "sort_limit")]
+    sort_limit: Option<usize>,
+}
+
+impl PageArgs {
+    /// Reject the page without the sort.
+    pub(crate) fn validate(&self, sorted: bool) -> Result<()> {
+        if !sorted && (self.sort_offset.is_some() || self.sort_limit.is_some()) {
+            Err(Error::new(ErrorCode::InvalidOption(
+                "sort_offset and sort_limit require sort".to_owned(),
+            )))
+        } else {
+            Ok(())
+        }
+    }
+    /// Return the page of the sorted items.
+    pub(crate) fn page<I: Iterator>(&self, sorted: I) -> impl Iterator<Item = I::Item> {
+        sorted
+            .skip(self.sort_offset.unwrap_or(0))
+            .take(self.sort_limit.unwrap_or(usize::MAX))
+    }
+}
+
+/// Options to specify a sort.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct SortArgs {
+    /// Specify sort on command line.
+    ///
+    /// Sort `/i` value desc
+    ///
+    /// {
+    ///   "sort": [
+    ///      {
+    ///        "p": "/i",
+    ///        "ord": "desc"
+    ///      }
+    ///   ]
+    /// }
+    ///
+    /// Basic sort order: null < array < object < bool < number < string.
+    /// Ignore inner values of array and object.
+    ///
+    /// If a pointed value does not exist, the row is sorted as null.
+    #[structopt(short = "k", long = "raw_sort")]
+    raw_sort: Option<String>,
+    /// Specify sort by file, `-` to read it from stdin.
+    #[structopt(short = "s", long = "sort")]
+    sort: Option<PathBuf>,
+    /// Specify sort by file descriptor, e.g. `--sort_fd 4 4<sort.json`.
+    #[structopt(long = "sort_fd")]
+    sort_fd: Option<u32>,
+    /// Format of `sort` and `sort_fd`, the same as `query_format`.
+    #[structopt(long = "sort_format", possible_values = &["json", "yaml", "json5"])]
+    sort_format: Option<String>,
+    /// Sort used if none of the above is specified.
+    #[structopt(skip)]
+    preset: Option<RawSort>,
+}
+
+impl SortArgs {
+    /// Fill the sort from the config unless already specified.
+    pub(crate) fn with_config(mut self, config: &mut Config) -> Self {
+        if self.raw_sort.is_none()
+            && self.sort.is_none()
+            && self.sort_fd.is_none()
+            && self.preset.is_none()
+        {
+            self.preset = config.sort.take();
+            if self.preset.is_none() {
+                self.sort = config.sort_file.take();
+            }
+        }
+        self
+    }
+    pub(crate) fn validate(&self) -> Result<()> {
+        let given = [
+            self.raw_sort.is_some(),
+            self.sort.is_some(),
+            self.sort_fd.is_some(),
+        ];
+        if given.iter().filter(|x| **x).count() > 1 {
+            Err(Error::new(ErrorCode::InvalidOption(
+                "sort, sort_fd and raw_sort are exclusive".to_owned(),
+            )))
+        } else {
+            Ok(())
+        }
+    }
+    /// Return true if the sort is read from stdin.
+    pub(crate) fn reads_stdin(&self) -> bool {
+        self.sort.as_deref() == Some(Path::new(STDIN))
+    }
+    /// Return true if a sort is specified, without reading it.
+    pub(crate) fn is_given(&self) -> bool {
+        self.raw_sort.is_some()
+            || self.sort.is_some()
+            || self.sort_fd.is_some()
+            || self.preset.is_some()
+    }
+    pub(crate) fn get_raw_sort(&self) -> Option<Result<RawSort>> {
+        let k = self.raw_sort.as_ref().map(|x| RawSort::try_from(x as &str));
+        let s = self
+            .sort
+            .clone()
+            .or_else(|| self.sort_fd.map(fd_path))
+            .map(|x| {
+                read_document(&x).and_then(|s| parse_document(&s, &x, self.sort_format.as_deref()))
+            });
+        k.xor(s).or_else(|| self.preset.clone().map(Ok))
+    }
+    /// Same as [`SortArgs::get_raw_sort`] but also reject the malformed pointers,
+    /// that `get_sort` sorts as null.
+    pub(crate) fn get_checked_sort(&self) -> Option<Result<RawSort>> {
+        self.get_raw_sort().map(|x| x.and_then(check_sort))
+    }
+    pub(crate) fn get_sort(&self, threads: usize) -> Result<Option<Sort>> {
+        self.get_raw_sort()
+            .map(|x| x.map(|s| Sort::from(s).with_threads(threads)))
+            .transpose()
+    }
+}
+
+/// Reject the malformed pointers of the sort.
+pub(crate) fn check_sort(sort: RawSort) -> Result<RawSort> {
+    for p in &sort.sort {
+        Pointer::parse(&p.pointer)?;
+    }
+    Ok(sort)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsongrep::query::EvaluableQuery;
+    use serde_json::json;
+
+    fn env_config() -> Config {
+        Config {
+            query_file: Some(PathBuf::from("/nonexistent/query.json")),
+            sort_file: Some(PathBuf::from("/nonexistent/sort.json")),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn env_files_given_options() {
+        let q = QueryArgs::from_iter(vec!["q", "-e", "/i > 1"]).with_config(&mut env_config());
+        assert!(q.get_raw_query().unwrap().is_ok());
+        let s = SortArgs::from_iter(vec!["s", "-k", r#"{"sort":[{"p":"/i"}]}"#])
+            .with_config(&mut env_config());
+        assert!(s.get_raw_sort().unwrap().is_ok());
+    }
+    #[test]
+    fn env_files_without_options() {
+        let q = QueryArgs::from_iter(vec!["q"]).with_config(&mut env_config());
+        assert!(q.get_raw_query().unwrap().is_err());
+        let s = SortArgs::from_iter(vec!["s"]).with_config(&mut env_config());
+        assert!(s.get_raw_sort().unwrap().is_err());
+    }
+
+    /// Return the query of `args` with the query file `name` of `expr`.
+    fn with_base(name: &str, expr: &str, args: &[&str]) -> Query {
+        let path =
+            std::env::temp_dir().join(format!("jsongrep-{}-{}.json", name, std::process::id()));
+        fs::write(
+            &path,
+            serde_json::to_string(&expr::parse(expr).unwrap()).unwrap(),
+        )
+        .unwrap();
+        let q = QueryArgs::from_iter(
+            ["q", "-q", path.to_str().unwrap()]
+                .iter()
+                .chain(args.iter()),
+        )
+        .get_query()
+        .unwrap();
+        fs::remove_file(&path).unwrap();
+        q.unwrap()
+    }
+    fn selected(q: &Query, values: &[i64]) -> Vec<i64> {
+        values
+            .iter()
+            .copied()
+            .filter(|&i| q.eval(&json!({ "i": i })).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn layer_inline_on_file() {
+        let q = with_base(
+            "layer-and",
+            "/i gt 1",
+            &[
+                "-r",
+                r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"lt","value":{"type":"number","value":5}}}}}"#,
+            ],
+        );
+        assert_eq!(vec![2, 3, 4], selected(&q, &[0, 1, 2, 3, 4, 5, 9]));
+    }
+    #[test]
+    fn layer_inline_on_file_combine_or() {
+        let q = with_base(
+            "layer-or",
+            "/i gt 1",
+            &["-e", "/i lt 3", "-e", "/i eq 9", "--combine", "or"],
+        );
+        assert_eq!(vec![2, 9], selected(&q, &[0, 1, 2, 3, 5, 9]));
+    }
+}
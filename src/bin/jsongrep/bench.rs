@@ -0,0 +1,110 @@
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::query::{EvaluableQuery, Query};
+use jsongrep::raw_query::Query as RawQuery;
+use serde_json::from_str;
+use serde_json::value::Value;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+/// Measure the throughput of a query.
+///
+/// Report lines/sec, bytes/sec and the time of each stage:
+/// parse (json parsing), eval (evaluating the query, including resolving its pointers)
+/// and output (writing the matched lines to nowhere).
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct BenchOpt {
+    /// Query file.
+    #[structopt(short = "q", long = "query")]
+    query: PathBuf,
+    /// Input file, a json per line.
+    #[structopt(short = "i", long = "input")]
+    input: PathBuf,
+    /// The number of times to process the input.
+    #[structopt(short = "n", long = "iterations", default_value = "1")]
+    iterations: usize,
+}
+
+/// Elapsed time of the stages.
+#[derive(Default)]
+struct Stages {
+    parse: Duration,
+    eval: Duration,
+    output: Duration,
+}
+
+impl BenchOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let io_err = |x| Error::new(ErrorCode::Io(x));
+        let q = fs::read_to_string(&self.query)
+            .map_err(io_err)
+            .and_then(|x| RawQuery::try_from(&x as &str))
+            .and_then(Query::try_from)?;
+        let input = fs::read_to_string(&self.input).map_err(io_err)?;
+        let lines: Vec<&str> = input.lines().collect();
+        let c = q.to_closure();
+        let mut out = io::sink();
+        let mut stages = Stages::default();
+        let (mut matched, mut errors) = (0, 0);
+        let start = Instant::now();
+        for _ in 0..self.iterations {
+            for line in &lines {
+                let t = Instant::now();
+                let v: Value = match from_str(line) {
+                    Ok(x) => x,
+                    Err(_) => {
+                        stages.parse += t.elapsed();
+                        errors += 1;
+                        continue;
+                    }
+                };
+                stages.parse += t.elapsed();
+                let t = Instant::now();
+                let r = c.eval(&v);
+                stages.eval += t.elapsed();
+                match r {
+                    Ok(true) => {
+                        matched += 1;
+                        let t = Instant::now();
+                        writeln!(out, "{}", line).map_err(io_err)?;
+                        stages.output += t.elapsed();
+                    }
+                    Ok(false) => {}
+                    Err(_) => errors += 1,
+                }
+            }
+        }
+        let elapsed = start.elapsed();
+        let n = lines.len() * self.iterations;
+        let bytes = input.len() * self.iterations;
+        let secs = elapsed.as_secs_f64();
+        println!(
+            "lines: {} ({} x {} iterations)",
+            n,
+            lines.len(),
+            self.iterations
+        );
+        println!("matched: {}, errors: {}", matched, errors);
+        println!("elapsed: {:.6}s", secs);
+        println!("lines/sec: {:.0}", n as f64 / secs);
+        println!("bytes/sec: {:.0}", bytes as f64 / secs);
+        println!("{:8} {:>12} {:>7}", "stage", "time(s)", "ratio");
+        for (name, d) in &[
+            ("parse", stages.parse),
+            ("eval", stages.eval),
+            ("output", stages.output),
+        ] {
+            println!(
+                "{:8} {:>12.6} {:>6.1}%",
+                name,
+                d.as_secs_f64(),
+                d.as_secs_f64() / secs * 100.0
+            );
+        }
+        Ok(())
+    }
+}
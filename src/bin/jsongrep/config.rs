@@ -0,0 +1,118 @@
+use crate::args::read_file;
+use crate::pipeline::StageConfig;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::raw_query::Query as RawQuery;
+use jsongrep::raw_sort::Sort as RawSort;
+use serde::Deserialize;
+use std::env;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+const QUERY_FILE_ENV: &str = "JSONGREP_QUERY_FILE";
+const SORT_FILE_ENV: &str = "JSONGREP_SORT_FILE";
+
+/// Settings of a whole grep job, the same as the options of `grep`.
+///
+/// ```yaml
+/// query: {"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}
+/// sort: {"sort":[{"p":"/i","ord":"desc"}]}
+/// input: in.ndjson
+/// output: out.ndjson
+/// threads: 4
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) query: Option<RawQuery>,
+    pub(crate) expr: Option<String>,
+    #[serde(default)]
+    pub(crate) mismatch_as_false: bool,
+    pub(crate) sort: Option<RawSort>,
+    pub(crate) input: Option<PathBuf>,
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) output_format: Option<String>,
+    pub(crate) max_errors: Option<usize>,
+    pub(crate) errors_out: Option<PathBuf>,
+    pub(crate) error_format: Option<String>,
+    #[serde(default)]
+    pub(crate) error_dedup: bool,
+    #[serde(default)]
+    pub(crate) error_summary: bool,
+    pub(crate) threads: Option<usize>,
+    pub(crate) flush_every: Option<usize>,
+    #[serde(default)]
+    pub(crate) line_buffered: bool,
+    #[serde(default)]
+    pub(crate) progress: bool,
+    #[serde(default)]
+    pub(crate) fail_on_no_match: bool,
+    #[serde(default)]
+    pub(crate) fail_on_error_lines: bool,
+    /// Stages to pass the selected jsons through instead of `sort` and `route`.
+    pub(crate) pipeline: Option<Vec<StageConfig>>,
+    /// Query file read only if no query is given, from the environment variable.
+    #[serde(skip)]
+    pub(crate) query_file: Option<PathBuf>,
+    /// Sort file read only if no sort is given, from the environment variable.
+    #[serde(skip)]
+    pub(crate) sort_file: Option<PathBuf>,
+}
+
+impl Config {
+    /// Read the config file, yaml if the extension is `yaml` or `yml`, toml if `toml`, otherwise json.
+    /// The relative paths in the config are resolved from the directory of the config.
+    pub(crate) fn load(path: &Path) -> Result<Config> {
+        info!(path = %path.display(), "load config");
+        let s = read_file(path)?;
+        let invalid = |x: String| {
+            Error::new(ErrorCode::InvalidOption(format!(
+                "invalid config {}: {}",
+                path.display(),
+                x
+            )))
+        };
+        let mut c: Config = match path.extension().and_then(|x| x.to_str()) {
+            Some("yaml" | "yml") => serde_yaml::from_str(&s).map_err(|x| invalid(x.to_string()))?,
+            Some("toml") => toml::from_str(&s).map_err(|x| invalid(x.to_string()))?,
+            _ => serde_json::from_str(&s).map_err(|x| invalid(x.to_string()))?,
+        };
+        if c.query.is_some() && c.expr.is_some() {
+            return Err(invalid("query and expr are exclusive".to_owned()));
+        }
+        // the paths in the config are relative to the config
+        if let Some(base) = path.parent() {
+            for x in [&mut c.input, &mut c.output, &mut c.errors_out] {
+                if let Some(p) = x.as_mut().filter(|p| p.is_relative()) {
+                    *p = base.join(&p);
+                }
+            }
+        }
+        Ok(c)
+    }
+    /// Return the query and the sort files specified by `JSONGREP_QUERY_FILE` and `JSONGREP_SORT_FILE`,
+    /// they are read later only if the query or the sort is not given by the others.
+    pub(crate) fn from_env() -> Config {
+        let path = |key| {
+            env::var_os(key)
+                .filter(|x| !x.is_empty())
+                .map(PathBuf::from)
+        };
+        Config {
+            query_file: path(QUERY_FILE_ENV),
+            sort_file: path(SORT_FILE_ENV),
+            ..Config::default()
+        }
+    }
+    /// Read the user config, `$XDG_CONFIG_HOME/jsongrep/config.toml`
+    /// or `~/.config/jsongrep/config.toml`, `None` if not exists.
+    pub(crate) fn user() -> Result<Option<Config>> {
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|x| PathBuf::from(x).join(".config")));
+        match dir.map(|x| x.join("jsongrep").join("config.toml")) {
+            Some(x) if x.is_file() => Config::load(&x).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
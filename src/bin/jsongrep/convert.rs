@@ -0,0 +1,43 @@
+use crate::args::io_err;
+use crate::grep::read_line;
+use jsongrep::error::{Error, ErrorCode, Result};
+use serde_json::value::Value;
+use serde_json::{from_reader, from_str, to_string, to_writer};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use structopt::StructOpt;
+
+/// Convert the jsons of stdin between a json per line and a json array.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct ConvertOpt {
+    /// Output format.
+    ///
+    /// lines: read a json array, write the elements one per line.
+    /// array: read a json per line, write them as a json array.
+    #[structopt(long = "to", default_value = "lines", possible_values = &["lines", "array"])]
+    to: String,
+}
+
+impl ConvertOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let json_err = |x| Error::new(ErrorCode::Json(x));
+        let mut stdin = io::stdin().lock();
+        let mut out = BufWriter::new(io::stdout().lock());
+        if self.to == "lines" {
+            let values: Vec<Value> = from_reader(stdin).map_err(json_err)?;
+            for v in values {
+                writeln!(out, "{}", to_string(&v).map_err(json_err)?).map_err(io_err)?;
+            }
+        } else {
+            let mut values = Vec::new();
+            let mut buf = String::new();
+            while read_line(&mut stdin, &mut buf).map_err(io_err)? {
+                values.push(from_str::<Value>(&buf).map_err(json_err)?);
+            }
+            to_writer(&mut out, &values).map_err(json_err)?;
+            writeln!(out).map_err(io_err)?;
+        }
+        out.flush().map_err(io_err)
+    }
+}
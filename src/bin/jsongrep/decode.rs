@@ -0,0 +1,157 @@
+use crate::enrich::set;
+use crate::infer::type_name;
+use base64::Engine;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::pointer::Pointer;
+use serde_json::value::Value;
+
+/// Encoding of a string field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    /// Standard base64, with or without the padding.
+    Base64,
+    Hex,
+    /// Percent-encoding, `+` as a space.
+    Url,
+    /// The string itself.
+    Plain,
+}
+
+/// Decoding of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Decoding {
+    encoding: Encoding,
+    /// Parse the decoded string as json.
+    json: bool,
+}
+
+impl Decoding {
+    /// Parse `base64`, `hex`, `url` or `json`, the first three suffixed by `-json` to parse the decoded string.
+    pub(crate) fn parse(s: &str) -> Option<Decoding> {
+        let (name, json) = match s.strip_suffix("-json") {
+            Some(x) => (x, true),
+            None => (s, false),
+        };
+        let encoding = match name {
+            "base64" => Encoding::Base64,
+            "hex" => Encoding::Hex,
+            "url" => Encoding::Url,
+            "json" if !json => Encoding::Plain,
+            _ => return None,
+        };
+        Some(Decoding {
+            encoding,
+            json: json || encoding == Encoding::Plain,
+        })
+    }
+    fn decode(&self, s: &str) -> Option<Value> {
+        let b = match self.encoding {
+            Encoding::Base64 => base64::engine::general_purpose::STANDARD_NO_PAD
+                .decode(s.trim_end_matches('='))
+                .ok()?,
+            Encoding::Hex => hex(s)?,
+            Encoding::Url => percent_encoding::percent_decode_str(&s.replace('+', " ")).collect(),
+            Encoding::Plain => s.as_bytes().to_vec(),
+        };
+        let s = String::from_utf8(b).ok()?;
+        if self.json {
+            serde_json::from_str(&s).ok()
+        } else {
+            Some(Value::String(s))
+        }
+    }
+}
+
+fn hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Replace the encoded string fields of the jsons by the decoded values.
+pub(crate) struct Decoder {
+    fields: Vec<(Pointer, Decoding)>,
+}
+
+impl Decoder {
+    pub(crate) fn new(fields: Vec<(Pointer, Decoding)>) -> Decoder {
+        Decoder { fields }
+    }
+    /// Decode the fields of `v`, the missing fields are left missing.
+    /// Return `Err` if a field is not a string or cannot be decoded.
+    pub(crate) fn decode(&self, v: &mut Value) -> Result<()> {
+        for (p, d) in &self.fields {
+            let x = match p.get(v) {
+                None => continue,
+                Some(Value::String(x)) => d.decode(x).ok_or_else(|| {
+                    Error::new(ErrorCode::InvalidTarget {
+                        pointer: p.to_string(),
+                        value: x.clone(),
+                    })
+                })?,
+                Some(x) => {
+                    return Err(Error::new(ErrorCode::TypeMismatch {
+                        got: type_name(x).to_owned(),
+                        want: "string".to_owned(),
+                        by: format!("decode {}", p),
+                    }))
+                }
+            };
+            set(v, p, x)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_decodings() {
+        let d = |encoding, json| Some(Decoding { encoding, json });
+        assert_eq!(d(Encoding::Base64, false), Decoding::parse("base64"));
+        assert_eq!(d(Encoding::Base64, true), Decoding::parse("base64-json"));
+        assert_eq!(d(Encoding::Hex, true), Decoding::parse("hex-json"));
+        assert_eq!(d(Encoding::Url, false), Decoding::parse("url"));
+        assert_eq!(d(Encoding::Plain, true), Decoding::parse("json"));
+        assert_eq!(None, Decoding::parse("json-json"));
+        assert_eq!(None, Decoding::parse("gzip"));
+    }
+    #[test]
+    fn decode_hex() {
+        assert_eq!(Some(b"ab".to_vec()), hex("6162"));
+        assert_eq!(Some(vec![0x6a, 0xff]), hex("6AfF"));
+        assert_eq!(Some(vec![]), hex(""));
+        assert_eq!(None, hex("616"));
+        assert_eq!(None, hex("zz"));
+        assert_eq!(None, hex("é1"));
+    }
+    #[test]
+    fn decode_fields() {
+        let d = Decoder::new(vec![
+            (
+                Pointer::parse("/b").unwrap(),
+                Decoding::parse("base64-json").unwrap(),
+            ),
+            (
+                Pointer::parse("/u").unwrap(),
+                Decoding::parse("url").unwrap(),
+            ),
+            (
+                Pointer::parse("/m").unwrap(),
+                Decoding::parse("hex").unwrap(),
+            ),
+        ]);
+        let mut v = json!({"b":"eyJhIjoxfQ","u":"a%2Fb+c"});
+        d.decode(&mut v).unwrap();
+        assert_eq!(json!({"b":{"a":1},"u":"a/b c"}), v);
+        assert!(d.decode(&mut json!({"b":1})).is_err());
+        assert!(d.decode(&mut json!({"b":"!"})).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use jsongrep::pointer::Pointer;
+use serde_json::value::Value;
+use serde_json::{from_str, to_string};
+use std::collections::{HashMap, VecDeque};
+
+/// Drop the jsons whose keys have been seen.
+///
+/// Remember the keys up to `capacity` if given, forgetting the least recently seen ones.
+pub(crate) struct Dedup {
+    pointers: Vec<Pointer>,
+    capacity: Option<usize>,
+    /// Key -> the generation it was seen last.
+    seen: HashMap<String, u64>,
+    /// Keys in order of being seen, with the generations, to evict.
+    order: VecDeque<(String, u64)>,
+    generation: u64,
+}
+
+impl Dedup {
+    pub(crate) fn new(pointers: Vec<Pointer>, capacity: Option<usize>) -> Dedup {
+        Dedup {
+            pointers,
+            capacity,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+            generation: 0,
+        }
+    }
+    /// Return true if the key of `line` is seen for the first time, `v` is `line` if parsed.
+    /// The missing values are keyed as null, the line not json is always new.
+    pub(crate) fn is_new(&mut self, line: &str, v: Option<&Value>) -> bool {
+        let parsed;
+        let v = match v {
+            Some(x) => x,
+            None => match from_str::<Value>(line) {
+                Ok(x) => {
+                    parsed = x;
+                    &parsed
+                }
+                Err(_) => return true,
+            },
+        };
+        let key: Vec<_> = self
+            .pointers
+            .iter()
+            .map(|p| p.get(v).unwrap_or(&Value::Null))
+            .collect();
+        let key = to_string(&key).unwrap();
+        self.generation += 1;
+        let new = self.seen.insert(key.clone(), self.generation).is_none();
+        if let Some(c) = self.capacity {
+            self.order.push_back((key, self.generation));
+            while self.seen.len() > c {
+                let (k, g) = self.order.pop_front().unwrap();
+                // skip the stale entries of the keys seen again later
+                if self.seen.get(&k) == Some(&g) {
+                    self.seen.remove(&k);
+                }
+            }
+            // drop the stale entries of the repeated keys not to grow without bound
+            if self.order.len() > 2 * c {
+                let seen = &self.seen;
+                self.order.retain(|(k, g)| seen.get(k) == Some(g));
+            }
+        }
+        new
+    }
+}
+
+/// Drop the jsons equal to the immediately preceding ones, like `uniq`.
+///
+/// Compare the values at the pointer if given, otherwise the whole jsons.
+pub(crate) struct Uniq {
+    pointer: Option<Pointer>,
+    /// Key of the preceding json.
+    last: Option<String>,
+}
+
+impl Uniq {
+    pub(crate) fn new(pointer: Option<Pointer>) -> Uniq {
+        Uniq {
+            pointer,
+            last: None,
+        }
+    }
+    /// Return true if the key of `line` differs from the preceding one.
+    /// The missing value is keyed as null, the line not json is keyed as is.
+    pub(crate) fn is_new(&mut self, line: &str) -> bool {
+        let key = match from_str::<Value>(line) {
+            Ok(v) => {
+                let v = match self.pointer.as_ref() {
+                    Some(p) => p.get(&v).unwrap_or(&Value::Null),
+                    None => &v,
+                };
+                to_string(v).unwrap()
+            }
+            Err(_) => line.to_owned(),
+        };
+        if self.last.as_deref() == Some(key.as_str()) {
+            return false;
+        }
+        self.last = Some(key);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dedup(capacity: Option<usize>) -> Dedup {
+        Dedup::new(vec![Pointer::parse("/k").unwrap()], capacity)
+    }
+
+    #[test]
+    fn dedup_keys() {
+        let mut d = dedup(None);
+        let got: Vec<_> = [
+            r#"{"k":1}"#,
+            r#"{"k":2,"x":0}"#,
+            r#"{"k":1,"x":1}"#,
+            "{}",
+            "{}",
+            "x",
+            "x",
+        ]
+        .iter()
+        .map(|x| d.is_new(x, None))
+        .collect();
+        assert_eq!(vec![true, true, false, true, false, true, true], got);
+    }
+    #[test]
+    fn dedup_capacity() {
+        let mut d = dedup(Some(2));
+        let got: Vec<_> = [
+            r#"{"k":1}"#,
+            r#"{"k":2}"#,
+            r#"{"k":3}"#,
+            r#"{"k":1}"#,
+            r#"{"k":3}"#,
+        ]
+        .iter()
+        .map(|x| d.is_new(x, None))
+        .collect();
+        assert_eq!(vec![true, true, true, true, false], got);
+    }
+    #[test]
+    fn dedup_repeated_keys_bounded() {
+        let mut d = dedup(Some(2));
+        for _ in 0..100 {
+            d.is_new(r#"{"k":1}"#, None);
+            d.is_new(r#"{"k":2}"#, None);
+        }
+        assert!(d.order.len() <= 4);
+        assert!(!d.is_new(r#"{"k":1}"#, None));
+    }
+    #[test]
+    fn uniq_lines() {
+        let mut u = Uniq::new(None);
+        let got: Vec<_> = [r#"{"a":1}"#, r#"{"a":1}"#, r#"{"a":2}"#, r#"{"a":1}"#]
+            .iter()
+            .map(|x| u.is_new(x))
+            .collect();
+        assert_eq!(vec![true, false, true, true], got);
+    }
+    #[test]
+    fn uniq_keys() {
+        let mut u = Uniq::new(Some(Pointer::parse("/k").unwrap()));
+        let got: Vec<_> = [r#"{"k":1,"x":0}"#, r#"{"k":1,"x":1}"#, r#"{"x":2}"#, "{}"]
+            .iter()
+            .map(|x| u.is_new(x))
+            .collect();
+        assert_eq!(vec![true, false, true, false], got);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::infer::type_name;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::pointer::Pointer;
+use serde_json::value::{Map, Value};
+use std::time::SystemTime;
+
+/// Add the fields to the jsons.
+pub(crate) struct Enricher {
+    /// Constant values.
+    fields: Vec<(Pointer, Value)>,
+    /// Pointer of the time of writing each json.
+    timestamp: Option<Pointer>,
+    /// Pointer of the id of each json.
+    id: Option<(Pointer, Ids)>,
+}
+
+/// Identifiers of the jsons.
+pub(crate) enum Ids {
+    /// Sequential numbers from the value.
+    Seq(u64),
+    /// Random UUIDs (version 4).
+    Uuid(fastrand::Rng),
+}
+
+impl Ids {
+    fn next(&mut self) -> Value {
+        match self {
+            Ids::Seq(n) => {
+                let x = *n;
+                *n += 1;
+                Value::from(x)
+            }
+            Ids::Uuid(rng) => {
+                let mut b = rng.u128(..).to_be_bytes();
+                b[6] = (b[6] & 0x0f) | 0x40;
+                b[8] = (b[8] & 0x3f) | 0x80;
+                let h: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+                Value::String(format!(
+                    "{}-{}-{}-{}-{}",
+                    &h[..8],
+                    &h[8..12],
+                    &h[12..16],
+                    &h[16..20],
+                    &h[20..]
+                ))
+            }
+        }
+    }
+}
+
+impl Enricher {
+    pub(crate) fn new(
+        fields: Vec<(Pointer, Value)>,
+        timestamp: Option<Pointer>,
+        id: Option<(Pointer, Ids)>,
+    ) -> Enricher {
+        Enricher {
+            fields,
+            timestamp,
+            id,
+        }
+    }
+    /// Set the fields of `v`, overwriting the existing values.
+    pub(crate) fn enrich(&mut self, v: &mut Value) -> Result<()> {
+        for (p, x) in &self.fields {
+            set(v, p, x.clone())?;
+        }
+        if let Some(p) = &self.timestamp {
+            let now = humantime::format_rfc3339_millis(SystemTime::now());
+            set(v, p, Value::String(now.to_string()))?;
+        }
+        if let Some((p, ids)) = self.id.as_mut() {
+            set(v, p, ids.next())?;
+        }
+        Ok(())
+    }
+}
+
+/// Set the value at `pointer`, creating the missing objects on the path.
+///
+/// An array element can be replaced but not appended.
+pub(crate) fn set(v: &mut Value, pointer: &Pointer, x: Value) -> Result<()> {
+    let mismatch = |got: &str| {
+        Error::new(ErrorCode::TypeMismatch {
+            got: got.to_owned(),
+            want: "object".to_owned(),
+            by: pointer.to_string(),
+        })
+    };
+    let mut target = v;
+    for token in pointer.tokens() {
+        target = match target {
+            Value::Object(m) => m
+                .entry(token.as_str())
+                .or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(a) => match token.index() {
+                Some(i) if i < a.len() => &mut a[i],
+                _ => return Err(mismatch("array")),
+            },
+            x => return Err(mismatch(type_name(x))),
+        };
+    }
+    *target = x;
+    Ok(())
+}
+
+/// Parse the value of KEY=VALUE, json if valid, otherwise the string itself.
+pub(crate) fn parse_value(s: &str) -> Value {
+    serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.to_owned()))
+}
@@ -0,0 +1,41 @@
+use crate::args::QueryArgs;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::query::Query;
+use serde_json::value::Value;
+use serde_json::{json, to_string_pretty};
+use structopt::StructOpt;
+
+/// Show how the query is evaluated.
+///
+/// Write the query after the optimization, e.g. flattened and reordered conditions,
+/// and the pointers read by the query in order of evaluation.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct ExplainOpt {
+    #[structopt(flatten)]
+    query: QueryArgs,
+}
+
+impl ExplainOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let q = self.query.get_query().unwrap_or_else(|| {
+            Err(Error::new(ErrorCode::InvalidOption(
+                "query is required".to_owned(),
+            )))
+        })?;
+        print_pretty(&describe(&q))
+    }
+}
+
+/// Return the optimized query and the pointers it reads.
+pub(crate) fn describe(q: &Query) -> Value {
+    let pointers: Vec<_> = q.pointers().iter().map(|x| x.to_string()).collect();
+    json!({ "query": q, "pointers": pointers })
+}
+
+pub(crate) fn print_pretty(v: &Value) -> Result<()> {
+    println!(
+        "{}",
+        to_string_pretty(v).map_err(|x| Error::new(ErrorCode::Json(x)))?
+    );
+    Ok(())
+}
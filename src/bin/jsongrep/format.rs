@@ -0,0 +1,85 @@
+#[cfg(feature = "parquet")]
+use crate::parquet_out::ParquetWriter;
+#[cfg(feature = "sqlite")]
+use crate::sqlite_out::SqliteWriter;
+use crate::table::Table;
+use serde_json::{from_str, Value};
+use std::convert::TryFrom;
+use std::io;
+use std::io::prelude::*;
+
+/// Format of the selected lines written to the output.
+pub(crate) enum Format {
+    /// A json per line.
+    Ndjson,
+    /// The elements of a single json array.
+    JsonArray,
+    /// The rows of a text table.
+    Table(Table),
+    /// MessagePack records, each prefixed by its length in 4 bytes big endian.
+    Msgpack,
+    /// CBOR sequence, RFC 8742.
+    Cbor,
+    /// Parquet file, not written to the output.
+    #[cfg(feature = "parquet")]
+    Parquet(Box<ParquetWriter>),
+    /// Rows of a SQLite table, not written to the output.
+    #[cfg(feature = "sqlite")]
+    Sqlite(Box<SqliteWriter>),
+}
+
+impl Format {
+    /// Write the line following the `written` lines.
+    pub(crate) fn write(
+        &mut self,
+        written: usize,
+        line: &str,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        match self {
+            Format::Ndjson => writeln!(out, "{}", line),
+            Format::JsonArray => {
+                let sep = if written == 0 { "[" } else { "," };
+                write!(out, "{}\n{}", sep, line)
+            }
+            Format::Table(t) => t.add(line, out),
+            Format::Msgpack => {
+                let b = rmp_serde::to_vec(&parse(line)?).map_err(invalid_data)?;
+                let len = u32::try_from(b.len()).map_err(invalid_data)?;
+                out.write_all(&len.to_be_bytes())?;
+                out.write_all(&b)
+            }
+            Format::Cbor => ciborium::into_writer(&parse(line)?, out).map_err(|e| match e {
+                ciborium::ser::Error::Io(x) => x,
+                x => invalid_data(x),
+            }),
+            #[cfg(feature = "parquet")]
+            Format::Parquet(w) => w.write(line),
+            #[cfg(feature = "sqlite")]
+            Format::Sqlite(w) => w.write(line),
+        }
+    }
+    /// Write the rest after the `written` lines.
+    pub(crate) fn finish(&mut self, written: usize, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Format::JsonArray => {
+                let open = if written == 0 { "[" } else { "" };
+                writeln!(out, "{}\n]", open)
+            }
+            Format::Table(t) => t.flush(out),
+            #[cfg(feature = "parquet")]
+            Format::Parquet(w) => w.finish(),
+            #[cfg(feature = "sqlite")]
+            Format::Sqlite(w) => w.commit(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn parse(line: &str) -> io::Result<Value> {
+    from_str(line).map_err(invalid_data)
+}
+
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
@@ -0,0 +1,1496 @@
+use crate::annotate::Annotator;
+use crate::args::{check_sort, io_err, load_query, selector, PageArgs, QueryArgs, SortArgs};
+use crate::config::Config;
+use crate::decode::{Decoder, Decoding};
+use crate::dedup;
+use crate::enrich::{parse_value, Enricher, Ids};
+use crate::explain;
+use crate::format::Format;
+use crate::index::{Entry, Index};
+use crate::infer::type_name;
+use crate::partition::Partitions;
+use crate::pipeline;
+use crate::pipeline::StageConfig;
+use crate::progress::{CountingReader, Progress};
+use crate::redact::{sha256, Redactor};
+use crate::rows::Rows;
+use crate::split::{parse_size, SplitWriter};
+use crate::table::Table;
+use crate::timeout::Watchdog;
+use jsongrep::error::{Error, ErrorCode, ErrorKind, Result};
+use jsongrep::pointer;
+use jsongrep::pointer::Pointer;
+use jsongrep::query::Query;
+use jsongrep::raw_sort::Sort as RawSort;
+use jsongrep::select::Query as Selector;
+use jsongrep::sort::Sort;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use regex::Regex;
+use serde::de::IgnoredAny;
+use serde::Serialize;
+use serde_json::value::Value;
+use serde_json::{from_str, json, to_string};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, SeekFrom};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Instant;
+use structopt::StructOpt;
+use tracing::info;
+
+/// Select the lines of stdin by the query, the default command.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct GrepOpt {
+    #[structopt(flatten)]
+    query: QueryArgs,
+    #[structopt(flatten)]
+    sort: SortArgs,
+    #[structopt(flatten)]
+    page: PageArgs,
+    /// Read the settings from this file, json or yaml by the extension.
+    ///
+    /// The keys are the same as the long names of the options, e.g.
+    /// {"query":{...},"sort":{...},"input":"in.ndjson","output":"out.ndjson","threads":4}.
+    /// `query` and `sort` are json objects, the same as `raw_query` and `raw_sort`.
+    /// `pipeline` is a list of stages run in order instead of `sort`,
+    /// e.g. [{"type":"expr","expr":"/code ge 500"},{"type":"aggregate","by":["/code"]},{"type":"limit","n":10}],
+    /// the types are filter, expr, project, aggregate, sort and limit.
+    ///
+    /// The options on command line take precedence,
+    /// and this takes precedence over JSONGREP_QUERY_FILE, JSONGREP_SORT_FILE
+    /// and the user config ~/.config/jsongrep/config.toml.
+    /// The relative paths in the file are resolved from the directory of the file.
+    #[structopt(long = "config")]
+    config: Option<PathBuf>,
+    /// Read the lines from this file instead of stdin.
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+    /// Write the selected lines to this file instead of stdout.
+    #[structopt(long = "output")]
+    output: Option<PathBuf>,
+    /// Format of the selected lines.
+    ///
+    /// ndjson: a json per line, by default.
+    /// json-array: a single json array of the selected jsons, written as they are selected.
+    /// table: an aligned text table of the values at `columns`.
+    /// msgpack: MessagePack records, each prefixed by its length in 4 bytes big endian.
+    /// cbor: CBOR sequence (RFC 8742), the CBOR items concatenated.
+    /// parquet: Parquet file of `output`, requires the parquet feature.
+    /// sqlite: rows of `sqlite_table` of the SQLite database of `output`, the values at `columns`
+    /// and the whole json as `json`, requires the sqlite feature.
+    ///
+    /// Exclusive with the splits and `pipeline`.
+    #[structopt(
+        long = "output_format",
+        possible_values = &["ndjson", "json-array", "table", "msgpack", "cbor", "parquet", "sqlite"]
+    )]
+    output_format: Option<String>,
+    /// Infer the columns of `output_format` parquet from this number of the first selected jsons.
+    ///
+    /// A column is the value at a pointer into the objects, of the type seen in the sample,
+    /// the mixed types as json strings. The values of the other types are written as null.
+    #[structopt(long = "schema_from_sample", default_value = "1000")]
+    schema_from_sample: usize,
+    /// Pointers of the columns of `output_format` table and sqlite, separated by commas, e.g. /a,/b.
+    #[structopt(long = "columns")]
+    columns: Option<String>,
+    /// Table of `output_format` sqlite, created if missing, `matches` by default.
+    #[structopt(long = "sqlite_table")]
+    sqlite_table: Option<String>,
+    /// Truncate the cells of the table longer than this number of characters.
+    #[structopt(long = "column_width")]
+    column_width: Option<usize>,
+    /// Compute the widths of the columns of the table over this number of rows before writing them.
+    ///
+    /// The widths only grow for the following rows.
+    #[structopt(long = "table_window", default_value = "100")]
+    table_window: usize,
+    /// Write the selected lines into the files of at most this number of lines,
+    /// named `out_prefix` and the numbers, e.g. part-00000.ndjson, part-00001.ndjson.
+    ///
+    /// Exclusive with `output`.
+    #[structopt(long = "split_lines")]
+    split_lines: Option<usize>,
+    /// Write the selected lines into the files of at most this size, e.g. 256MB, 1G.
+    ///
+    /// K, M and G are powers of 1024, KB, MB and GB are of 1000.
+    /// A line is never split. Can be combined with `split_lines`.
+    #[structopt(long = "split_bytes", parse(try_from_str = parse_size))]
+    split_bytes: Option<u64>,
+    /// Prefix of the files of `split_lines` and `split_bytes`, can include a directory.
+    #[structopt(long = "out_prefix", default_value = "part-")]
+    out_prefix: String,
+    /// Write each selected line into `out_dir`/VALUE.ndjson by the value at this pointer.
+    ///
+    /// A string is named by itself, the others by their json prefixed by `=`, e.g. `=1`,
+    /// a missing value as null.
+    /// The characters other than alphanumerics, `-`, `_` and `.` are percent-encoded.
+    /// At most 128 files are kept open, the others are reopened to append.
+    /// Exclusive with the sort, `route`, `output` and the splits.
+    #[structopt(long = "partition_by")]
+    partition_by: Option<String>,
+    /// Directory of the files of `partition_by`, created if missing.
+    #[structopt(long = "out_dir")]
+    out_dir: Option<PathBuf>,
+    /// Process only the lines of these 1-based indexes, e.g. 100-200,5000,9000-.
+    ///
+    /// The indexes are of the input lines by default, or of the selected lines by `rows_of`.
+    /// The input is not read beyond the last index.
+    #[structopt(long = "rows")]
+    rows: Option<Rows>,
+    /// What `rows` counts.
+    ///
+    /// input: the lines of the input, before the query, by default.
+    /// matched: the lines meeting the query.
+    #[structopt(long = "rows_of", possible_values = &["input", "matched"])]
+    rows_of: Option<String>,
+    /// Read only the leading json of each line, ignoring the content after it,
+    /// e.g. a plain text suffix or another json.
+    ///
+    /// The lines are written without the trailing content.
+    #[structopt(long = "ignore_trailing")]
+    ignore_trailing: bool,
+    /// Abort if the number of error lines exceeds this.
+    ///
+    /// The number of error lines is written to stderr at the end.
+    #[structopt(long = "max_errors")]
+    max_errors: Option<usize>,
+    /// Write the error lines verbatim to this file instead of writing errors to stderr.
+    #[structopt(long = "errors_out")]
+    errors_out: Option<PathBuf>,
+    /// Format of the errors written to stderr.
+    ///
+    /// text: human readable message, by default.
+    /// json: one line json object per error, like {"line":3,"code":"InvalidPointer","pointer":"/s",...}.
+    #[structopt(long = "error_format", possible_values = &["text", "json"])]
+    error_format: Option<String>,
+    /// Write the same errors only once with the running count,
+    /// and the total counts at the end.
+    ///
+    /// The errors are the same if they differ only in the values of the lines,
+    /// e.g. the errors from the same invalid pointer.
+    #[structopt(long = "error_dedup")]
+    error_dedup: bool,
+    /// Write the counts and the first line numbers of the error categories at the end.
+    #[structopt(long = "error_summary")]
+    error_summary: bool,
+    /// The number of threads to evaluate the lines and to sort them.
+    ///
+    /// The output keeps the order of the input.
+    /// 1 by default.
+    #[structopt(long = "threads")]
+    threads: Option<usize>,
+    /// Flush stdout every this number of lines.
+    ///
+    /// By default, stdout is flushed when the buffer is full.
+    #[structopt(long = "flush_every")]
+    flush_every: Option<usize>,
+    /// Flush stdout every line, same as `--flush_every 1`.
+    #[structopt(long = "line_buffered")]
+    line_buffered: bool,
+    /// Check the query and the sort, write them after the optimization and exit
+    /// without reading the input.
+    ///
+    /// Write {"query":...,"pointers":[...],"sort":...}, null if not specified.
+    #[structopt(long = "dry_run")]
+    dry_run: bool,
+    /// Write the progress to stderr: the bytes read, the ratio and the ETA if `input` is given,
+    /// and the number of the matched lines.
+    #[structopt(long = "progress")]
+    progress: bool,
+    /// Exit with 1 if no line is selected, like grep.
+    #[structopt(long = "fail_on_no_match")]
+    fail_on_no_match: bool,
+    /// Exit with 0 even if no line is selected, the default,
+    /// that overrides `fail_on_no_match` of the config.
+    #[structopt(long = "exit_zero_on_no_match")]
+    exit_zero_on_no_match: bool,
+    /// Exit with 2 if any line fails to be selected,
+    /// e.g. the line is not json or the type of the value mismatches the query.
+    ///
+    /// Takes precedence over `fail_on_no_match`.
+    #[structopt(long = "fail_on_error_lines")]
+    fail_on_error_lines: bool,
+    /// Abort if the whole run takes longer than this, e.g. 30s, 5m.
+    ///
+    /// Exit with 124 like timeout(1), the output not flushed yet is lost.
+    #[structopt(long = "timeout")]
+    timeout: Option<humantime::Duration>,
+    /// Abort if a line takes longer than this to evaluate, e.g. 100ms.
+    ///
+    /// Guard against the slow matchers on a single line, the line number is written to stderr.
+    /// Exit with 124 the same as `timeout`.
+    #[structopt(long = "line_timeout")]
+    line_timeout: Option<humantime::Duration>,
+    /// Route the selected lines by a query file, NAME=QUERY_FILE, can be specified multiple times.
+    ///
+    /// A line is written to the `route_out` of every route whose query it meets,
+    /// and to the output only if it meets none of them,
+    /// so that a single pass splits the input by the queries.
+    /// Exclusive with the sort.
+    #[structopt(long = "route", number_of_values = 1)]
+    route: Vec<String>,
+    /// Output file of a route, NAME=PATH, required for each `route`.
+    #[structopt(long = "route_out", number_of_values = 1)]
+    route_out: Vec<String>,
+    /// Drop the selected lines whose values at this pointer have been seen,
+    /// can be specified multiple times to dedup by the tuple of the values.
+    ///
+    /// A missing value is keyed as null.
+    #[structopt(long = "dedup_by", number_of_values = 1)]
+    dedup_by: Vec<String>,
+    /// Remember at most this number of the keys of `dedup_by`, forgetting the least recently seen ones.
+    ///
+    /// Unlimited by default.
+    #[structopt(long = "dedup_capacity")]
+    dedup_capacity: Option<usize>,
+    /// Drop the lines equal to the immediately preceding written line, like `uniq`,
+    /// on the value at this pointer if given.
+    ///
+    /// Compared after sorting, so the equal lines are dropped if sorted by the pointer.
+    #[structopt(long = "uniq")]
+    uniq: Option<Option<String>>,
+    /// Decode the string at the pointer of the selected lines, POINTER=ENCODING,
+    /// can be specified multiple times, e.g. /payload=base64-json.
+    ///
+    /// ENCODING: base64, hex, url (percent-encoding) or json (nested json string),
+    /// the first three suffixed by `-json` to parse the decoded string as json.
+    /// The decoded lines are written in the compact form with the keys sorted.
+    #[structopt(long = "decode", number_of_values = 1)]
+    decode: Vec<String>,
+    /// Replace the value at this pointer of the selected lines by "***",
+    /// can be specified multiple times.
+    ///
+    /// The redacted lines are written in the compact form with the keys sorted.
+    #[structopt(long = "redact", number_of_values = 1)]
+    redact: Vec<String>,
+    /// Replace the values at the pointers matching this regular expression, e.g. '^/headers/authorization$',
+    /// can be specified multiple times.
+    #[structopt(long = "redact_regex", number_of_values = 1)]
+    redact_regex: Vec<String>,
+    /// Replace the redacted values by the sha256 hex of their json instead of "***",
+    /// so that the same values can still be correlated.
+    #[structopt(long = "redact_hash")]
+    redact_hash: bool,
+    /// Add a field to the selected jsons, KEY=VALUE, can be specified multiple times.
+    ///
+    /// KEY is a top-level key, or a pointer if it starts with `/`.
+    /// VALUE is json if valid, otherwise a string, e.g. env=prod, /meta/rev=3.
+    /// The added lines are written in the compact form with the keys sorted.
+    #[structopt(long = "inject", number_of_values = 1)]
+    inject: Vec<String>,
+    /// Add the time of writing each selected json at this pointer in RFC 3339, e.g. /__ingested_at.
+    #[structopt(long = "inject_timestamp")]
+    inject_timestamp: Option<String>,
+    /// Add the hostname at this pointer, /__hostname if the pointer is omitted.
+    #[structopt(long = "inject_hostname")]
+    inject_hostname: Option<Option<String>>,
+    /// Add an id at this pointer to each selected json, e.g. /__id.
+    ///
+    /// The ids are given in order of the input, before sorting.
+    #[structopt(long = "assign_id")]
+    assign_id: Option<String>,
+    /// Kind of the ids of `assign_id`.
+    ///
+    /// seq: sequential numbers from `id_start`, by default.
+    /// uuid: random UUIDs (version 4), reproducible by `seed`.
+    #[structopt(long = "id_format", possible_values = &["seq", "uuid"])]
+    id_format: Option<String>,
+    /// The first id of `assign_id` in seq, 1 by default.
+    #[structopt(long = "id_start")]
+    id_start: Option<u64>,
+    /// Add the digest of each selected json as `__hash`, e.g. sha256.
+    ///
+    /// The digest is of the compact json with the keys sorted, after `redact`,
+    /// and the lines are written in the same form.
+    /// The lines not objects are errors.
+    #[structopt(long = "hash", possible_values = &["sha256"])]
+    hash: Option<String>,
+    /// Write only this fraction of the selected lines at random, e.g. 0.1.
+    ///
+    /// The dropped lines are not counted as matched.
+    #[structopt(long = "output_sample")]
+    output_sample: Option<f64>,
+    /// Seed of `output_sample` to draw the same lines from the same input,
+    /// and of the uuids of `assign_id`.
+    #[structopt(long = "seed")]
+    seed: Option<u64>,
+    /// Wrap each selected json as {"record":..., "meta":{"line":..., "file":..., "matched_pairs":[...], "query":...}}.
+    ///
+    /// matched_pairs: pointers of the pairs of the query accepting the json.
+    /// query: stems of `query_file`, null if not given.
+    /// The annotated lines are written in the compact form with the keys sorted.
+    #[structopt(long = "annotate")]
+    annotate: bool,
+    /// Stages from `pipeline` of the config.
+    #[structopt(skip)]
+    pipeline: Option<Vec<StageConfig>>,
+}
+
+impl GrepOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        // command line > --config > environment variables > user config
+        let mut opt = self.clone();
+        if let Some(x) = &self.config {
+            opt = opt.with_config(Config::load(x)?);
+        }
+        opt = opt.with_config(Config::from_env());
+        if let Some(x) = Config::user()? {
+            opt = opt.with_config(x);
+        }
+        opt.validate()?;
+        // read the query and the sort only once, stdin and the pipes cannot be read again
+        let query = opt.query.compile()?;
+        let sort = opt.sort.get_raw_sort().transpose()?;
+        if opt.dry_run {
+            return opt.print_plan(query.as_ref(), sort);
+        }
+        if let Some(stages) = opt.pipeline.as_ref() {
+            return pipeline::run(
+                stages,
+                opt.get_threads(),
+                &selector(query.as_ref()),
+                opt.open_input()?,
+                BufWriter::new(opt.open_output()?),
+            );
+        }
+        let start = Instant::now();
+        let watchdog = Watchdog::start(
+            opt.timeout.map(Into::into),
+            opt.line_timeout.map(Into::into),
+        );
+        let threads = opt.get_threads();
+        let mut g = Grep {
+            selector: selector(query.as_ref()),
+            sort: sort.map(|x| Sort::from(x).with_threads(threads)),
+            page: opt.page.clone(),
+            lines: Vec::new(),
+            reporter: opt.get_reporter()?,
+            // evaluate sequentially if failed to build the pool, as the sort
+            pool: Some(threads)
+                .filter(|&n| n > 1)
+                .and_then(|n| ThreadPoolBuilder::new().num_threads(n).build().ok()),
+            out: BufWriter::new(opt.open_output()?),
+            format: opt.get_format()?,
+            flush_every: opt.get_flush_every(),
+            written: 0,
+            matched: 0,
+            watchdog,
+            routes: opt.get_routes()?,
+            dedup: opt.get_dedup()?,
+            uniq: opt.get_uniq()?,
+            decoder: opt.get_decoder()?,
+            redactor: opt.get_redactor()?,
+            enricher: opt.get_enricher()?,
+            annotator: opt.get_annotator(query.as_ref()),
+            hash: opt.hash.is_some(),
+            sample: opt.get_sample(),
+            partitions: opt.get_partitions()?,
+            input_rows: opt.rows.clone().filter(|_| !opt.rows_of_matched()),
+            matched_rows: opt.rows.clone().filter(|_| opt.rows_of_matched()),
+            selected: 0,
+        };
+        // evaluate line by line unless parallel not to delay the output
+        let batch_size = if threads > 1 {
+            threads * BATCH_SIZE_PER_THREAD
+        } else {
+            1
+        };
+        // reuse the buffers of the lines
+        let mut batch = vec![String::new(); batch_size];
+        let mut offset = 0;
+        let mut progress = if opt.progress {
+            Some(opt.new_progress()?)
+        } else {
+            None
+        };
+        if let Some((path, entries)) = opt.lookup_index(query.as_ref())? {
+            // read only the lines that may be selected
+            let mut input = BufReader::new(File::open(path).map_err(io_err)?);
+            for (line, at) in entries {
+                input.seek(SeekFrom::Start(at)).map_err(io_err)?;
+                read_line(&mut input, &mut batch[0]).map_err(io_err)?;
+                if opt.ignore_trailing {
+                    truncate_trailing(&mut batch[0]);
+                }
+                g.process(line - 1, &batch[..1])?;
+                offset += 1;
+            }
+        } else {
+            let mut input = opt.open_input()?;
+            if let Some(p) = progress.as_ref() {
+                input = Box::new(CountingReader::new(input, p.counter()));
+            }
+            loop {
+                let mut n = 0;
+                while n < batch_size && read_line(&mut input, &mut batch[n]).map_err(io_err)? {
+                    if opt.ignore_trailing {
+                        truncate_trailing(&mut batch[n]);
+                    }
+                    n += 1;
+                }
+                g.process(offset, &batch[..n])?;
+                if let Some(p) = progress.as_mut() {
+                    p.update(g.matched);
+                }
+                offset += n;
+                if n < batch_size || g.done(offset) {
+                    break;
+                }
+            }
+        }
+        info!(input = %opt.input_name(), lines = offset, "close input");
+        if let Some(p) = progress.as_mut() {
+            p.finish(g.matched);
+        }
+        let (matched, errors) = (g.matched, g.reporter.count);
+        g.finish()?;
+        info!(
+            lines = offset,
+            matched,
+            errors,
+            elapsed = ?start.elapsed(),
+            "finished"
+        );
+        if let Some(code) = opt.exit_code(matched, errors) {
+            process::exit(code);
+        }
+        Ok(())
+    }
+    /// Return the exit code by the policy if not 0.
+    fn exit_code(&self, matched: usize, errors: usize) -> Option<i32> {
+        if self.fail_on_error_lines && errors > 0 {
+            Some(2)
+        } else if self.fail_on_no_match && matched == 0 {
+            Some(1)
+        } else {
+            None
+        }
+    }
+    /// Fill the options not specified yet from `config`.
+    fn with_config(mut self, mut config: Config) -> Self {
+        self.query = self.query.with_config(&mut config);
+        self.sort = self.sort.with_config(&mut config);
+        self.input = self.input.or(config.input);
+        self.output = self.output.or(config.output);
+        self.output_format = self.output_format.or(config.output_format);
+        self.max_errors = self.max_errors.or(config.max_errors);
+        self.errors_out = self.errors_out.or(config.errors_out);
+        self.error_format = self.error_format.or(config.error_format);
+        self.error_dedup |= config.error_dedup;
+        self.error_summary |= config.error_summary;
+        self.progress |= config.progress;
+        self.pipeline = self.pipeline.or(config.pipeline);
+        if !self.exit_zero_on_no_match {
+            self.fail_on_no_match |= config.fail_on_no_match;
+        }
+        self.fail_on_error_lines |= config.fail_on_error_lines;
+        self.threads = self.threads.or(config.threads);
+        // flush_every and line_buffered are exclusive, the command line wins
+        if self.flush_every.is_none() && !self.line_buffered {
+            self.flush_every = config.flush_every;
+            self.line_buffered = config.line_buffered;
+        }
+        self
+    }
+    /// Write the compiled query and the sort.
+    fn print_plan(&self, query: Option<&Query>, sort: Option<RawSort>) -> Result<()> {
+        let mut v = match query {
+            Some(q) => explain::describe(q),
+            None => json!({ "query": null, "pointers": [] }),
+        };
+        v["sort"] = json!(sort.map(check_sort).transpose()?);
+        explain::print_pretty(&v)
+    }
+    /// Return the input and the lines to read if the index of the input is available for the query.
+    fn lookup_index(&self, query: Option<&Query>) -> Result<Option<(&PathBuf, Vec<Entry>)>> {
+        let input = match &self.input {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let index = match Index::load(input)? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let entries = query.and_then(|q| index.lookup(q));
+        if let Some(x) = entries.as_ref() {
+            info!(input = %input.display(), lines = x.len(), "use index");
+        }
+        Ok(entries.map(|x| (input, x)))
+    }
+    /// Return the routes opening their outputs.
+    fn get_routes(&self) -> Result<Vec<Route>> {
+        let invalid = |x: String| Error::new(ErrorCode::InvalidOption(x));
+        let pair = |x: &str| {
+            x.split_once('=')
+                .map(|(k, v)| (k.to_owned(), PathBuf::from(v)))
+                .ok_or_else(|| invalid(format!("{} should be NAME=PATH", x)))
+        };
+        let outs = self
+            .route_out
+            .iter()
+            .map(|x| pair(x))
+            .collect::<Result<HashMap<_, _>>>()?;
+        if outs.len() != self.route_out.len() {
+            return Err(invalid("route_out names should be unique".to_owned()));
+        }
+        let mut routes: Vec<Route> = Vec::new();
+        for x in &self.route {
+            let (name, path) = pair(x)?;
+            if routes.iter().any(|r| r.name == name) {
+                return Err(invalid(format!("route {} is given twice", name)));
+            }
+            let out = outs
+                .get(&name)
+                .ok_or_else(|| invalid(format!("route_out of {} is required", name)))?;
+            let q = Query::try_from(load_query(&path)?)?;
+            routes.push(Route {
+                selector: Selector::new(Box::new(q.to_closure())),
+                out: BufWriter::new(File::create(out).map_err(io_err)?),
+                name,
+                matched: 0,
+            });
+        }
+        if let Some(x) = outs.keys().find(|x| !routes.iter().any(|r| &&r.name == x)) {
+            return Err(invalid(format!("route {} is required by route_out", x)));
+        }
+        Ok(routes)
+    }
+    fn get_dedup(&self) -> Result<Option<dedup::Dedup>> {
+        if self.dedup_by.is_empty() {
+            return Ok(None);
+        }
+        let pointers = self
+            .dedup_by
+            .iter()
+            .map(|x| Pointer::parse(x))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(dedup::Dedup::new(pointers, self.dedup_capacity)))
+    }
+    fn get_uniq(&self) -> Result<Option<dedup::Uniq>> {
+        match &self.uniq {
+            None => Ok(None),
+            Some(x) => Ok(Some(dedup::Uniq::new(
+                x.as_deref().map(Pointer::parse).transpose()?,
+            ))),
+        }
+    }
+    fn get_decoder(&self) -> Result<Option<Decoder>> {
+        if self.decode.is_empty() {
+            return Ok(None);
+        }
+        let fields = self
+            .decode
+            .iter()
+            .map(|x| {
+                let invalid = || {
+                    Error::new(ErrorCode::InvalidOption(format!(
+                        "decode should be POINTER=ENCODING: {}",
+                        x
+                    )))
+                };
+                let (p, e) = x.rsplit_once('=').ok_or_else(invalid)?;
+                Ok((Pointer::parse(p)?, Decoding::parse(e).ok_or_else(invalid)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Decoder::new(fields)))
+    }
+    fn get_redactor(&self) -> Result<Option<Redactor>> {
+        if self.redact.is_empty() && self.redact_regex.is_empty() {
+            return Ok(None);
+        }
+        let pointers = self
+            .redact
+            .iter()
+            .map(|x| Pointer::parse(x))
+            .collect::<Result<Vec<_>>>()?;
+        let patterns = self
+            .redact_regex
+            .iter()
+            .map(|x| {
+                Regex::new(x).map_err(|e| {
+                    Error::new(ErrorCode::InvalidOption(format!("redact_regex: {}", e)))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Redactor::new(pointers, patterns, self.redact_hash)))
+    }
+    fn get_partitions(&self) -> Result<Option<Partitions>> {
+        let Some(p) = self.partition_by.as_ref() else {
+            return Ok(None);
+        };
+        let dir = self.out_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+        fs::create_dir_all(&dir).map_err(io_err)?;
+        Ok(Some(Partitions::new(Pointer::parse(p)?, dir)))
+    }
+    fn get_annotator(&self, query: Option<&Query>) -> Option<Annotator> {
+        if !self.annotate {
+            return None;
+        }
+        Some(Annotator::new(
+            query.cloned(),
+            self.input_name(),
+            self.query.query_name(),
+        ))
+    }
+    fn get_enricher(&self) -> Result<Option<Enricher>> {
+        if self.inject.is_empty()
+            && self.inject_timestamp.is_none()
+            && self.inject_hostname.is_none()
+            && self.assign_id.is_none()
+        {
+            return Ok(None);
+        }
+        let mut fields = self
+            .inject
+            .iter()
+            .map(|x| {
+                let (k, v) = x.split_once('=').ok_or_else(|| {
+                    Error::new(ErrorCode::InvalidOption(format!(
+                        "inject should be KEY=VALUE: {}",
+                        x
+                    )))
+                })?;
+                let p = if k.starts_with('/') {
+                    Pointer::parse(k)?
+                } else {
+                    Pointer::parse(&format!("/{}", pointer::escape(k)))?
+                };
+                Ok((p, parse_value(v)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(p) = &self.inject_hostname {
+            let host = gethostname::gethostname().to_string_lossy().into_owned();
+            fields.push((
+                Pointer::parse(p.as_deref().unwrap_or("/__hostname"))?,
+                Value::String(host),
+            ));
+        }
+        let timestamp = self
+            .inject_timestamp
+            .as_deref()
+            .map(Pointer::parse)
+            .transpose()?;
+        let id = match self.assign_id.as_deref() {
+            Some(p) => {
+                let ids = if self.id_format.as_deref() == Some("uuid") {
+                    Ids::Uuid(self.new_rng())
+                } else {
+                    Ids::Seq(self.id_start.unwrap_or(1))
+                };
+                Some((Pointer::parse(p)?, ids))
+            }
+            None => None,
+        };
+        Ok(Some(Enricher::new(fields, timestamp, id)))
+    }
+    fn rows_of_matched(&self) -> bool {
+        self.rows_of.as_deref() == Some("matched")
+    }
+    fn get_sample(&self) -> Option<(f64, fastrand::Rng)> {
+        self.output_sample.map(|x| (x, self.new_rng()))
+    }
+    /// Return a random generator seeded by `seed` if given.
+    fn new_rng(&self) -> fastrand::Rng {
+        match self.seed {
+            Some(x) => fastrand::Rng::with_seed(x),
+            None => fastrand::Rng::new(),
+        }
+    }
+    fn get_threads(&self) -> usize {
+        self.threads.unwrap_or(1)
+    }
+    /// Return the progress of the input, the size is known if `input` is given.
+    fn new_progress(&self) -> Result<Progress> {
+        let total = match &self.input {
+            Some(x) => Some(fs::metadata(x).map_err(io_err)?.len()),
+            None => None,
+        };
+        Ok(Progress::new(total))
+    }
+    fn input_name(&self) -> String {
+        self.input
+            .as_ref()
+            .map_or("stdin".to_owned(), |x| x.display().to_string())
+    }
+    fn open_input(&self) -> Result<Box<dyn BufRead>> {
+        info!(input = %self.input_name(), "open input");
+        match &self.input {
+            Some(x) => Ok(Box::new(io::BufReader::new(File::open(x).map_err(io_err)?))),
+            None => Ok(Box::new(io::stdin().lock())),
+        }
+    }
+    fn open_output(&self) -> Result<Box<dyn Write>> {
+        if self.split_lines.is_some() || self.split_bytes.is_some() {
+            return Ok(Box::new(SplitWriter::new(
+                self.out_prefix.clone(),
+                self.split_lines,
+                self.split_bytes,
+            )));
+        }
+        match &self.output {
+            // the file is written by the format
+            Some(_) if matches!(self.output_format.as_deref(), Some("parquet" | "sqlite")) => {
+                Ok(Box::new(io::sink()))
+            }
+            Some(x) => Ok(Box::new(File::create(x).map_err(io_err)?)),
+            None => Ok(Box::new(io::stdout().lock())),
+        }
+    }
+    fn validate(&self) -> Result<()> {
+        self.sort.validate()?;
+        self.page.validate(self.sort.is_given())?;
+        if self.pipeline.is_some() {
+            pipeline::check(&self.unsupported_by_pipeline())?;
+        }
+        let invalid = |x: &str| Err(Error::new(ErrorCode::InvalidOption(x.to_owned())));
+        let format = self.output_format.as_deref();
+        if self.threads == Some(0) {
+            invalid("threads should be positive")
+        } else if !matches!(self.error_format.as_deref(), None | Some("text" | "json")) {
+            invalid("error_format should be text or json")
+        } else if !matches!(
+            format,
+            None | Some(
+                "ndjson" | "json-array" | "table" | "msgpack" | "cbor" | "parquet" | "sqlite"
+            )
+        ) {
+            invalid("output_format should be ndjson, json-array, table, msgpack, cbor, parquet or sqlite")
+        } else if format == Some("parquet") && cfg!(not(feature = "parquet")) {
+            invalid("output_format parquet requires the parquet feature")
+        } else if format == Some("sqlite") && cfg!(not(feature = "sqlite")) {
+            invalid("output_format sqlite requires the sqlite feature")
+        } else if matches!(format, Some("parquet" | "sqlite")) && self.output.is_none() {
+            invalid("output_format parquet and sqlite require output")
+        } else if self.schema_from_sample == 0 {
+            invalid("schema_from_sample should be positive")
+        } else if !matches!(format, None | Some("ndjson"))
+            && (self.split_lines.is_some() || self.split_bytes.is_some() || self.pipeline.is_some())
+        {
+            invalid("output_format other than ndjson is exclusive with split and pipeline")
+        } else if format == Some("table") && self.columns.is_none() {
+            invalid("output_format table requires columns")
+        } else if self.columns.is_some() && !matches!(format, Some("table" | "sqlite")) {
+            invalid("columns requires output_format table or sqlite")
+        } else if (self.column_width.is_some() || self.table_window != 100)
+            && format != Some("table")
+        {
+            invalid("column_width and table_window require output_format table")
+        } else if self.sqlite_table.is_some() && format != Some("sqlite") {
+            invalid("sqlite_table requires output_format sqlite")
+        } else if self.column_width == Some(0) || self.table_window == 0 {
+            invalid("column_width and table_window should be positive")
+        } else if self.flush_every == Some(0) {
+            invalid("flush_every should be positive")
+        } else if self.line_buffered && self.flush_every.is_some() {
+            invalid("flush_every and line_buffered are exclusive")
+        } else if self.fail_on_no_match && self.exit_zero_on_no_match {
+            invalid("fail_on_no_match and exit_zero_on_no_match are exclusive")
+        } else if self.dedup_capacity == Some(0) {
+            invalid("dedup_capacity should be positive")
+        } else if self.dedup_capacity.is_some() && self.dedup_by.is_empty() {
+            invalid("dedup_capacity requires dedup_by")
+        } else if matches!(self.output_sample, Some(x) if !(0.0..=1.0).contains(&x)) {
+            invalid("output_sample should be between 0 and 1")
+        } else if self.seed.is_some()
+            && self.output_sample.is_none()
+            && self.id_format.as_deref() != Some("uuid")
+        {
+            invalid("seed requires output_sample or id_format uuid")
+        } else if (self.id_format.is_some() || self.id_start.is_some()) && self.assign_id.is_none()
+        {
+            invalid("id_format and id_start require assign_id")
+        } else if self.id_start.is_some() && self.id_format.as_deref() == Some("uuid") {
+            invalid("id_start is for id_format seq")
+        } else if self.split_lines == Some(0) || self.split_bytes == Some(0) {
+            invalid("split_lines and split_bytes should be positive")
+        } else if self.output.is_some()
+            && (self.split_lines.is_some() || self.split_bytes.is_some())
+        {
+            invalid("output is exclusive with split_lines and split_bytes")
+        } else if self.rows_of.is_some() && self.rows.is_none() {
+            invalid("rows_of requires rows")
+        } else if self.out_dir.is_some() && self.partition_by.is_none() {
+            invalid("out_dir requires partition_by")
+        } else if self.partition_by.is_some()
+            && (self.sort.is_given()
+                || !self.route.is_empty()
+                || self.output.is_some()
+                || self.split_lines.is_some()
+                || self.split_bytes.is_some())
+        {
+            invalid("partition_by is exclusive with sort, route, output and split")
+        } else if self.redact_hash && self.redact.is_empty() && self.redact_regex.is_empty() {
+            invalid("redact_hash requires redact or redact_regex")
+        } else if self.annotate && (!self.route.is_empty() || self.partition_by.is_some()) {
+            invalid("annotate is exclusive with route and partition_by")
+        } else if self.uniq.is_some() && self.partition_by.is_some() {
+            invalid("uniq and partition_by are exclusive")
+        } else if !self.route.is_empty() && self.sort.is_given() {
+            invalid("route and sort are exclusive")
+        } else if self.query.reads_stdin() && self.sort.reads_stdin() {
+            invalid("query_file and sort cannot both be read from stdin")
+        } else if (self.query.reads_stdin() || self.sort.reads_stdin())
+            && self.input.is_none()
+            && !self.dry_run
+        {
+            invalid("input is required to read the query or the sort from stdin")
+        } else {
+            Ok(())
+        }
+    }
+    /// Return the names of the options given that the pipeline does not apply.
+    fn unsupported_by_pipeline(&self) -> Vec<&'static str> {
+        [
+            ("sort", self.sort.is_given()),
+            ("route", !self.route.is_empty()),
+            ("rows", self.rows.is_some()),
+            ("ignore_trailing", self.ignore_trailing),
+            ("max_errors", self.max_errors.is_some()),
+            ("errors_out", self.errors_out.is_some()),
+            ("error_format", self.error_format.is_some()),
+            ("error_dedup", self.error_dedup),
+            ("error_summary", self.error_summary),
+            ("flush_every", self.flush_every.is_some()),
+            ("line_buffered", self.line_buffered),
+            ("progress", self.progress),
+            ("fail_on_no_match", self.fail_on_no_match),
+            ("fail_on_error_lines", self.fail_on_error_lines),
+            ("timeout", self.timeout.is_some()),
+            ("line_timeout", self.line_timeout.is_some()),
+            ("split_lines", self.split_lines.is_some()),
+            ("split_bytes", self.split_bytes.is_some()),
+            ("partition_by", self.partition_by.is_some()),
+            ("dedup_by", !self.dedup_by.is_empty()),
+            ("uniq", self.uniq.is_some()),
+            ("decode", !self.decode.is_empty()),
+            ("redact", !self.redact.is_empty()),
+            ("redact_regex", !self.redact_regex.is_empty()),
+            ("inject", !self.inject.is_empty()),
+            ("inject_timestamp", self.inject_timestamp.is_some()),
+            ("inject_hostname", self.inject_hostname.is_some()),
+            ("assign_id", self.assign_id.is_some()),
+            ("hash", self.hash.is_some()),
+            ("output_sample", self.output_sample.is_some()),
+            ("annotate", self.annotate),
+        ]
+        .iter()
+        .filter(|x| x.1)
+        .map(|x| x.0)
+        .collect()
+    }
+    fn get_format(&self) -> Result<Format> {
+        Ok(match self.output_format.as_deref() {
+            Some("json-array") => Format::JsonArray,
+            Some("table") => Format::Table(Table::new(
+                self.get_columns()?,
+                self.column_width,
+                self.table_window,
+            )),
+            Some("msgpack") => Format::Msgpack,
+            Some("cbor") => Format::Cbor,
+            #[cfg(feature = "parquet")]
+            Some("parquet") => {
+                let file = File::create(self.output.as_ref().unwrap()).map_err(io_err)?;
+                Format::Parquet(Box::new(crate::parquet_out::ParquetWriter::new(
+                    file,
+                    self.schema_from_sample,
+                )))
+            }
+            #[cfg(feature = "sqlite")]
+            Some("sqlite") => Format::Sqlite(Box::new(
+                crate::sqlite_out::SqliteWriter::new(
+                    self.output.as_ref().unwrap(),
+                    self.sqlite_table.as_deref().unwrap_or("matches"),
+                    self.get_columns()?,
+                )
+                .map_err(io_err)?,
+            )),
+            _ => Format::Ndjson,
+        })
+    }
+    fn get_columns(&self) -> Result<Vec<Pointer>> {
+        self.columns
+            .iter()
+            .flat_map(|x| x.split(','))
+            .map(Pointer::parse)
+            .collect()
+    }
+    fn get_flush_every(&self) -> Option<usize> {
+        if self.line_buffered {
+            Some(1)
+        } else {
+            self.flush_every
+        }
+    }
+    fn get_reporter(&self) -> Result<Reporter> {
+        let out = self
+            .errors_out
+            .as_ref()
+            .map(|x| {
+                File::create(x)
+                    .map(BufWriter::new)
+                    .map_err(|x| Error::new(ErrorCode::Io(x)))
+            })
+            .transpose()?;
+        Ok(Reporter {
+            count: 0,
+            max: self.max_errors,
+            out,
+            json: self.error_format.as_deref() == Some("json"),
+            dedup: if self.error_dedup {
+                Some(Dedup::default())
+            } else {
+                None
+            },
+            summary: if self.error_summary {
+                Some(Summary::default())
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// Read a line into `buf` without the line terminator.
+/// Return `false` if reached EOF.
+pub(crate) fn read_line(r: &mut impl BufRead, buf: &mut String) -> io::Result<bool> {
+    buf.clear();
+    if r.read_line(buf)? == 0 {
+        return Ok(false);
+    }
+    if buf.ends_with('\n') {
+        buf.pop();
+        if buf.ends_with('\r') {
+            buf.pop();
+        }
+    }
+    Ok(true)
+}
+
+/// Cut `line` after the leading json, keep it as is if it does not start with a json.
+fn truncate_trailing(line: &mut String) {
+    let mut values = serde_json::Deserializer::from_str(line).into_iter::<IgnoredAny>();
+    if let Some(Ok(_)) = values.next() {
+        let end = values.byte_offset();
+        line.truncate(end);
+    }
+}
+
+/// The number of lines evaluated at once by a thread.
+const BATCH_SIZE_PER_THREAD: usize = 256;
+
+/// Outcome of a line.
+enum Selected {
+    /// The line meets the condition, with the parsed json if sorting.
+    Matched(Option<Value>),
+    Unmatched,
+}
+
+/// Select the lines and write them.
+struct Grep {
+    selector: Selector,
+    sort: Option<Sort>,
+    page: PageArgs,
+    /// Matched lines to be sorted.
+    lines: Vec<String>,
+    reporter: Reporter,
+    /// Threads to evaluate the lines, `None` if single-threaded.
+    pool: Option<ThreadPool>,
+    out: BufWriter<Box<dyn Write>>,
+    format: Format,
+    /// Flush the output every this number of lines.
+    flush_every: Option<usize>,
+    /// The number of the lines written.
+    written: usize,
+    /// The number of the matched lines.
+    matched: usize,
+    watchdog: Option<Arc<Watchdog>>,
+    routes: Vec<Route>,
+    dedup: Option<dedup::Dedup>,
+    uniq: Option<dedup::Uniq>,
+    decoder: Option<Decoder>,
+    redactor: Option<Redactor>,
+    enricher: Option<Enricher>,
+    annotator: Option<Annotator>,
+    /// Add `__hash` to the selected jsons.
+    hash: bool,
+    /// Fraction of the selected lines to write and the random generator.
+    sample: Option<(f64, fastrand::Rng)>,
+    partitions: Option<Partitions>,
+    /// Indexes of the input lines to process.
+    input_rows: Option<Rows>,
+    /// Indexes of the selected lines to process.
+    matched_rows: Option<Rows>,
+    /// The number of the lines meeting the query.
+    selected: usize,
+}
+
+/// Key of the digest added by `hash`.
+const HASH_KEY: &str = "__hash";
+
+/// Destination of the lines meeting a query.
+struct Route {
+    name: String,
+    selector: Selector,
+    out: BufWriter<File>,
+    /// The number of the lines written.
+    matched: usize,
+}
+
+impl Grep {
+    /// Evaluate a line, parse the whole json if `parse`.
+    fn select(selector: &Selector, line: &str, parse: bool) -> Result<Selected> {
+        // parse only the values referred by the query unless sorting
+        if parse {
+            selector
+                .filter(line)
+                .map(|x| x.map_or(Selected::Unmatched, |v| Selected::Matched(Some(v))))
+        } else {
+            selector.matches_raw(line).map(|x| {
+                if x {
+                    Selected::Matched(None)
+                } else {
+                    Selected::Unmatched
+                }
+            })
+        }
+    }
+    /// Return true if the lines after the `offset`-th line are out of `rows`.
+    fn done(&self, offset: usize) -> bool {
+        let last = |r: Option<&Rows>| r.and_then(|x| x.last());
+        matches!(last(self.input_rows.as_ref()), Some(x) if offset >= x)
+            || matches!(last(self.matched_rows.as_ref()), Some(x) if self.selected >= x)
+    }
+    /// Write a line to stdout unless the same as the preceding one by `uniq`.
+    fn write(&mut self, line: &str) -> Result<()> {
+        if let Some(u) = self.uniq.as_mut() {
+            if !u.is_new(line) {
+                return Ok(());
+            }
+        }
+        self.format
+            .write(self.written, line, &mut self.out)
+            .map_err(io_err)?;
+        self.written += 1;
+        if matches!(self.flush_every, Some(n) if self.written.is_multiple_of(n)) {
+            self.out.flush().map_err(io_err)?;
+        }
+        Ok(())
+    }
+    /// Evaluate the lines starting at the `offset`-th line,
+    /// in parallel if multiple threads are available.
+    fn select_all(&self, offset: usize, batch: &[String]) -> Vec<Result<Selected>> {
+        let selector = &self.selector;
+        let parse = self.sort.is_some();
+        let watchdog = self.watchdog.as_deref();
+        let rows = self.input_rows.as_ref();
+        let select = move |i: usize, x: &str| {
+            if rows.is_some_and(|r| !r.contains(offset + i + 1)) {
+                return Ok(Selected::Unmatched);
+            }
+            let _guard = watchdog.map(|w| w.line(offset + i + 1));
+            Self::select(selector, x, parse)
+        };
+        match self.pool.as_ref() {
+            Some(pool) if batch.len() > 1 => pool.install(|| {
+                batch
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, x)| select(i, x))
+                    .collect()
+            }),
+            _ => batch
+                .iter()
+                .enumerate()
+                .map(|(i, x)| select(i, x))
+                .collect(),
+        }
+    }
+    /// Select the lines of the batch starting at the `offset`-th line in order.
+    /// Return `Err` if failed to write the output.
+    fn process(&mut self, offset: usize, batch: &[String]) -> Result<()> {
+        for (i, selected) in self.select_all(offset, batch).into_iter().enumerate() {
+            let line = &batch[i];
+            match selected {
+                Ok(Selected::Matched(v)) => {
+                    self.selected += 1;
+                    if let Some(r) = self.matched_rows.as_ref() {
+                        if !r.contains(self.selected) {
+                            continue;
+                        }
+                    }
+                    if let Some(d) = self.dedup.as_mut() {
+                        if !d.is_new(line, v.as_ref()) {
+                            continue;
+                        }
+                    }
+                    if let Some((ratio, rng)) = self.sample.as_mut() {
+                        if rng.f64() >= *ratio {
+                            continue;
+                        }
+                    }
+                    let line = match self.rewrite(offset + i + 1, line, v.as_ref()) {
+                        Ok(x) => x,
+                        Err(e) => {
+                            self.report(offset + i + 1, line, &e);
+                            continue;
+                        }
+                    };
+                    self.matched += 1;
+                    match (self.sort.as_mut(), v) {
+                        (Some(s), Some(v)) => match s.try_add(v) {
+                            Ok(_) => self.lines.push(line.into_owned()),
+                            Err(e) => self.report(offset + i + 1, &line, &e),
+                        },
+                        _ if !self.routes.is_empty() => self.route(offset + i + 1, &line)?,
+                        (_, v) if self.partitions.is_some() => {
+                            let r = self.partitions.as_mut().unwrap().write(&line, v.as_ref());
+                            if let Err(e) = r {
+                                self.report(offset + i + 1, &line, &e);
+                            }
+                        }
+                        _ => self.write(&line)?,
+                    }
+                }
+                Ok(Selected::Unmatched) => continue,
+                Err(e) => self.report(offset + i + 1, line, &e),
+            }
+        }
+        Ok(())
+    }
+    /// Return the `n`-th line to write, decoded, redacted, enriched, hashed and annotated if required,
+    /// `v` is `line` if parsed.
+    fn rewrite<'a>(&mut self, n: usize, line: &'a str, v: Option<&Value>) -> Result<Cow<'a, str>> {
+        if self.decoder.is_none()
+            && self.redactor.is_none()
+            && self.enricher.is_none()
+            && !self.hash
+            && self.annotator.is_none()
+        {
+            return Ok(Cow::Borrowed(line));
+        }
+        let mut v = match v {
+            Some(x) => x.clone(),
+            None => from_str(line).map_err(|x| Error::new(ErrorCode::Json(x)))?,
+        };
+        // tell the pairs by the original json
+        let matched_pairs = self.annotator.as_ref().map(|a| a.matched_pairs(&v));
+        if let Some(d) = self.decoder.as_ref() {
+            d.decode(&mut v)?;
+        }
+        if let Some(r) = self.redactor.as_ref() {
+            r.redact(&mut v);
+        }
+        if let Some(e) = self.enricher.as_mut() {
+            e.enrich(&mut v)?;
+        }
+        if self.hash {
+            let digest = sha256(&v);
+            match v.as_object_mut() {
+                Some(x) => x.insert(HASH_KEY.to_owned(), Value::String(digest)),
+                None => {
+                    return Err(Error::new(ErrorCode::TypeMismatch {
+                        got: type_name(&v).to_owned(),
+                        want: "object".to_owned(),
+                        by: "hash".to_owned(),
+                    }))
+                }
+            };
+        }
+        if let (Some(a), Some(x)) = (self.annotator.as_ref(), matched_pairs) {
+            v = a.annotate(n, v, x);
+        }
+        Ok(Cow::Owned(v.to_string()))
+    }
+    /// Write the `n`-th line to the matched routes, or to the output if none.
+    fn route(&mut self, n: usize, line: &str) -> Result<()> {
+        let mut routed = false;
+        for i in 0..self.routes.len() {
+            let r = &mut self.routes[i];
+            match r.selector.matches_raw(line) {
+                Ok(true) => {
+                    writeln!(r.out, "{}", line).map_err(io_err)?;
+                    r.matched += 1;
+                    routed = true;
+                }
+                Ok(false) => continue,
+                Err(e) => self.report(n, line, &e),
+            }
+        }
+        if !routed {
+            self.write(line)?;
+        }
+        Ok(())
+    }
+    /// Report the error of the `n`-th line, exit if too many errors.
+    fn report(&mut self, n: usize, line: &str, e: &Error) {
+        if let Err(x) = self.reporter.report(n, line, e) {
+            self.reporter.diagnose(None, &x, None);
+            // exiting by the error above anyway
+            let _ = self.reporter.finish();
+            let _ = self.out.flush();
+            process::exit(1);
+        }
+    }
+    /// Report the errors and write the sorted lines.
+    /// Return `Err` if failed to write the output.
+    fn finish(mut self) -> Result<()> {
+        self.reporter.finish()?;
+        if let Some(s) = self.sort.take() {
+            let lines = std::mem::take(&mut self.lines);
+            for i in self.page.page(s.sorted_indexes().into_iter()) {
+                self.write(&lines[i])?;
+            }
+        }
+        self.format
+            .finish(self.written, &mut self.out)
+            .map_err(io_err)?;
+        self.out.flush().map_err(io_err)?;
+        if let Some(p) = self.partitions.as_mut() {
+            let files = p.finish()?;
+            info!(files, "close partitions");
+        }
+        for r in &mut self.routes {
+            r.out.flush().map_err(io_err)?;
+            info!(route = %r.name, matched = r.matched, "close route");
+        }
+        Ok(())
+    }
+}
+
+/// Error of a line for json formatted error.
+#[derive(Serialize)]
+struct LineError<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(flatten)]
+    error: &'a Error,
+    /// The number of occurrences so far of the same error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+}
+
+/// Counts of the same errors, in order of first occurrence.
+#[derive(Default)]
+struct Dedup {
+    index: HashMap<String, usize>,
+    counts: Vec<(String, usize)>,
+}
+
+impl Dedup {
+    /// Count up the error and return the number of its occurrences.
+    fn add(&mut self, err: &Error) -> usize {
+        let signature = err.signature();
+        let i = match self.index.get(&signature) {
+            Some(i) => *i,
+            None => {
+                self.index.insert(signature.clone(), self.counts.len());
+                self.counts.push((signature, 0));
+                self.counts.len() - 1
+            }
+        };
+        self.counts[i].1 += 1;
+        self.counts[i].1
+    }
+}
+
+/// Count and the first line number of an error category.
+#[derive(Serialize)]
+struct SummaryRow {
+    kind: String,
+    count: usize,
+    first_line: usize,
+}
+
+/// Error counts by category.
+#[derive(Default)]
+struct Summary(HashMap<ErrorKind, (usize, usize)>);
+
+impl Summary {
+    fn add(&mut self, n: usize, err: &Error) {
+        self.0.entry(err.kind()).or_insert((0, n)).0 += 1;
+    }
+    /// Return the rows in order of first occurrence.
+    fn rows(&self) -> Vec<SummaryRow> {
+        let mut rows = self
+            .0
+            .iter()
+            .map(|(k, (count, first_line))| SummaryRow {
+                kind: format!("{:?}", k),
+                count: *count,
+                first_line: *first_line,
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|x| x.first_line);
+        rows
+    }
+}
+
+/// Report the lines that failed to be selected.
+struct Reporter {
+    /// The number of the error lines.
+    count: usize,
+    max: Option<usize>,
+    out: Option<BufWriter<File>>,
+    json: bool,
+    dedup: Option<Dedup>,
+    summary: Option<Summary>,
+}
+
+impl Reporter {
+    /// Report the error of the `n`-th line.
+    /// Return `Err` if too many errors occurred.
+    fn report(&mut self, n: usize, line: &str, err: &Error) -> Result<()> {
+        self.count += 1;
+        if let Some(x) = self.summary.as_mut() {
+            x.add(n, err);
+        }
+        match self.out.as_mut() {
+            Some(w) => writeln!(w, "{}", line).map_err(|x| Error::new(ErrorCode::Io(x)))?,
+            None => match self.dedup.as_mut().map(|x| x.add(err)) {
+                // write on the 1st, 10th, 100th, ... occurrences
+                Some(c) if c == 10_usize.pow(c.ilog10()) => self.diagnose(Some(n), err, Some(c)),
+                Some(_) => {}
+                None => self.diagnose(Some(n), err, None),
+            },
+        }
+        match self.max {
+            Some(max) if self.count > max => Err(Error::new(ErrorCode::TooManyErrors { max })),
+            _ => Ok(()),
+        }
+    }
+    /// Flush the error lines and write the number of them.
+    fn finish(&mut self) -> Result<()> {
+        if let Some(w) = self.out.as_mut() {
+            w.flush().map_err(|x| Error::new(ErrorCode::Io(x)))?;
+        }
+        if let Some(d) = self.dedup.as_ref() {
+            for (signature, count) in d.counts.iter().filter(|(_, c)| *c > 1) {
+                if self.json {
+                    eprintln!("{}", json!({ "signature": signature, "total": count }));
+                } else {
+                    eprintln!("{}: {} times", signature, count);
+                }
+            }
+        }
+        if let Some(x) = self.summary.as_ref() {
+            self.write_summary(x.rows());
+        }
+        if self.max.is_some() {
+            if self.json {
+                eprintln!("{}", json!({ "error_lines": self.count }));
+            } else {
+                eprintln!("{} error lines", self.count);
+            }
+        }
+        Ok(())
+    }
+    fn write_summary(&self, rows: Vec<SummaryRow>) {
+        if self.json {
+            eprintln!("{}", json!({ "summary": rows }));
+            return;
+        }
+        let width = rows.iter().map(|x| x.kind.len()).max().unwrap_or(0).max(4);
+        eprintln!(
+            "{:width$} {:>8} {:>10}",
+            "kind",
+            "count",
+            "first_line",
+            width = width
+        );
+        for r in rows {
+            eprintln!(
+                "{:width$} {:>8} {:>10}",
+                r.kind,
+                r.count,
+                r.first_line,
+                width = width
+            );
+        }
+    }
+    /// Write an error to stderr.
+    fn diagnose(&self, line: Option<usize>, error: &Error, count: Option<usize>) {
+        if self.json {
+            let e = LineError { line, error, count };
+            eprintln!("{}", to_string(&e).unwrap());
+            return;
+        }
+        let suffix = match count {
+            Some(c) if c > 1 => format!(" ({} times)", c),
+            _ => String::new(),
+        };
+        match line {
+            Some(n) => eprintln!("line {}: {}{}", n, error, suffix),
+            None => eprintln!("{}{}", error, suffix),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_pipeline(args: &[&str]) -> GrepOpt {
+        let mut opt = GrepOpt::from_iter(std::iter::once("grep").chain(args.iter().copied()));
+        opt.pipeline = Some(Vec::new());
+        opt
+    }
+
+    #[test]
+    fn pipeline_alone() {
+        with_pipeline(&["--threads", "2", "--output", "out.ndjson"])
+            .validate()
+            .unwrap();
+    }
+
+    macro_rules! test_pipeline_exclusive {
+        ($name:ident, $($arg:literal),+) => {
+            #[test]
+            fn $name() {
+                let err = with_pipeline(&[$($arg),+]).validate().err().unwrap();
+                assert!(err.to_string().contains("pipeline is exclusive"), "{}", err);
+            }
+        };
+    }
+
+    test_pipeline_exclusive!(pipeline_sort, "-k", r#"{"sort":[{"p":"/i"}]}"#);
+    test_pipeline_exclusive!(pipeline_route, "--route", "a=a.json");
+    test_pipeline_exclusive!(pipeline_rows, "--rows", "1-10");
+    test_pipeline_exclusive!(pipeline_ignore_trailing, "--ignore_trailing");
+    test_pipeline_exclusive!(pipeline_max_errors, "--max_errors", "0");
+    test_pipeline_exclusive!(pipeline_errors_out, "--errors_out", "err.txt");
+    test_pipeline_exclusive!(pipeline_error_format, "--error_format", "json");
+    test_pipeline_exclusive!(pipeline_error_dedup, "--error_dedup");
+    test_pipeline_exclusive!(pipeline_error_summary, "--error_summary");
+    test_pipeline_exclusive!(pipeline_flush_every, "--flush_every", "10");
+    test_pipeline_exclusive!(pipeline_line_buffered, "--line_buffered");
+    test_pipeline_exclusive!(pipeline_progress, "--progress");
+    test_pipeline_exclusive!(pipeline_fail_on_no_match, "--fail_on_no_match");
+    test_pipeline_exclusive!(pipeline_fail_on_error_lines, "--fail_on_error_lines");
+    test_pipeline_exclusive!(pipeline_timeout, "--timeout", "1ms");
+    test_pipeline_exclusive!(pipeline_line_timeout, "--line_timeout", "1ms");
+    test_pipeline_exclusive!(pipeline_split_lines, "--split_lines", "10");
+    test_pipeline_exclusive!(pipeline_split_bytes, "--split_bytes", "1MB");
+    test_pipeline_exclusive!(pipeline_partition_by, "--partition_by", "/k");
+    test_pipeline_exclusive!(pipeline_dedup_by, "--dedup_by", "/id");
+    test_pipeline_exclusive!(pipeline_uniq, "--uniq");
+    test_pipeline_exclusive!(pipeline_decode, "--decode", "/p=base64");
+    test_pipeline_exclusive!(pipeline_redact, "--redact", "/password");
+    test_pipeline_exclusive!(pipeline_redact_regex, "--redact_regex", "^/password$");
+    test_pipeline_exclusive!(pipeline_inject, "--inject", "env=prod");
+    test_pipeline_exclusive!(pipeline_inject_timestamp, "--inject_timestamp", "/ts");
+    test_pipeline_exclusive!(pipeline_inject_hostname, "--inject_hostname");
+    test_pipeline_exclusive!(pipeline_assign_id, "--assign_id", "/id");
+    test_pipeline_exclusive!(pipeline_hash, "--hash", "sha256");
+    test_pipeline_exclusive!(pipeline_output_sample, "--output_sample", "0.5");
+    test_pipeline_exclusive!(pipeline_annotate, "--annotate");
+}
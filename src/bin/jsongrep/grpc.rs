@@ -0,0 +1,43 @@
+use crate::args::io_err;
+use jsongrep::error::Result;
+use jsongrep::grpc::FilterServer;
+use std::io;
+use std::net::SocketAddr;
+use structopt::StructOpt;
+use tracing::info;
+
+/// Serve the gRPC service `jsongrep.Filter`.
+///
+/// The query is given by the metadata `jsongrep-query` of each call, see [`jsongrep::grpc`].
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct GrpcOpt {
+    /// Address to listen on.
+    #[structopt(long = "addr", default_value = "127.0.0.1:50051")]
+    addr: SocketAddr,
+    /// Serve the Prometheus metrics at `/metrics` on this address,
+    /// the counters of the records read, matched, filtered and errored, and the latency histogram.
+    #[cfg(feature = "metrics")]
+    #[structopt(long = "metrics_addr")]
+    metrics_addr: Option<SocketAddr>,
+}
+
+impl GrpcOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let rt = tokio::runtime::Runtime::new().map_err(io_err)?;
+        #[allow(unused_mut)]
+        let mut server = FilterServer::default();
+        #[cfg(feature = "metrics")]
+        if let Some(addr) = self.metrics_addr {
+            let m = std::sync::Arc::new(crate::metrics::Metrics::new()?);
+            m.serve(addr)?;
+            server = server.with_observer(m);
+        }
+        info!(addr = %self.addr, "serve grpc");
+        rt.block_on(
+            tonic::transport::Server::builder()
+                .add_service(server)
+                .serve(self.addr),
+        )
+        .map_err(|x| io_err(io::Error::other(x)))
+    }
+}
@@ -0,0 +1,179 @@
+use crate::args::io_err;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::pointer::Pointer;
+use jsongrep::query::{Query, Value};
+use jsongrep::raw_query as raw;
+use serde::{Deserialize, Serialize};
+use serde_json::value::Value as JSONValue;
+use serde_json::{from_reader, from_str, to_string, to_writer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use structopt::StructOpt;
+use tracing::{info, warn};
+
+/// Manage the index of the values of an input file.
+///
+/// `grep --input FILE` reads only the lines having the values
+/// if the query has `eq` pairs on the indexed pointers, not under `not` nor `or`,
+/// and `FILE.jgi` is built after FILE is modified last.
+/// The errors of the lines not read are not reported.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct IndexOpt {
+    #[structopt(subcommand)]
+    cmd: IndexCommand,
+}
+
+#[derive(Debug, Clone, StructOpt)]
+enum IndexCommand {
+    /// Build the index of FILE into FILE.jgi.
+    Build(BuildOpt),
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct BuildOpt {
+    /// Input file, a json per line.
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    /// Pointers to index, separated by commas, e.g. /id,/level.
+    #[structopt(long = "pointers", use_delimiter = true, required = true)]
+    pointers: Vec<String>,
+}
+
+impl IndexOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        match &self.cmd {
+            IndexCommand::Build(x) => x.run(),
+        }
+    }
+}
+
+impl BuildOpt {
+    fn run(&self) -> Result<()> {
+        let pointers = self
+            .pointers
+            .iter()
+            .map(|x| Pointer::parse(x))
+            .collect::<Result<Vec<_>>>()?;
+        let index = Index::build(&self.file, &pointers)?;
+        let path = path(&self.file);
+        let mut w = BufWriter::new(File::create(&path).map_err(io_err)?);
+        to_writer(&mut w, &index).map_err(|x| Error::new(ErrorCode::Json(x)))?;
+        w.flush().map_err(io_err)?;
+        info!(index = %path.display(), "index built");
+        Ok(())
+    }
+}
+
+/// Path of the index of `input`.
+pub(crate) fn path(input: &Path) -> PathBuf {
+    let mut x = input.as_os_str().to_owned();
+    x.push(".jgi");
+    PathBuf::from(x)
+}
+
+/// Line number and byte offset of a line.
+pub(crate) type Entry = (usize, u64);
+
+/// Lines of a file by the values at the pointers.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Index {
+    /// Size of the indexed file.
+    len: u64,
+    /// Modification time of the indexed file, nanoseconds since the unix epoch.
+    modified: u128,
+    /// Pointer -> key of the value -> lines.
+    pointers: BTreeMap<String, BTreeMap<String, Vec<Entry>>>,
+}
+
+/// Key of a value in the index.
+///
+/// The numbers equal to each other as [`Value`] can have the same key,
+/// the lines are evaluated by the query anyway.
+fn key(v: &Value) -> String {
+    to_string(&raw::Value::from(v)).unwrap()
+}
+
+/// Return the size and the modification time of `path`.
+fn stat(path: &Path) -> Result<(u64, u128)> {
+    let m = fs::metadata(path).map_err(io_err)?;
+    let modified = m
+        .modified()
+        .map_err(io_err)?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_nanos());
+    Ok((m.len(), modified))
+}
+
+impl Index {
+    fn build(input: &Path, pointers: &[Pointer]) -> Result<Index> {
+        let (len, modified) = stat(input)?;
+        let mut index = Index {
+            len,
+            modified,
+            pointers: pointers
+                .iter()
+                .map(|x| (x.to_string(), BTreeMap::new()))
+                .collect(),
+        };
+        let mut r = BufReader::new(File::open(input).map_err(io_err)?);
+        let mut buf = String::new();
+        let (mut n, mut offset) = (0, 0);
+        loop {
+            buf.clear();
+            let size = r.read_line(&mut buf).map_err(io_err)?;
+            if size == 0 {
+                break;
+            }
+            n += 1;
+            match from_str::<JSONValue>(&buf) {
+                Ok(v) => {
+                    for p in pointers {
+                        if let Some(x) = p.get(&v).and_then(Value::from_json) {
+                            let values = index.pointers.get_mut(p.as_str()).unwrap();
+                            values.entry(key(&x)).or_default().push((n, offset));
+                        }
+                    }
+                }
+                Err(e) => warn!(line = n, error = %e, "not indexed"),
+            }
+            offset += size as u64;
+        }
+        Ok(index)
+    }
+    /// Read the index of `input` if built after `input` is modified last.
+    pub(crate) fn load(input: &Path) -> Result<Option<Index>> {
+        let path = path(input);
+        let f = match File::open(&path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(io_err(e)),
+        };
+        let index: Index =
+            from_reader(BufReader::new(f)).map_err(|x| Error::new(ErrorCode::Json(x)))?;
+        if stat(input)? != (index.len, index.modified) {
+            warn!(index = %path.display(), "index is stale, ignored");
+            return Ok(None);
+        }
+        Ok(Some(index))
+    }
+    /// Return the lines that may meet `query` in order,
+    /// `None` if the query has no `eq` pairs on the indexed pointers.
+    pub(crate) fn lookup(&self, query: &Query) -> Option<Vec<Entry>> {
+        query
+            .required_equals()
+            .into_iter()
+            .filter_map(|(p, v)| {
+                self.pointers
+                    .get(p.as_str())
+                    .map(|x| x.get(&key(v)).map_or(&[][..], |x| &x[..]))
+            })
+            .min_by_key(|x| x.len())
+            .map(|x| x.to_vec())
+    }
+}
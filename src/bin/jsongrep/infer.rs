@@ -0,0 +1,93 @@
+use crate::args::io_err;
+use crate::grep::read_line;
+use jsongrep::error::Result;
+use jsongrep::pointer;
+use jsongrep::select::Query as Selector;
+use serde_json::json;
+use serde_json::value::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use structopt::StructOpt;
+
+/// Report the pointers seen in the jsons of stdin.
+///
+/// Write {"pointer":"/a","count":N,"ratio":R,"types":{"string":N,...},"example":...} per pointer
+/// in order of first occurrence.
+/// `count` is the number of the jsons that have the pointer, `ratio` is `count` per the jsons read.
+/// `example` is the first scalar value seen.
+/// The lines that are not json are written to stderr and skipped.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct InferOpt {
+    /// Read at most this number of jsons.
+    #[structopt(long = "sample", default_value = "10000")]
+    sample: usize,
+}
+
+/// What is seen at a pointer.
+#[derive(Default)]
+struct Seen {
+    count: usize,
+    types: BTreeMap<&'static str, usize>,
+    example: Option<Value>,
+}
+
+pub(crate) fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl InferOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let selector = Selector::all();
+        let mut pointers: Vec<(String, Seen)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut stdin = io::stdin().lock();
+        let mut buf = String::new();
+        let (mut n, mut records) = (0, 0);
+        while records < self.sample && read_line(&mut stdin, &mut buf).map_err(io_err)? {
+            n += 1;
+            let v = match selector.filter(&buf) {
+                Ok(Some(x)) => x,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("line {}: {}", n, e);
+                    continue;
+                }
+            };
+            records += 1;
+            // skip the root
+            for (p, x) in pointer::walk(&v).into_iter().skip(1) {
+                let i = *index.entry(p.to_string()).or_insert_with(|| {
+                    pointers.push((p.to_string(), Seen::default()));
+                    pointers.len() - 1
+                });
+                let seen = &mut pointers[i].1;
+                seen.count += 1;
+                *seen.types.entry(type_name(x)).or_insert(0) += 1;
+                if seen.example.is_none() && !matches!(x, Value::Array(_) | Value::Object(_)) {
+                    seen.example = Some(x.clone());
+                }
+            }
+        }
+        let mut out = BufWriter::new(io::stdout().lock());
+        for (p, seen) in pointers {
+            let v = json!({
+                "pointer": p,
+                "count": seen.count,
+                "ratio": seen.count as f64 / records as f64,
+                "types": seen.types,
+                "example": seen.example,
+            });
+            writeln!(out, "{}", v).map_err(io_err)?;
+        }
+        out.flush().map_err(io_err)
+    }
+}
@@ -0,0 +1,23 @@
+use std::io;
+use tracing::level_filters::LevelFilter;
+
+/// Write the diagnostics to stderr.
+///
+/// `verbose` raises the level from warn: 1 for info, 2 for debug and 3 or more for trace.
+/// Write a json per event if `json`.
+pub(crate) fn init(verbose: u8, json: bool) {
+    let level = match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_max_level(level);
+    if json {
+        builder.json().init();
+    } else {
+        builder.init();
+    }
+}
@@ -0,0 +1,106 @@
+mod agg;
+mod annotate;
+mod args;
+mod bench;
+mod config;
+mod convert;
+mod decode;
+mod dedup;
+mod enrich;
+mod explain;
+mod format;
+mod grep;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod index;
+mod infer;
+mod logging;
+mod merge;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "parquet")]
+mod parquet_out;
+mod partition;
+mod pipeline;
+mod progress;
+mod redact;
+mod rows;
+mod scaffold;
+mod schema;
+mod sort;
+mod split;
+#[cfg(feature = "sqlite")]
+mod sqlite_out;
+mod table;
+mod timeout;
+mod validate;
+
+use std::process;
+use structopt::StructOpt;
+
+fn main() {
+    let opt = Opt::from_args();
+    logging::init(opt.verbose, opt.log_format == "json");
+    let r = match &opt.cmd {
+        None => opt.grep.run(),
+        Some(Command::Grep(x)) => x.run(),
+        Some(Command::Sort(x)) => x.run(),
+        Some(Command::Merge(x)) => x.run(),
+        Some(Command::Validate(x)) => x.run(),
+        Some(Command::Explain(x)) => x.run(),
+        Some(Command::Convert(x)) => x.run(),
+        Some(Command::Agg(x)) => x.run(),
+        Some(Command::Schema(x)) => x.run(),
+        Some(Command::Infer(x)) => x.run(),
+        Some(Command::Index(x)) => x.run(),
+        Some(Command::Scaffold(x)) => x.run(),
+        Some(Command::Bench(x)) => x.run(),
+        #[cfg(feature = "grpc")]
+        Some(Command::Grpc(x)) => x.run(),
+    };
+    if let Err(e) = r {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[derive(Debug, Clone, StructOpt)]
+/// Grep json.
+///
+/// Grep json from stdin by query.
+/// Without a subcommand, the options of `grep` are accepted.
+#[structopt(name = "jsongrep")]
+struct Opt {
+    #[structopt(flatten)]
+    grep: grep::GrepOpt,
+    /// Write more diagnostics to stderr: -v for info, -vv for debug, -vvv for trace.
+    ///
+    /// Only warnings by default.
+    #[structopt(short = "v", long = "verbose", parse(from_occurrences), global = true)]
+    verbose: u8,
+    /// Format of the diagnostics.
+    #[structopt(long = "log_format", default_value = "text", possible_values = &["text", "json"], global = true)]
+    log_format: String,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+// parsed only once
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, StructOpt)]
+enum Command {
+    Grep(grep::GrepOpt),
+    Sort(sort::SortOpt),
+    Merge(merge::MergeOpt),
+    Validate(validate::ValidateOpt),
+    Explain(explain::ExplainOpt),
+    Convert(convert::ConvertOpt),
+    Agg(agg::AggOpt),
+    Schema(schema::SchemaOpt),
+    Infer(infer::InferOpt),
+    Index(index::IndexOpt),
+    Scaffold(scaffold::ScaffoldOpt),
+    Bench(bench::BenchOpt),
+    #[cfg(feature = "grpc")]
+    Grpc(grpc::GrpcOpt),
+}
@@ -0,0 +1,108 @@
+use crate::args::{io_err, SortArgs};
+use crate::config::Config;
+use crate::grep::read_line;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::select::Query as Selector;
+use jsongrep::sort::Sort;
+use serde_json::value::Value;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Merge the files of jsons already sorted by the sort into a sorted output.
+///
+/// Keep only the current line of each file, so the memory does not grow with the size of the files.
+/// The equal lines are written in order of the files.
+/// The lines that are not json are written to stderr and skipped.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct MergeOpt {
+    #[structopt(flatten)]
+    sort: SortArgs,
+    /// Sorted files.
+    #[structopt(required = true, min_values = 1)]
+    inputs: Vec<PathBuf>,
+}
+
+/// The current line of a sorted file.
+struct Head {
+    input: BufReader<File>,
+    path: PathBuf,
+    /// Line number of `line`.
+    n: usize,
+    line: String,
+    value: Option<Value>,
+}
+
+impl Head {
+    /// Read the next json, `value` is `None` if reached EOF.
+    fn advance(&mut self, selector: &Selector) -> Result<()> {
+        self.value = None;
+        while read_line(&mut self.input, &mut self.line).map_err(io_err)? {
+            self.n += 1;
+            match selector.filter(&self.line) {
+                Ok(Some(v)) => {
+                    self.value = Some(v);
+                    break;
+                }
+                Ok(None) => continue,
+                Err(e) => eprintln!("{} line {}: {}", self.path.display(), self.n, e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MergeOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        self.sort.validate()?;
+        let sort = self.sort.clone().with_config(&mut Config::from_env());
+        if sort.reads_stdin() {
+            return Err(Error::new(ErrorCode::InvalidOption(
+                "give the sort by a file or sort_fd".to_owned(),
+            )));
+        }
+        let sort: Sort = sort.get_sort(1)?.ok_or_else(|| {
+            Error::new(ErrorCode::InvalidOption(
+                "sort or raw_sort is required".to_owned(),
+            ))
+        })?;
+        let selector = Selector::all();
+        let mut heads = Vec::with_capacity(self.inputs.len());
+        for path in &self.inputs {
+            let mut h = Head {
+                input: BufReader::new(File::open(path).map_err(io_err)?),
+                path: path.clone(),
+                n: 0,
+                line: String::new(),
+                value: None,
+            };
+            h.advance(&selector)?;
+            heads.push(h);
+        }
+        let mut out = BufWriter::new(io::stdout().lock());
+        loop {
+            // the first of the smallest heads, the number of the files is small
+            let mut min: Option<usize> = None;
+            for (i, h) in heads.iter().enumerate() {
+                let Some(v) = h.value.as_ref() else {
+                    continue;
+                };
+                match min {
+                    Some(j)
+                        if sort.compare(v, heads[j].value.as_ref().unwrap()) != Ordering::Less => {}
+                    _ => min = Some(i),
+                }
+            }
+            let Some(i) = min else {
+                break;
+            };
+            writeln!(out, "{}", heads[i].line).map_err(io_err)?;
+            heads[i].advance(&selector)?;
+        }
+        out.flush().map_err(io_err)
+    }
+}
@@ -0,0 +1,117 @@
+use crate::args::io_err;
+use jsongrep::error::{Error, Result};
+use jsongrep::grpc::Observer;
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, Registry, TextEncoder};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Time to wait for a request to be read or the response to be written,
+/// not to block the other scrapes by a stalled connection.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Counters of the records and the latency histogram of the evaluations.
+pub(crate) struct Metrics {
+    registry: Registry,
+    read: IntCounter,
+    matched: IntCounter,
+    filtered: IntCounter,
+    errored: IntCounter,
+    latency: Histogram,
+}
+
+fn prometheus_err(x: prometheus::Error) -> Error {
+    io_err(io::Error::other(x))
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Result<Metrics> {
+        let counter = |name: &str, help: &str| IntCounter::new(name, help).map_err(prometheus_err);
+        let m = Metrics {
+            registry: Registry::new(),
+            read: counter("jsongrep_records_read_total", "Records read.")?,
+            matched: counter(
+                "jsongrep_records_matched_total",
+                "Records meeting the query.",
+            )?,
+            filtered: counter(
+                "jsongrep_records_filtered_total",
+                "Records not meeting the query.",
+            )?,
+            errored: counter(
+                "jsongrep_records_errored_total",
+                "Records failed to be evaluated.",
+            )?,
+            latency: Histogram::with_opts(
+                HistogramOpts::new(
+                    "jsongrep_record_duration_seconds",
+                    "Time to evaluate a record.",
+                )
+                .buckets(prometheus::exponential_buckets(1e-6, 4.0, 10).map_err(prometheus_err)?),
+            )
+            .map_err(prometheus_err)?,
+        };
+        for c in [&m.read, &m.matched, &m.filtered, &m.errored] {
+            m.registry
+                .register(Box::new(c.clone()))
+                .map_err(prometheus_err)?;
+        }
+        m.registry
+            .register(Box::new(m.latency.clone()))
+            .map_err(prometheus_err)?;
+        Ok(m)
+    }
+    /// Serve `/metrics` on `addr` in the text format on another thread.
+    pub(crate) fn serve(self: &Arc<Self>, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr).map_err(io_err)?;
+        info!(addr = %addr, "serve metrics");
+        let m = Arc::clone(self);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if let Err(e) = stream.and_then(|x| m.respond(x)) {
+                    warn!(error = %e, "metrics request failed");
+                }
+            }
+        });
+        Ok(())
+    }
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        if (parts.next(), parts.next()) != (Some("GET"), Some("/metrics")) {
+            return stream.write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n");
+        }
+        let encoder = TextEncoder::new();
+        let mut body = Vec::new();
+        encoder
+            .encode(&self.registry.gather(), &mut body)
+            .map_err(io::Error::other)?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ncontent-length: {}\r\n\r\n",
+            encoder.format_type(),
+            body.len()
+        )?;
+        stream.write_all(&body)
+    }
+}
+
+impl Observer for Metrics {
+    fn observe(&self, outcome: std::result::Result<bool, &Error>, elapsed: Duration) {
+        self.read.inc();
+        match outcome {
+            Ok(true) => self.matched.inc(),
+            Ok(false) => self.filtered.inc(),
+            Err(_) => self.errored.inc(),
+        }
+        self.latency.observe(elapsed.as_secs_f64());
+    }
+}
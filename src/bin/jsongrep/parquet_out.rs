@@ -0,0 +1,229 @@
+use jsongrep::pointer;
+use jsongrep::pointer::Pointer;
+use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use serde_json::{from_str, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+/// The number of the rows of a row group.
+const ROW_GROUP_SIZE: usize = 8192;
+
+/// Type of a column, the json strings of the values of the mixed types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Bool,
+    Int,
+    Double,
+    String,
+    Json,
+}
+
+impl Kind {
+    fn of(v: &Value) -> Option<Kind> {
+        match v {
+            Value::Null => None,
+            Value::Bool(_) => Some(Kind::Bool),
+            Value::Number(x) if x.is_i64() => Some(Kind::Int),
+            Value::Number(_) => Some(Kind::Double),
+            Value::String(_) => Some(Kind::String),
+            _ => Some(Kind::Json),
+        }
+    }
+    fn merge(self, other: Kind) -> Kind {
+        match (self, other) {
+            (x, y) if x == y => x,
+            (Kind::Int, Kind::Double) | (Kind::Double, Kind::Int) => Kind::Double,
+            _ => Kind::Json,
+        }
+    }
+}
+
+/// An optional column of the value at the pointer.
+struct Column {
+    pointer: Pointer,
+    kind: Option<Kind>,
+}
+
+/// Write the jsons into a Parquet file, the columns inferred from the first `sample` jsons.
+///
+/// A column is the value at a pointer into the objects, named the pointer without the leading `/`,
+/// or `value` for the root.
+/// The values of the types other than the inferred one are written as null.
+pub(crate) struct ParquetWriter {
+    file: Option<File>,
+    writer: Option<SerializedFileWriter<File>>,
+    sample: usize,
+    columns: Vec<Column>,
+    rows: Vec<Value>,
+}
+
+impl ParquetWriter {
+    pub(crate) fn new(file: File, sample: usize) -> ParquetWriter {
+        ParquetWriter {
+            file: Some(file),
+            writer: None,
+            sample,
+            columns: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+    pub(crate) fn write(&mut self, line: &str) -> io::Result<()> {
+        self.rows.push(from_str(line).map_err(io::Error::other)?);
+        if self.writer.is_none() && self.rows.len() >= self.sample {
+            self.open()?;
+        }
+        if self.writer.is_some() && self.rows.len() >= ROW_GROUP_SIZE {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        if self.writer.is_none() {
+            self.open()?;
+        }
+        if !self.rows.is_empty() {
+            self.write_row_group()?;
+        }
+        if let Some(w) = self.writer.take() {
+            w.close().map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+    /// Infer the columns from the buffered rows and start the file.
+    fn open(&mut self) -> io::Result<()> {
+        let mut index = HashMap::new();
+        let columns = &mut self.columns;
+        for v in &self.rows {
+            for (p, x) in leaves(v) {
+                let i = *index.entry(p.to_string()).or_insert_with(|| {
+                    columns.push(Column {
+                        pointer: p.clone(),
+                        kind: None,
+                    });
+                    columns.len() - 1
+                });
+                let c = &mut columns[i];
+                c.kind = match (c.kind, Kind::of(x)) {
+                    (Some(a), Some(b)) => Some(a.merge(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+        let fields = self
+            .columns
+            .iter()
+            .map(|c| {
+                let name = match c.pointer.as_str() {
+                    "" => "value",
+                    x => &x[1..],
+                };
+                let (physical, converted) = match c.kind {
+                    Some(Kind::Bool) => (PhysicalType::BOOLEAN, ConvertedType::NONE),
+                    Some(Kind::Int) => (PhysicalType::INT64, ConvertedType::NONE),
+                    Some(Kind::Double) => (PhysicalType::DOUBLE, ConvertedType::NONE),
+                    Some(Kind::String) => (PhysicalType::BYTE_ARRAY, ConvertedType::UTF8),
+                    Some(Kind::Json) | None => (PhysicalType::BYTE_ARRAY, ConvertedType::JSON),
+                };
+                Type::primitive_type_builder(name, physical)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .with_converted_type(converted)
+                    .build()
+                    .map(Arc::new)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io::Error::other)?;
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()
+            .map_err(io::Error::other)?;
+        let file = self.file.take().unwrap();
+        let props = Arc::new(WriterProperties::builder().build());
+        self.writer = Some(
+            SerializedFileWriter::new(file, Arc::new(schema), props).map_err(io::Error::other)?,
+        );
+        Ok(())
+    }
+    fn write_row_group(&mut self) -> io::Result<()> {
+        let rows = std::mem::take(&mut self.rows);
+        let mut rg = self
+            .writer
+            .as_mut()
+            .unwrap()
+            .next_row_group()
+            .map_err(io::Error::other)?;
+        for c in &self.columns {
+            let mut w = match rg.next_column().map_err(io::Error::other)? {
+                Some(x) => x,
+                None => break,
+            };
+            let values = rows.iter().map(|v| c.pointer.get(v));
+            let r = match c.kind {
+                Some(Kind::Bool) => {
+                    let (x, d) = levels(values.map(|v| v.and_then(Value::as_bool)));
+                    w.typed::<BoolType>().write_batch(&x, Some(&d), None)
+                }
+                Some(Kind::Int) => {
+                    let (x, d) = levels(values.map(|v| v.and_then(Value::as_i64)));
+                    w.typed::<Int64Type>().write_batch(&x, Some(&d), None)
+                }
+                Some(Kind::Double) => {
+                    let (x, d) = levels(values.map(|v| v.and_then(Value::as_f64)));
+                    w.typed::<DoubleType>().write_batch(&x, Some(&d), None)
+                }
+                Some(Kind::String) => {
+                    let (x, d) = levels(values.map(|v| {
+                        v.and_then(Value::as_str)
+                            .map(|s| ByteArray::from(s.as_bytes().to_vec()))
+                    }));
+                    w.typed::<ByteArrayType>().write_batch(&x, Some(&d), None)
+                }
+                Some(Kind::Json) | None => {
+                    let (x, d) = levels(values.map(|v| {
+                        v.filter(|x| !x.is_null())
+                            .map(|x| ByteArray::from(x.to_string().into_bytes()))
+                    }));
+                    w.typed::<ByteArrayType>().write_batch(&x, Some(&d), None)
+                }
+            };
+            r.map_err(io::Error::other)?;
+            w.close().map_err(io::Error::other)?;
+        }
+        rg.close().map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+/// Return the present values and the definition levels, 0 for null.
+fn levels<T>(values: impl Iterator<Item = Option<T>>) -> (Vec<T>, Vec<i16>) {
+    let mut x = Vec::new();
+    let mut d = Vec::new();
+    for v in values {
+        d.push(i16::from(v.is_some()));
+        x.extend(v);
+    }
+    (x, d)
+}
+
+/// Return the values with their pointers, descending into the objects only.
+fn leaves(v: &Value) -> Vec<(Pointer, &Value)> {
+    let mut objects: Vec<String> = Vec::new();
+    let mut acc = Vec::new();
+    for (p, x) in pointer::walk(v) {
+        let parent = p.as_str().rsplit_once('/').map_or("", |x| x.0);
+        if !p.as_str().is_empty() && !objects.iter().any(|x| x == parent) {
+            continue; // in an array
+        }
+        if x.is_object() {
+            objects.push(p.as_str().to_owned());
+        } else {
+            acc.push((p, x));
+        }
+    }
+    acc
+}
@@ -0,0 +1,144 @@
+use crate::args::io_err;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::pointer::Pointer;
+use serde_json::from_str;
+use serde_json::value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+/// The number of the files kept open at once.
+const MAX_OPEN_FILES: usize = 128;
+
+/// Write the jsons into the files named after the values at a pointer.
+///
+/// Keep at most `max_open` files open, closing the least recently written one
+/// and reopening it to append when its value comes again.
+pub(crate) struct Partitions {
+    pointer: Pointer,
+    dir: PathBuf,
+    max_open: usize,
+    /// File name -> the open file and the tick it was written last.
+    files: HashMap<String, (BufWriter<File>, u64)>,
+    /// Names of the files created by this run, to append instead of truncating.
+    created: HashSet<String>,
+    tick: u64,
+}
+
+impl Partitions {
+    pub(crate) fn new(pointer: Pointer, dir: PathBuf) -> Partitions {
+        Partitions::with_max_open(pointer, dir, MAX_OPEN_FILES)
+    }
+    fn with_max_open(pointer: Pointer, dir: PathBuf, max_open: usize) -> Partitions {
+        Partitions {
+            pointer,
+            dir,
+            max_open,
+            files: HashMap::new(),
+            created: HashSet::new(),
+            tick: 0,
+        }
+    }
+    /// Write `line` into the file of its value, `v` is `line` if parsed.
+    pub(crate) fn write(&mut self, line: &str, v: Option<&Value>) -> Result<()> {
+        let parsed: Value;
+        let v = match v {
+            Some(x) => x,
+            None => {
+                parsed = from_str(line).map_err(|x| Error::new(ErrorCode::Json(x)))?;
+                &parsed
+            }
+        };
+        let name = file_name(self.pointer.get(v).unwrap_or(&Value::Null));
+        self.tick += 1;
+        if !self.files.contains_key(&name) {
+            if self.files.len() >= self.max_open {
+                self.close_least_recent()?;
+            }
+            let path = self.dir.join(&name);
+            let f = if self.created.contains(&name) {
+                OpenOptions::new().append(true).open(path)
+            } else {
+                File::create(path)
+            }
+            .map_err(io_err)?;
+            self.created.insert(name.clone());
+            self.files.insert(name.clone(), (BufWriter::new(f), 0));
+        }
+        let (f, tick) = self.files.get_mut(&name).unwrap();
+        *tick = self.tick;
+        writeln!(f, "{}", line).map_err(io_err)
+    }
+    fn close_least_recent(&mut self) -> Result<()> {
+        let name = match self.files.iter().min_by_key(|(_, (_, t))| *t) {
+            Some((x, _)) => x.clone(),
+            None => return Ok(()),
+        };
+        let (mut f, _) = self.files.remove(&name).unwrap();
+        f.flush().map_err(io_err)
+    }
+    /// Flush the files, return the number of them.
+    pub(crate) fn finish(&mut self) -> Result<usize> {
+        for (f, _) in self.files.values_mut() {
+            f.flush().map_err(io_err)?;
+        }
+        Ok(self.created.len())
+    }
+}
+
+/// Return the file name of the value: a string by itself, the others by their json prefixed by `=`,
+/// with the unsafe characters percent-encoded.
+///
+/// `=` of a string is encoded, so the string `"1"` and the number `1` are named differently.
+/// The empty string is named by its json too, not to make the hidden file `.ndjson`.
+fn file_name(v: &Value) -> String {
+    let (mut name, s) = match v {
+        Value::String(x) if !x.is_empty() => (String::with_capacity(x.len()), x.clone()),
+        x => ("=".to_owned(), x.to_string()),
+    };
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            // a leading dot makes the file hidden, or the parent
+            b'.' if i == 0 => name.push_str("%2E"),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => name.push(b as char),
+            _ => name.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    name.push_str(".ndjson");
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn file_name_by_type() {
+        assert_eq!("1.ndjson", file_name(&json!("1")));
+        assert_eq!("=1.ndjson", file_name(&json!(1)));
+        assert_eq!("%3D1.ndjson", file_name(&json!("=1")));
+        assert_eq!("=null.ndjson", file_name(&Value::Null));
+        assert_eq!("%2E..ndjson", file_name(&json!("..")));
+        assert_eq!("a%2Fb.ndjson", file_name(&json!("a/b")));
+        assert_eq!("=%22%22.ndjson", file_name(&json!("")));
+        assert_eq!("_.ndjson", file_name(&json!("_")));
+    }
+    #[test]
+    fn reopen_closed_files() {
+        let dir = std::env::temp_dir().join(format!("jsongrep-partition-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut p = Partitions::with_max_open(Pointer::parse("/k").unwrap(), dir.clone(), 2);
+        for k in ["a", "b", "c", "a", "b", "c"] {
+            p.write(&json!({ "k": k }).to_string(), None).unwrap();
+        }
+        assert_eq!(3, p.finish().unwrap());
+        assert!(p.files.len() <= 2);
+        let got = fs::read_to_string(dir.join("a.ndjson")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!("{\"k\":\"a\"}\n{\"k\":\"a\"}\n", got);
+    }
+}
@@ -0,0 +1,235 @@
+use crate::args::io_err;
+use crate::grep::read_line;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::expr;
+use jsongrep::pointer::Pointer;
+use jsongrep::query::closure::ClosureQuery;
+use jsongrep::query::{EvaluableQuery, Query};
+use jsongrep::raw_query::Query as RawQuery;
+use jsongrep::raw_sort::Sort as RawSort;
+use jsongrep::select::Query as Selector;
+use jsongrep::sort::Sort;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::value::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::prelude::*;
+use tracing::info;
+
+/// A stage of the `pipeline` of the config.
+///
+/// ```yaml
+/// pipeline:
+///   - {type: expr, expr: '/level eq "error"'}
+///   - {type: project, fields: {id: /id, code: /res/code}}
+///   - {type: aggregate, by: [/code]}
+///   - {type: sort, sort: [{p: /count, ord: desc}]}
+///   - {type: limit, n: 10}
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum StageConfig {
+    /// Keep the jsons meeting the query, the same as `raw_query`.
+    Filter(RawQuery),
+    /// Keep the jsons meeting the expression, the same as `expr`.
+    Expr { expr: String },
+    /// Replace the json by an object of the values at the pointers by the keys,
+    /// a missing value is null.
+    Project { fields: BTreeMap<String, String> },
+    /// Replace the jsons by {"key":[...],"count":N} per the values at the pointers
+    /// in order of first occurrence.
+    Aggregate { by: Vec<String> },
+    /// Sort the jsons, the same as `raw_sort`.
+    Sort(RawSort),
+    /// Keep the first `n` jsons.
+    Limit { n: usize },
+}
+
+/// Destination of the jsons from a stage.
+type Out<'a> = dyn FnMut(Value) -> Result<()> + 'a;
+
+trait Stage {
+    fn push(&mut self, v: Value, out: &mut Out<'_>) -> Result<()>;
+    /// Write the jsons kept until the end of the input.
+    fn finish(&mut self, _out: &mut Out<'_>) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct Filter(ClosureQuery);
+
+impl Stage for Filter {
+    fn push(&mut self, v: Value, out: &mut Out<'_>) -> Result<()> {
+        if self.0.eval(&v)? {
+            out(v)?;
+        }
+        Ok(())
+    }
+}
+
+struct Project(Vec<(String, Pointer)>);
+
+impl Stage for Project {
+    fn push(&mut self, v: Value, out: &mut Out<'_>) -> Result<()> {
+        let m: Map<String, Value> = self
+            .0
+            .iter()
+            .map(|(k, p)| (k.clone(), p.get(&v).cloned().unwrap_or(Value::Null)))
+            .collect();
+        out(Value::Object(m))
+    }
+}
+
+#[derive(Default)]
+struct Aggregate {
+    by: Vec<Pointer>,
+    groups: Vec<(Value, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl Stage for Aggregate {
+    fn push(&mut self, v: Value, _out: &mut Out<'_>) -> Result<()> {
+        let key = Value::Array(
+            self.by
+                .iter()
+                .map(|p| p.get(&v).cloned().unwrap_or(Value::Null))
+                .collect(),
+        );
+        let groups = &mut self.groups;
+        let i = *self.index.entry(key.to_string()).or_insert_with(|| {
+            groups.push((key, 0));
+            groups.len() - 1
+        });
+        self.groups[i].1 += 1;
+        Ok(())
+    }
+    fn finish(&mut self, out: &mut Out<'_>) -> Result<()> {
+        for (key, count) in self.groups.drain(..) {
+            out(json!({ "key": key, "count": count }))?;
+        }
+        Ok(())
+    }
+}
+
+struct Sorter(Option<Sort>);
+
+impl Stage for Sorter {
+    fn push(&mut self, v: Value, _out: &mut Out<'_>) -> Result<()> {
+        self.0.as_mut().unwrap().try_push(v)
+    }
+    fn finish(&mut self, out: &mut Out<'_>) -> Result<()> {
+        for v in self.0.take().unwrap().into_sorted_iter() {
+            out(v)?;
+        }
+        Ok(())
+    }
+}
+
+struct Limit(usize);
+
+impl Stage for Limit {
+    fn push(&mut self, v: Value, out: &mut Out<'_>) -> Result<()> {
+        if self.0 > 0 {
+            self.0 -= 1;
+            out(v)?;
+        }
+        Ok(())
+    }
+}
+
+impl StageConfig {
+    fn build(&self, threads: usize) -> Result<Box<dyn Stage>> {
+        let parse_all = |x: &[String]| {
+            x.iter()
+                .map(|x| Pointer::parse(x))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(match self {
+            StageConfig::Filter(q) => Box::new(Filter(Query::try_from(q.clone())?.to_closure())),
+            StageConfig::Expr { expr: e } => {
+                Box::new(Filter(Query::try_from(expr::parse(e)?)?.to_closure()))
+            }
+            StageConfig::Project { fields } => Box::new(Project(
+                fields
+                    .iter()
+                    .map(|(k, p)| Ok((k.clone(), Pointer::parse(p)?)))
+                    .collect::<Result<_>>()?,
+            )),
+            StageConfig::Aggregate { by } => Box::new(Aggregate {
+                by: parse_all(by)?,
+                ..Aggregate::default()
+            }),
+            StageConfig::Sort(s) => {
+                for p in &s.sort {
+                    Pointer::parse(&p.pointer)?;
+                }
+                Box::new(Sorter(Some(Sort::from(s.clone()).with_threads(threads))))
+            }
+            StageConfig::Limit { n } => Box::new(Limit(*n)),
+        })
+    }
+}
+
+/// Pass `v` through the stages into `sink`.
+fn feed(stages: &mut [Box<dyn Stage>], v: Value, sink: &mut Out<'_>) -> Result<()> {
+    match stages.split_first_mut() {
+        None => sink(v),
+        Some((first, rest)) => first.push(v, &mut |x| feed(rest, x, sink)),
+    }
+}
+
+/// Flush the stages in order into `sink`.
+fn finish(stages: &mut [Box<dyn Stage>], sink: &mut Out<'_>) -> Result<()> {
+    for i in 0..stages.len() {
+        let (head, rest) = stages.split_at_mut(i + 1);
+        head[i].finish(&mut |x| feed(rest, x, sink))?;
+    }
+    Ok(())
+}
+
+/// Pass the jsons of `input` selected by `selector` through the stages, write the results to `out`.
+///
+/// The lines failed to be selected or to pass a stage are written to stderr and skipped.
+pub(crate) fn run(
+    config: &[StageConfig],
+    threads: usize,
+    selector: &Selector,
+    mut input: impl BufRead,
+    mut out: impl Write,
+) -> Result<()> {
+    let mut stages = config
+        .iter()
+        .map(|x| x.build(threads))
+        .collect::<Result<Vec<_>>>()?;
+    info!(stages = stages.len(), "run pipeline");
+    let mut sink = |v: Value| writeln!(out, "{}", v).map_err(io_err);
+    let mut buf = String::new();
+    let mut n = 0;
+    while read_line(&mut input, &mut buf).map_err(io_err)? {
+        n += 1;
+        let r = selector
+            .filter(&buf)
+            .and_then(|x| x.map_or(Ok(()), |v| feed(&mut stages, v, &mut sink)));
+        match r {
+            Err(e) if e.kind() == jsongrep::error::ErrorKind::Io => return Err(e),
+            Err(e) => eprintln!("line {}: {}", n, e),
+            Ok(()) => continue,
+        }
+    }
+    finish(&mut stages, &mut sink)?;
+    out.flush().map_err(io_err)
+}
+
+/// Reject the config of the pipeline mixed with the options it does not apply,
+/// `given` are the names of such options given.
+pub(crate) fn check(given: &[&str]) -> Result<()> {
+    if given.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new(ErrorCode::InvalidOption(format!(
+            "pipeline is exclusive with {}, use the stages",
+            given.join(", ")
+        ))))
+    }
+}
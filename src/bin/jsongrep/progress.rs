@@ -0,0 +1,124 @@
+use std::cell::Cell;
+use std::io;
+use std::io::prelude::*;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Minimum interval of rendering the progress.
+const RENDER_INTERVAL: Duration = Duration::from_millis(200);
+/// Width of the progress bar.
+const BAR_WIDTH: usize = 20;
+
+/// Reader that counts the bytes read.
+pub(crate) struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R> CountingReader<R> {
+    pub(crate) fn new(inner: R, count: Rc<Cell<u64>>) -> Self {
+        CountingReader { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+    fn consume(&mut self, amt: usize) {
+        self.count.set(self.count.get() + amt as u64);
+        self.inner.consume(amt)
+    }
+}
+
+/// Progress of reading the input, written to stderr.
+pub(crate) struct Progress {
+    /// The number of bytes read.
+    read: Rc<Cell<u64>>,
+    /// The size of the input, `None` if unknown, e.g. stdin.
+    total: Option<u64>,
+    start: Instant,
+    rendered: Option<Instant>,
+}
+
+impl Progress {
+    pub(crate) fn new(total: Option<u64>) -> Progress {
+        Progress {
+            read: Rc::new(Cell::new(0)),
+            total,
+            start: Instant::now(),
+            rendered: None,
+        }
+    }
+    /// Return the counter to be shared with [`CountingReader`].
+    pub(crate) fn counter(&self) -> Rc<Cell<u64>> {
+        Rc::clone(&self.read)
+    }
+    /// Render the progress unless rendered recently.
+    pub(crate) fn update(&mut self, matched: usize) {
+        if matches!(self.rendered, Some(x) if x.elapsed() < RENDER_INTERVAL) {
+            return;
+        }
+        self.render(matched);
+    }
+    /// Render the last progress and end the line.
+    pub(crate) fn finish(&mut self, matched: usize) {
+        self.render(matched);
+        eprintln!();
+    }
+    fn render(&mut self, matched: usize) {
+        self.rendered = Some(Instant::now());
+        let read = self.read.get();
+        let status = match self.total.filter(|x| *x > 0) {
+            Some(total) => {
+                let ratio = (read as f64 / total as f64).min(1.0);
+                let filled = (ratio * BAR_WIDTH as f64) as usize;
+                let elapsed = self.start.elapsed().as_secs_f64();
+                let eta = if read > 0 {
+                    format!(
+                        "{:.0}s",
+                        elapsed * (total.saturating_sub(read)) as f64 / read as f64
+                    )
+                } else {
+                    "-".to_owned()
+                };
+                format!(
+                    "[{}{}] {:5.1}% {} / {} ETA {}",
+                    "#".repeat(filled),
+                    "-".repeat(BAR_WIDTH - filled),
+                    ratio * 100.0,
+                    human_bytes(read),
+                    human_bytes(total),
+                    eta
+                )
+            }
+            None => human_bytes(read),
+        };
+        eprint!("\r{}, {} matched", status, matched);
+    }
+}
+
+fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{}B", n);
+    }
+    let mut x = n as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for u in &UNITS[1..] {
+        if x < 1024.0 {
+            break;
+        }
+        x /= 1024.0;
+        unit = u;
+    }
+    format!("{:.1}{}", x, unit)
+}
@@ -0,0 +1,59 @@
+use jsongrep::pointer::{walk, Pointer};
+use regex::Regex;
+use serde_json::value::Value;
+use sha2::{Digest, Sha256};
+
+/// Replacement of the redacted values.
+const MASK: &str = "***";
+
+/// Replace the sensitive values of the jsons.
+pub(crate) struct Redactor {
+    pointers: Vec<Pointer>,
+    /// Patterns of the pointers of the values to redact.
+    patterns: Vec<Regex>,
+    /// Replace by the hash instead of the mask, to keep the same values correlated.
+    hash: bool,
+}
+
+impl Redactor {
+    pub(crate) fn new(pointers: Vec<Pointer>, patterns: Vec<Regex>, hash: bool) -> Redactor {
+        Redactor {
+            pointers,
+            patterns,
+            hash,
+        }
+    }
+    /// Replace the values at the pointers and at the pointers matching the patterns.
+    /// The missing values are left missing.
+    pub(crate) fn redact(&self, v: &mut Value) {
+        let mut targets: Vec<String> = self.pointers.iter().map(|x| x.to_string()).collect();
+        if !self.patterns.is_empty() {
+            targets.extend(
+                walk(v)
+                    .into_iter()
+                    .map(|(p, _)| p.to_string())
+                    .filter(|p| !p.is_empty() && self.patterns.iter().any(|r| r.is_match(p))),
+            );
+        }
+        for p in targets {
+            if let Some(x) = v.pointer_mut(&p) {
+                *x = self.replacement(x);
+            }
+        }
+    }
+    fn replacement(&self, v: &Value) -> Value {
+        if self.hash {
+            Value::String(sha256(v))
+        } else {
+            Value::String(MASK.to_owned())
+        }
+    }
+}
+
+/// Lowercase hex of the sha256 of the compact json of `v`, whose keys are sorted.
+pub(crate) fn sha256(v: &Value) -> String {
+    Sha256::digest(v.to_string())
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
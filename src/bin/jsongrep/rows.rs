@@ -0,0 +1,67 @@
+use std::str::FromStr;
+
+/// Ranges of the 1-based indexes of the lines, e.g. 100-200,5000,9000-.
+#[derive(Debug, Clone)]
+pub(crate) struct Rows(Vec<(usize, Option<usize>)>);
+
+impl FromStr for Rows {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index = |x: &str| match x.trim().parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!("invalid row {:?} of {}", x, s)),
+            Ok(n) => Ok(n),
+        };
+        let ranges = s
+            .split(',')
+            .map(|x| match x.split_once('-') {
+                None => index(x).map(|n| (n, Some(n))),
+                Some((a, "")) => index(a).map(|n| (n, None)),
+                Some((a, b)) => match (index(a)?, index(b)?) {
+                    (a, b) if a <= b => Ok((a, Some(b))),
+                    _ => Err(format!("invalid range {:?} of {}", x, s)),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Rows(ranges))
+    }
+}
+
+impl Rows {
+    pub(crate) fn contains(&self, n: usize) -> bool {
+        self.0
+            .iter()
+            .any(|(a, b)| *a <= n && b.is_none_or(|b| n <= b))
+    }
+    /// Return the last index in the ranges, `None` if unbounded.
+    pub(crate) fn last(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .map(|x| x.1)
+            .try_fold(0, |acc, x| x.map(|x| acc.max(x)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rows() {
+        let r: Rows = "100-200,5000,9000-".parse().unwrap();
+        assert!(!r.contains(99));
+        assert!(r.contains(100));
+        assert!(r.contains(200));
+        assert!(!r.contains(201));
+        assert!(r.contains(5000));
+        assert!(!r.contains(8999));
+        assert!(r.contains(usize::MAX));
+        assert_eq!(None, r.last());
+        assert_eq!(Some(200), "3, 100-200".parse::<Rows>().unwrap().last());
+    }
+    #[test]
+    fn parse_invalid_rows() {
+        for x in ["", "0", "a", "2-1", "-3", "1,,2"] {
+            assert!(x.parse::<Rows>().is_err(), "{}", x);
+        }
+    }
+}
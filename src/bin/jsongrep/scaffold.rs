@@ -0,0 +1,68 @@
+use crate::args::read_file;
+use crate::explain::print_pretty;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::pointer;
+use jsongrep::raw_query as raw;
+use serde_json::value::Value;
+use serde_json::{from_str, to_value};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Write a query to edit from an example json.
+///
+/// The query has an `eq` pair per leaf of the example, the pairs are joined by `and`.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct ScaffoldOpt {
+    /// Example json file.
+    #[structopt(parse(from_os_str))]
+    example: PathBuf,
+}
+
+/// Return the raw value of the scalar `v`, `None` if `v` is an array or an object.
+fn leaf(v: &Value) -> Option<raw::Value> {
+    match v {
+        Value::Null => Some(raw::Value::Null),
+        Value::Bool(x) => Some(raw::Value::Bool { value: *x }),
+        Value::Number(x) => Some(raw::Value::Number {
+            value: x.as_f64().unwrap(),
+        }),
+        Value::String(x) => Some(raw::Value::String { value: x.clone() }),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+impl ScaffoldOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let example: Value =
+            from_str(&read_file(&self.example)?).map_err(|x| Error::new(ErrorCode::Json(x)))?;
+        let mut pair: Vec<_> = pointer::walk(&example)
+            .into_iter()
+            .filter_map(|(p, v)| {
+                leaf(v).map(|value| raw::QueryCondition::Raw {
+                    pair: raw::QueryPair {
+                        pointer: p.to_string(),
+                        condition: raw::Condition::Equal { value },
+                        on_mismatch: None,
+                        coerce: false,
+                        trim: false,
+                        collapse_ws: false,
+                    },
+                })
+            })
+            .collect();
+        let query = match pair.len() {
+            0 => {
+                return Err(Error::new(ErrorCode::InvalidOption(
+                    "example has no leaves".to_owned(),
+                )))
+            }
+            1 => pair.pop().unwrap(),
+            _ => raw::QueryCondition::And { pair },
+        };
+        let q = raw::Query {
+            query,
+            on_mismatch: None,
+        };
+        print_pretty(&to_value(q).map_err(|x| Error::new(ErrorCode::Json(x)))?)
+    }
+}
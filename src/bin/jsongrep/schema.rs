@@ -0,0 +1,28 @@
+use crate::explain::print_pretty;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::raw_query::Query as RawQuery;
+use jsongrep::raw_sort::Sort as RawSort;
+use schemars::schema_for;
+use serde_json::to_value;
+use structopt::StructOpt;
+
+/// Write the JSON Schema of the query or the sort format.
+///
+/// e.g. `"$schema"` of a query file or the json schema settings of an editor.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct SchemaOpt {
+    /// Format to describe.
+    #[structopt(possible_values = &["query", "sort"])]
+    target: String,
+}
+
+impl SchemaOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        let schema = if self.target == "query" {
+            schema_for!(RawQuery)
+        } else {
+            schema_for!(RawSort)
+        };
+        print_pretty(&to_value(schema).map_err(|x| Error::new(ErrorCode::Json(x)))?)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::args::{io_err, PageArgs, SortArgs};
+use crate::config::Config;
+use crate::grep::read_line;
+use jsongrep::error::{Error, ErrorCode, Result};
+use jsongrep::select::Query as Selector;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use structopt::StructOpt;
+
+/// Sort the jsons of stdin.
+///
+/// The sort is read from JSONGREP_SORT_FILE unless specified.
+/// The lines that are not json are written to stderr and skipped.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct SortOpt {
+    #[structopt(flatten)]
+    sort: SortArgs,
+    #[structopt(flatten)]
+    page: PageArgs,
+    /// The number of threads to sort the lines.
+    #[structopt(long = "threads", default_value = "1")]
+    threads: usize,
+}
+
+impl SortOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        self.sort.validate()?;
+        let sort = self.sort.clone().with_config(&mut Config::from_env());
+        if self.threads == 0 {
+            return Err(Error::new(ErrorCode::InvalidOption(
+                "threads should be positive".to_owned(),
+            )));
+        }
+        if sort.reads_stdin() {
+            return Err(Error::new(ErrorCode::InvalidOption(
+                "stdin is for the lines, give the sort by sort_fd".to_owned(),
+            )));
+        }
+        let mut sort = sort.get_sort(self.threads)?.ok_or_else(|| {
+            Error::new(ErrorCode::InvalidOption(
+                "sort or raw_sort is required".to_owned(),
+            ))
+        })?;
+        let selector = Selector::all();
+        let mut lines = Vec::new();
+        let mut stdin = io::stdin().lock();
+        let mut buf = String::new();
+        let mut n = 0;
+        while read_line(&mut stdin, &mut buf).map_err(io_err)? {
+            n += 1;
+            match selector.filter(&buf) {
+                Ok(Some(v)) => match sort.try_add(v) {
+                    Ok(_) => lines.push(buf.clone()),
+                    Err(e) => eprintln!("line {}: {}", n, e),
+                },
+                Ok(None) => continue,
+                Err(e) => eprintln!("line {}: {}", n, e),
+            }
+        }
+        let mut out = BufWriter::new(io::stdout().lock());
+        for i in self.page.page(sort.sorted_indexes().into_iter()) {
+            writeln!(out, "{}", lines[i]).map_err(io_err)?;
+        }
+        out.flush().map_err(io_err)
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// Parse a size like 256MB: K, M and G are powers of 1024, KB, MB and GB are of 1000.
+pub(crate) fn parse_size(s: &str) -> Result<u64, String> {
+    let i = s.find(|x: char| !x.is_ascii_digit()).unwrap_or(s.len());
+    let n: u64 = s[..i].parse().map_err(|_| format!("invalid size {}", s))?;
+    let unit = match &s[i..] {
+        "" | "B" => 1,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        x => return Err(format!("unknown unit {} of {}", x, s)),
+    };
+    n.checked_mul(unit)
+        .ok_or_else(|| format!("too large size {}", s))
+}
+
+/// Write the lines into the numbered files PREFIX00000.ndjson, PREFIX00001.ndjson, ...
+/// switching to the next file before it exceeds the limits.
+///
+/// A line is never split, the line longer than `bytes` is written into a file alone.
+/// The files are created when the first line is written to them.
+pub(crate) struct SplitWriter {
+    prefix: String,
+    lines: Option<usize>,
+    bytes: Option<u64>,
+    out: Option<BufWriter<File>>,
+    /// Index of the next file.
+    index: usize,
+    /// The number of the lines and the bytes written to the current file.
+    written: (usize, u64),
+    /// The line not terminated yet.
+    pending: Vec<u8>,
+    /// The head of the pending line was already written to the current file by flush.
+    continued: bool,
+}
+
+impl SplitWriter {
+    pub(crate) fn new(prefix: String, lines: Option<usize>, bytes: Option<u64>) -> SplitWriter {
+        SplitWriter {
+            prefix,
+            lines,
+            bytes,
+            out: None,
+            index: 0,
+            written: (0, 0),
+            pending: Vec::new(),
+            continued: false,
+        }
+    }
+    /// Return the file to write the line of `len` bytes into.
+    fn target(&mut self, len: u64) -> io::Result<&mut BufWriter<File>> {
+        let (lines, bytes) = self.written;
+        let full = lines > 0
+            && (matches!(self.lines, Some(x) if lines >= x)
+                || matches!(self.bytes, Some(x) if bytes + len > x));
+        if self.out.is_none() || (full && !self.continued) {
+            if let Some(x) = self.out.as_mut() {
+                x.flush()?;
+            }
+            let path = format!("{}{:05}.ndjson", self.prefix, self.index);
+            self.out = Some(BufWriter::new(File::create(path)?));
+            self.index += 1;
+            self.written = (0, 0);
+        }
+        Ok(self.out.as_mut().unwrap())
+    }
+    /// Write the pending bytes, as a whole line if `terminated`.
+    fn write_pending(&mut self, terminated: bool) -> io::Result<()> {
+        let len = self.pending.len() as u64;
+        let pending = std::mem::take(&mut self.pending);
+        self.target(len)?.write_all(&pending)?;
+        self.written.1 += len;
+        if terminated {
+            self.written.0 += 1;
+        }
+        self.continued = !terminated;
+        Ok(())
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(i) = rest.iter().position(|x| *x == b'\n') {
+            self.pending.extend_from_slice(&rest[..=i]);
+            self.write_pending(true)?;
+            rest = &rest[i + 1..];
+        }
+        self.pending.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.write_pending(false)?;
+        }
+        match self.out.as_mut() {
+            Some(x) => x.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SplitWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(Ok(10), parse_size("10"));
+        assert_eq!(Ok(10), parse_size("10B"));
+        assert_eq!(Ok(2048), parse_size("2K"));
+        assert_eq!(Ok(2048), parse_size("2KiB"));
+        assert_eq!(Ok(2000), parse_size("2KB"));
+        assert_eq!(Ok(256 << 20), parse_size("256M"));
+        assert_eq!(Ok(256_000_000), parse_size("256MB"));
+        assert_eq!(Ok(3 << 30), parse_size("3GiB"));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("1TB").is_err());
+        assert!(parse_size("18446744073709551615K").is_err());
+    }
+    #[test]
+    fn split_lines_and_bytes() {
+        let dir = std::env::temp_dir().join(format!("jsongrep-split-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let prefix = dir.join("part-").to_str().unwrap().to_owned();
+        {
+            let mut w = SplitWriter::new(prefix.clone(), Some(2), Some(8));
+            // a line is split across the writes
+            w.write_all(b"a\nb\nc").unwrap();
+            w.write_all(b"\nlonger line\nd\n").unwrap();
+        }
+        let read = |i: usize| fs::read_to_string(format!("{}{:05}.ndjson", prefix, i)).ok();
+        let got: Vec<_> = (0..5).map(read).collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            vec![
+                Some("a\nb\n".to_owned()),
+                Some("c\n".to_owned()),
+                Some("longer line\n".to_owned()),
+                Some("d\n".to_owned()),
+                None,
+            ],
+            got
+        );
+    }
+}
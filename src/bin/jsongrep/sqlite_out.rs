@@ -0,0 +1,98 @@
+use jsongrep::pointer::Pointer;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection};
+use serde_json::{from_str, Value};
+use std::io;
+use std::path::Path;
+
+/// The number of the rows inserted in a transaction.
+const BATCH_SIZE: usize = 10000;
+
+/// Insert the jsons into a SQLite table, the values at the pointers as the columns
+/// and the whole json as the `json` column.
+///
+/// A column is named the pointer without the leading `/`.
+/// The table is created if missing.
+pub(crate) struct SqliteWriter {
+    conn: Connection,
+    columns: Vec<Pointer>,
+    insert: String,
+    /// The number of the rows inserted in the current transaction.
+    pending: usize,
+}
+
+impl SqliteWriter {
+    pub(crate) fn new(path: &Path, table: &str, columns: Vec<Pointer>) -> io::Result<SqliteWriter> {
+        let conn = Connection::open(path).map_err(io::Error::other)?;
+        let names: Vec<String> = columns
+            .iter()
+            .map(|p| quote(p.as_str().strip_prefix('/').unwrap_or("value")))
+            .chain(std::iter::once(quote("json")))
+            .collect();
+        let create = format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            quote(table),
+            names.join(", ")
+        );
+        conn.execute_batch(&create).map_err(io::Error::other)?;
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(table),
+            names.join(", "),
+            vec!["?"; names.len()].join(", ")
+        );
+        Ok(SqliteWriter {
+            conn,
+            columns,
+            insert,
+            pending: 0,
+        })
+    }
+    pub(crate) fn write(&mut self, line: &str) -> io::Result<()> {
+        let v: Value = from_str(line).map_err(io::Error::other)?;
+        let row = self
+            .columns
+            .iter()
+            .map(|p| p.get(&v).map_or(SqlValue::Null, to_sql))
+            .chain(std::iter::once(SqlValue::Blob(line.as_bytes().to_vec())));
+        if self.pending == 0 {
+            self.conn.execute_batch("BEGIN").map_err(io::Error::other)?;
+        }
+        self.conn
+            .prepare_cached(&self.insert)
+            .and_then(|mut s| s.execute(params_from_iter(row)))
+            .map_err(io::Error::other)?;
+        self.pending += 1;
+        if self.pending >= BATCH_SIZE {
+            self.commit()?;
+        }
+        Ok(())
+    }
+    pub(crate) fn commit(&mut self) -> io::Result<()> {
+        if self.pending > 0 {
+            self.conn
+                .execute_batch("COMMIT")
+                .map_err(io::Error::other)?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+}
+
+fn to_sql(v: &Value) -> SqlValue {
+    match v {
+        Value::Null => SqlValue::Null,
+        Value::Bool(x) => SqlValue::Integer(i64::from(*x)),
+        Value::Number(x) => match x.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(x.as_f64().unwrap()),
+        },
+        Value::String(x) => SqlValue::Text(x.clone()),
+        x => SqlValue::Text(x.to_string()),
+    }
+}
+
+/// Quote the identifier.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
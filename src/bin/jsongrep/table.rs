@@ -0,0 +1,118 @@
+use jsongrep::pointer::Pointer;
+use serde_json::{from_str, Value};
+use std::io;
+use std::io::prelude::*;
+
+/// Write the values at the pointers of the lines as an aligned text table.
+///
+/// The widths of the columns are computed over the rows buffered up to `window`,
+/// they only grow for the following windows.
+pub(crate) struct Table {
+    columns: Vec<Pointer>,
+    /// Truncate the cells longer than this number of characters.
+    max_width: Option<usize>,
+    window: usize,
+    widths: Vec<usize>,
+    /// Rows not written yet, the header first until written.
+    rows: Vec<Vec<String>>,
+    header: bool,
+}
+
+impl Table {
+    pub(crate) fn new(columns: Vec<Pointer>, max_width: Option<usize>, window: usize) -> Table {
+        let header: Vec<String> = columns.iter().map(|x| x.to_string()).collect();
+        let mut t = Table {
+            widths: vec![0; columns.len()],
+            columns,
+            max_width,
+            window,
+            rows: Vec::new(),
+            header: true,
+        };
+        t.push(header);
+        t
+    }
+    fn push(&mut self, row: Vec<String>) {
+        let row: Vec<String> = row.into_iter().map(|x| self.truncate(escape(&x))).collect();
+        for (w, x) in self.widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(x.chars().count());
+        }
+        self.rows.push(row);
+    }
+    fn truncate(&self, cell: String) -> String {
+        match self.max_width {
+            Some(n) if cell.chars().count() > n => {
+                let mut x: String = cell.chars().take(n.saturating_sub(1)).collect();
+                x.push('…');
+                x
+            }
+            _ => cell,
+        }
+    }
+    /// Add the row of `line`, write the buffered rows if the window is full.
+    pub(crate) fn add(&mut self, line: &str, out: &mut impl Write) -> io::Result<()> {
+        let v: Value = from_str(line).unwrap_or(Value::Null);
+        let row = self
+            .columns
+            .iter()
+            .map(|p| match p.get(&v) {
+                None => String::new(),
+                Some(Value::String(x)) => x.clone(),
+                Some(x) => x.to_string(),
+            })
+            .collect();
+        self.push(row);
+        if self.rows.len() >= self.window + usize::from(self.header) {
+            self.flush(out)?;
+        }
+        Ok(())
+    }
+    /// Write the buffered rows.
+    pub(crate) fn flush(&mut self, out: &mut impl Write) -> io::Result<()> {
+        for row in self.rows.drain(..) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(self.widths.iter())
+                .map(|(x, w)| format!("{}{}", x, " ".repeat(w - x.chars().count())))
+                .collect();
+            writeln!(out, "{}", cells.join("  ").trim_end())?;
+        }
+        self.header = false;
+        Ok(())
+    }
+}
+
+/// Escape the control characters such as newlines and tabs not to break the rows.
+fn escape(cell: &str) -> String {
+    let mut x = String::with_capacity(cell.len());
+    for c in cell.chars() {
+        if c.is_control() {
+            x.extend(c.escape_default());
+        } else {
+            x.push(c);
+        }
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_control_characters() {
+        let mut t = Table::new(
+            vec![Pointer::parse("/a").unwrap(), Pointer::parse("/b").unwrap()],
+            None,
+            10,
+        );
+        let mut out = Vec::new();
+        t.add(r#"{"a":"x\ny\tz","b":1}"#, &mut out).unwrap();
+        t.add(r#"{"a":"\u001b[31mred","b":2}"#, &mut out).unwrap();
+        t.flush(&mut out).unwrap();
+        assert_eq!(
+            "/a             /b\nx\\ny\\tz        1\n\\u{1b}[31mred  2\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
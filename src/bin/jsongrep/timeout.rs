@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exit code on timeout, the same as timeout(1).
+const EXIT_TIMEOUT: i32 = 124;
+
+/// Abort the process if the run or a line takes too long.
+///
+/// The evaluation of a line cannot be interrupted,
+/// so the whole process exits with [`EXIT_TIMEOUT`] without flushing the output.
+pub(crate) struct Watchdog {
+    line_timeout: Option<Duration>,
+    /// Start times of the lines being evaluated, by the line numbers.
+    lines: Mutex<BTreeMap<usize, Instant>>,
+}
+
+impl Watchdog {
+    /// Start watching on another thread, `None` if no timeout is given.
+    pub(crate) fn start(
+        timeout: Option<Duration>,
+        line_timeout: Option<Duration>,
+    ) -> Option<Arc<Watchdog>> {
+        if timeout.is_none() && line_timeout.is_none() {
+            return None;
+        }
+        let w = Arc::new(Watchdog {
+            line_timeout,
+            lines: Mutex::new(BTreeMap::new()),
+        });
+        let tick = line_timeout
+            .map_or(Duration::from_millis(100), |x| x / 4)
+            .clamp(Duration::from_millis(1), Duration::from_millis(100));
+        let start = Instant::now();
+        let watched = Arc::clone(&w);
+        thread::spawn(move || loop {
+            thread::sleep(tick);
+            if let Some(t) = timeout.filter(|t| start.elapsed() >= *t) {
+                eprintln!("Timeout (run exceeded {})", humantime::format_duration(t));
+                process::exit(EXIT_TIMEOUT);
+            }
+            if let Some((n, t)) = watched.slowest() {
+                eprintln!(
+                    "line {}: Timeout (line exceeded {})",
+                    n,
+                    humantime::format_duration(t)
+                );
+                process::exit(EXIT_TIMEOUT);
+            }
+        });
+        Some(w)
+    }
+    /// Watch the evaluation of the `n`-th line until the guard is dropped.
+    pub(crate) fn line(&self, n: usize) -> LineGuard<'_> {
+        if self.line_timeout.is_some() {
+            self.lines.lock().unwrap().insert(n, Instant::now());
+        }
+        LineGuard { watchdog: self, n }
+    }
+    /// Return the line that exceeded the timeout and the timeout.
+    fn slowest(&self) -> Option<(usize, Duration)> {
+        let t = self.line_timeout?;
+        let lines = self.lines.lock().unwrap();
+        lines
+            .iter()
+            .find(|(_, start)| start.elapsed() >= t)
+            .map(|(n, _)| (*n, t))
+    }
+}
+
+/// Evaluation of a line watched by [`Watchdog`].
+pub(crate) struct LineGuard<'a> {
+    watchdog: &'a Watchdog,
+    n: usize,
+}
+
+impl Drop for LineGuard<'_> {
+    fn drop(&mut self) {
+        if self.watchdog.line_timeout.is_some() {
+            self.watchdog.lines.lock().unwrap().remove(&self.n);
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::args::{QueryArgs, SortArgs};
+use jsongrep::error::{Error, ErrorCode, Result};
+use structopt::StructOpt;
+
+/// Check the query and the sort without reading stdin.
+///
+/// Write `ok` for each valid one, exit with 1 and write the error if invalid.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct ValidateOpt {
+    #[structopt(flatten)]
+    query: QueryArgs,
+    #[structopt(flatten)]
+    sort: SortArgs,
+}
+
+impl ValidateOpt {
+    pub(crate) fn run(&self) -> Result<()> {
+        self.sort.validate()?;
+        let query = self.query.get_query();
+        let sort = self.sort.get_checked_sort();
+        if query.is_none() && sort.is_none() {
+            return Err(Error::new(ErrorCode::InvalidOption(
+                "query or sort is required".to_owned(),
+            )));
+        }
+        if let Some(x) = query {
+            x?;
+            println!("query: ok");
+        }
+        if let Some(x) = sort {
+            x?;
+            println!("sort: ok");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! C interface of the query engine, built with `--features capi` as a cdylib.
+//!
+//! The functions return one of the `JSONGREP_*` codes, see `include/jsongrep.h`.
+//! The codes are stable: new codes may be added but the existing ones never change.
+use crate::error::{Error, ErrorKind};
+use crate::query;
+use crate::raw_query as raw;
+use crate::select;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// Success, `jsongrep_select` does not return this but [`JSONGREP_MATCHED`] or [`JSONGREP_UNMATCHED`].
+pub const JSONGREP_OK: c_int = 0;
+/// The json meets the condition.
+pub const JSONGREP_MATCHED: c_int = 1;
+/// The json does not meet the condition.
+pub const JSONGREP_UNMATCHED: c_int = 0;
+/// A null pointer is given.
+pub const JSONGREP_ERR_NULL: c_int = -1;
+/// A given string is not valid UTF-8.
+pub const JSONGREP_ERR_UTF8: c_int = -2;
+/// A given string is not a valid json.
+pub const JSONGREP_ERR_JSON: c_int = -3;
+/// A given query is invalid.
+pub const JSONGREP_ERR_QUERY: c_int = -4;
+/// Failed to evaluate the json, e.g. type mismatch.
+pub const JSONGREP_ERR_EVAL: c_int = -5;
+
+/// Compiled query, opaque to C.
+pub struct JsongrepQuery(select::Query);
+
+fn error_code(err: &Error) -> c_int {
+    match err.kind() {
+        ErrorKind::Json => JSONGREP_ERR_JSON,
+        ErrorKind::InvalidRegex
+        | ErrorKind::NoChildren
+        | ErrorKind::MalformedPointer
+        | ErrorKind::UnknownMatcher
+        | ErrorKind::InvalidTime => JSONGREP_ERR_QUERY,
+        _ => JSONGREP_ERR_EVAL,
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str, c_int> {
+    if s.is_null() {
+        return Err(JSONGREP_ERR_NULL);
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| JSONGREP_ERR_UTF8)
+}
+
+/// Compile the json of the raw query and store it to `*out`.
+/// The query should be released by [`jsongrep_free`].
+///
+/// # Safety
+///
+/// `json` must be a null-terminated string and `out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn jsongrep_compile(
+    json: *const c_char,
+    out: *mut *mut JsongrepQuery,
+) -> c_int {
+    if out.is_null() {
+        return JSONGREP_ERR_NULL;
+    }
+    *out = ptr::null_mut();
+    let json = match to_str(json) {
+        Ok(x) => x,
+        Err(x) => return x,
+    };
+    match raw::Query::try_from(json).and_then(query::Query::try_from) {
+        Ok(q) => {
+            *out = Box::into_raw(Box::new(JsongrepQuery(select::Query::new(Box::new(q)))));
+            JSONGREP_OK
+        }
+        Err(x) if x.is_json() => JSONGREP_ERR_QUERY,
+        Err(x) => error_code(&x),
+    }
+}
+
+/// Report whether `line` meets the condition of `query`.
+///
+/// # Safety
+///
+/// `query` must be returned by [`jsongrep_compile`] and `line` must be a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn jsongrep_select(
+    query: *const JsongrepQuery,
+    line: *const c_char,
+) -> c_int {
+    if query.is_null() {
+        return JSONGREP_ERR_NULL;
+    }
+    let line = match to_str(line) {
+        Ok(x) => x,
+        Err(x) => return x,
+    };
+    match (*query).0.matches_raw(line) {
+        Ok(true) => JSONGREP_MATCHED,
+        Ok(false) => JSONGREP_UNMATCHED,
+        Err(x) => error_code(&x),
+    }
+}
+
+/// Release the query.
+///
+/// # Safety
+///
+/// `query` must be returned by [`jsongrep_compile`] or null, and must not be used after this.
+#[no_mangle]
+pub unsafe extern "C" fn jsongrep_free(query: *mut JsongrepQuery) {
+    if !query.is_null() {
+        drop(Box::from_raw(query));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn compile(json: &str) -> (c_int, *mut JsongrepQuery) {
+        let json = CString::new(json).unwrap();
+        let mut q = ptr::null_mut();
+        let code = unsafe { jsongrep_compile(json.as_ptr(), &mut q) };
+        (code, q)
+    }
+
+    fn select(q: *const JsongrepQuery, line: &str) -> c_int {
+        let line = CString::new(line).unwrap();
+        unsafe { jsongrep_select(q, line.as_ptr()) }
+    }
+
+    #[test]
+    fn test_compile_select_free() {
+        let (code, q) = compile(
+            r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}"#,
+        );
+        assert_eq!(JSONGREP_OK, code);
+        assert_eq!(JSONGREP_MATCHED, select(q, r#"{"i":2}"#));
+        assert_eq!(JSONGREP_UNMATCHED, select(q, r#"{"i":1}"#));
+        assert_eq!(JSONGREP_ERR_JSON, select(q, "two"));
+        assert_eq!(JSONGREP_ERR_EVAL, select(q, r#"{"i":"2"}"#));
+        assert_eq!(JSONGREP_ERR_NULL, unsafe {
+            jsongrep_select(q, ptr::null())
+        });
+        unsafe { jsongrep_free(q) };
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_compile_invalid() {
+        let (code, q) = compile("{}");
+        assert_eq!(JSONGREP_ERR_QUERY, code);
+        assert!(q.is_null());
+        let (code, _) = compile(
+            r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","mtype":"regex","value":{"type":"string","value":"["}}}}}"#,
+        );
+        assert_eq!(JSONGREP_ERR_QUERY, code);
+        assert_eq!(JSONGREP_ERR_NULL, unsafe {
+            jsongrep_compile(ptr::null(), &mut ptr::null_mut())
+        });
+    }
+}
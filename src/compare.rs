@@ -1,2 +1,3 @@
+mod key;
 pub mod sort;
 mod value;
@@ -0,0 +1,108 @@
+use crate::raw_sort::DatetimeFormat;
+use crate::timestamp;
+use serde_json::value::Value;
+
+/// Parse the value into the seconds since the unix epoch by the first matching format.
+pub(crate) fn datetime(v: &Value, formats: &[DatetimeFormat]) -> Option<f64> {
+    formats.iter().find_map(|f| match (f, v) {
+        (DatetimeFormat::Iso8601, Value::String(x)) => timestamp::parse(x),
+        (DatetimeFormat::Epoch, _) => epoch(v),
+        (DatetimeFormat::EpochMillis, _) => epoch(v).map(|x| x / 1000.0),
+        _ => None,
+    })
+}
+
+/// Parse the string into the version, ignoring the leading `v`.
+#[cfg(feature = "semver")]
+pub(crate) fn semver(v: &Value) -> Option<semver::Version> {
+    match v {
+        Value::String(x) => {
+            let x = x.trim();
+            semver::Version::parse(x.strip_prefix(['v', 'V']).unwrap_or(x)).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Parse the number or the numeric string.
+pub(crate) fn number(v: &Value) -> Option<f64> {
+    epoch(v)
+}
+
+fn epoch(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(x) => x.as_f64(),
+        Value::String(x) => x.trim().parse::<f64>().ok().filter(|x| x.is_finite()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ALL: &[DatetimeFormat] = &[DatetimeFormat::Iso8601, DatetimeFormat::Epoch];
+
+    macro_rules! test_datetime {
+        ($name:ident, $value:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($want, datetime(&$value, ALL));
+            }
+        };
+    }
+
+    test_datetime!(
+        datetime_epoch_origin,
+        json!("1970-01-01T00:00:00Z"),
+        Some(0.0)
+    );
+    test_datetime!(datetime_date, json!("2000-03-01"), Some(951868800.0));
+    test_datetime!(
+        datetime_offset,
+        json!("2000-03-01T09:00:00+09:00"),
+        Some(951868800.0)
+    );
+    test_datetime!(
+        datetime_negative_offset,
+        json!("2000-02-29 15:00-0900"),
+        Some(951868800.0)
+    );
+    test_datetime!(
+        datetime_fraction,
+        json!("1970-01-01T00:00:01.5Z"),
+        Some(1.5)
+    );
+    test_datetime!(datetime_epoch_number, json!(951868800), Some(951868800.0));
+    test_datetime!(datetime_epoch_string, json!("12.5"), Some(12.5));
+    test_datetime!(datetime_invalid_day, json!("2001-02-29"), None);
+    test_datetime!(datetime_invalid_offset, json!("2000-03-01T00:00+9"), None);
+    test_datetime!(datetime_bool, json!(true), None);
+    #[cfg(feature = "semver")]
+    #[test]
+    fn semver_order() {
+        let mut v: Vec<_> = ["1.10.0", "v1.9.0", "1.10.0-rc.1", "1.9.0+build"]
+            .iter()
+            .map(|x| semver(&json!(x)).unwrap())
+            .collect();
+        v.sort();
+        let got: Vec<String> = v.iter().map(|x| x.to_string()).collect();
+        assert_eq!(vec!["1.9.0", "1.9.0+build", "1.10.0-rc.1", "1.10.0"], got);
+        assert_eq!(None, semver(&json!("1.9")));
+    }
+    #[test]
+    fn number_string() {
+        assert_eq!(Some(10.0), number(&json!(" 10 ")));
+        assert_eq!(Some(-1.5), number(&json!(-1.5)));
+        assert_eq!(None, number(&json!("ten")));
+        assert_eq!(None, number(&json!("NaN")));
+    }
+    #[test]
+    fn datetime_epoch_millis() {
+        assert_eq!(
+            Some(1.5),
+            datetime(&json!(1500), &[DatetimeFormat::EpochMillis])
+        );
+    }
+}
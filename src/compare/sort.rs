@@ -1,21 +1,87 @@
-use crate::compare::value::PairValue;
-use crate::raw_sort::Order;
+use crate::compare::key;
+use crate::compare::value::{Interner, PairValue, TypeOrder};
+use crate::error::{Error, ErrorCode, Result};
+use crate::pointer::Pointer;
+use crate::raw_query::JsonType;
+use crate::raw_sort::{DatetimeFormat, Order, Unparsable};
 use serde_json::value::Value;
+use std::cmp::Ordering;
 
-/// JSON pointer and sort order.
-struct PairSetting(String, Order);
+/// Parser of the sort key from the value at the pointer.
+pub(crate) enum KeyParser {
+    /// Seconds since the unix epoch by the first matching format.
+    Datetime(Vec<DatetimeFormat>),
+    /// Semantic version.
+    #[cfg(feature = "semver")]
+    Semver,
+    /// Number or numeric string.
+    Number,
+}
+
+/// JSON pointer, sort order, key parser and what to do with the unparsable values.
+struct PairSetting(Pointer, Order, Option<(KeyParser, Unparsable)>);
+
+impl PairSetting {
+    /// Return the sort key of `v`, null if not found.
+    /// Return `Err` if not parsable and [`Unparsable::Error`], or null.
+    fn key(&self, v: &Value, interner: &mut Interner) -> Result<PairValue> {
+        let Some(x) = self.0.get(v) else {
+            return Ok(PairValue::Null); // invalid pointer as null
+        };
+        let (parser, on_unparsable) = match &self.2 {
+            None => return Ok(PairValue::new(x, interner)),
+            Some(_) if x.is_null() => return Ok(PairValue::Null),
+            Some(p) => p,
+        };
+        let key = match parser {
+            KeyParser::Datetime(f) => key::datetime(x, f).map(PairValue::Number),
+            #[cfg(feature = "semver")]
+            KeyParser::Semver => key::semver(x).map(|v| PairValue::Version(std::sync::Arc::new(v))),
+            KeyParser::Number => key::number(x).map(PairValue::Number),
+        };
+        match (key, on_unparsable) {
+            (Some(k), _) => Ok(k),
+            (None, Unparsable::Null) => Ok(PairValue::Null),
+            (None, Unparsable::Error) => Err(Error::new(ErrorCode::InvalidTarget {
+                pointer: self.0.to_string(),
+                value: x.to_string(),
+            })),
+        }
+    }
+}
 
 /// Sort indexes.
-pub(crate) struct PairsListSettings(Vec<PairSetting>);
+pub(crate) struct PairsListSettings {
+    pairs: Vec<PairSetting>,
+    type_order: TypeOrder,
+    strict_types: bool,
+}
 
 impl PairsListSettings {
     /// Return a new empty [`PairsListSettings`].
     pub(crate) fn new() -> PairsListSettings {
-        PairsListSettings(Vec::new())
+        PairsListSettings {
+            pairs: Vec::new(),
+            type_order: TypeOrder::default(),
+            strict_types: false,
+        }
+    }
+    /// Add a new sort index, the key is parsed by `parser` if any.
+    pub(crate) fn add(
+        &mut self,
+        pointer: Pointer,
+        order: Order,
+        parser: Option<(KeyParser, Unparsable)>,
+    ) {
+        self.pairs.push(PairSetting(pointer, order, parser));
+    }
+    /// Compare the values of the different types by `types` first, then the others in the default order.
+    pub(crate) fn type_order(&mut self, types: &[JsonType]) {
+        self.type_order = TypeOrder::new(types);
     }
-    /// Add a new sort index.
-    pub(crate) fn add(&mut self, pointer: String, order: Order) {
-        self.0.push(PairSetting(pointer, order));
+    /// Reject the values whose type differs from the first non-null one of the same index.
+    pub(crate) fn strict_types(&mut self) {
+        self.strict_types = true;
     }
     pub(crate) fn builder(self) -> PairsListBuilder {
         PairsListBuilder::from(self)
@@ -32,32 +98,79 @@ struct Pairs {
 pub(crate) struct PairsListBuilder {
     settings: Vec<PairSetting>,
     list: Vec<Pairs>,
+    interner: Interner,
+    type_order: TypeOrder,
+    /// The type of the first non-null value of each index, `None` if not strict.
+    kinds: Option<Vec<Option<usize>>>,
 }
 
 impl From<PairsListSettings> for PairsListBuilder {
     fn from(v: PairsListSettings) -> PairsListBuilder {
+        let kinds = v.strict_types.then(|| vec![None; v.pairs.len()]);
         PairsListBuilder {
-            settings: v.0,
+            settings: v.pairs,
             list: Vec::new(),
+            interner: Interner::default(),
+            type_order: v.type_order,
+            kinds,
         }
     }
 }
 
 impl PairsListBuilder {
     /// Generate sort indexed value.
-    pub(crate) fn add(&mut self, value: Value) {
+    /// Return `Err` and add nothing if the types are strict and a key has another type.
+    pub(crate) fn add(&mut self, value: &Value) -> Result<()> {
+        let interner = &mut self.interner;
         let pairs: Vec<PairValue> = self
             .settings
             .iter()
-            .map(|s| {
-                let x = value
-                    .pointer(&s.0)
-                    .map_or_else(|| Value::Null, |x| x.clone()); // invalid pointer as null
-                PairValue::from(x)
-            })
-            .collect::<Vec<_>>();
+            .map(|s| s.key(value, interner))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(kinds) = &mut self.kinds {
+            for (i, p) in pairs.iter().enumerate() {
+                match (kinds[i], p.kind()) {
+                    (_, 0) | (None, _) => (),
+                    (Some(x), y) if x == y => (),
+                    (Some(x), _) => {
+                        let PairSetting(pointer, ..) = &self.settings[i];
+                        return Err(Error::new(ErrorCode::TypeMismatch {
+                            got: format!("{}", pointer.get(value).unwrap()),
+                            want: PairValue::kind_name(x).to_owned(),
+                            by: format!("sort {}", pointer),
+                        }));
+                    }
+                }
+            }
+            for (i, p) in pairs.iter().enumerate() {
+                if kinds[i].is_none() && p.kind() != 0 {
+                    kinds[i] = Some(p.kind());
+                }
+            }
+        }
         let index = self.list.len();
         self.list.push(Pairs { index, pairs });
+        Ok(())
+    }
+    /// Compare the values by the sort indexes, the same order as [`PairsList::sort`].
+    pub(crate) fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        // the index sorted last takes precedence
+        let interner = &mut Interner::default();
+        for s in self.settings.iter().rev() {
+            let PairSetting(_, order, _) = s;
+            // the values rejected by add are compared as null
+            let mut key = |v| s.key(v, interner).unwrap_or(PairValue::Null);
+            let o = key(a).compare(&key(b), &self.type_order);
+            let o = if matches!(order, Order::Asc) {
+                o
+            } else {
+                o.reverse()
+            };
+            if o != Ordering::Equal {
+                return o;
+            }
+        }
+        Ordering::Equal
     }
     pub(crate) fn build(self) -> PairsList {
         PairsList::from(self)
@@ -69,6 +182,7 @@ impl From<PairsListBuilder> for PairsList {
         PairsList {
             list: v.list,
             settings: v.settings,
+            type_order: v.type_order,
         }
     }
 }
@@ -77,17 +191,19 @@ impl From<PairsListBuilder> for PairsList {
 pub(crate) struct PairsList {
     list: Vec<Pairs>,
     settings: Vec<PairSetting>,
+    type_order: TypeOrder,
 }
 
 impl PairsList {
     fn sort_by(&mut self, index: usize) {
-        let PairSetting(_, order) = &self.settings[index];
+        let PairSetting(_, order, _) = &self.settings[index];
+        let t = &self.type_order;
         if matches!(order, Order::Asc) {
             self.list
-                .sort_by(|a: &Pairs, b: &Pairs| a.pairs[index].cmp(&b.pairs[index]));
+                .sort_by(|a: &Pairs, b: &Pairs| a.pairs[index].compare(&b.pairs[index], t));
         } else {
             self.list
-                .sort_by(|a: &Pairs, b: &Pairs| b.pairs[index].cmp(&a.pairs[index]));
+                .sort_by(|a: &Pairs, b: &Pairs| b.pairs[index].compare(&a.pairs[index], t));
         }
     }
     /// Sort the values sequentially.
@@ -96,6 +212,27 @@ impl PairsList {
             self.sort_by(i);
         }
     }
+    /// Same as [`PairsList::sort`] but sort by each index in parallel on `threads` threads.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn par_sort(&mut self, threads: usize) {
+        use rayon::prelude::*;
+
+        let pool = match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+            Ok(x) => x,
+            Err(_) => return self.sort(), // fallback to the sequential one
+        };
+        let list = &mut self.list;
+        let t = &self.type_order;
+        for (index, PairSetting(_, order, _)) in self.settings.iter().enumerate() {
+            pool.install(|| {
+                if matches!(order, Order::Asc) {
+                    list.par_sort_by(|a, b| a.pairs[index].compare(&b.pairs[index], t));
+                } else {
+                    list.par_sort_by(|a, b| b.pairs[index].compare(&a.pairs[index], t));
+                }
+            });
+        }
+    }
     /// Read the indexes of the values.
     pub(crate) fn indexes(&self) -> Vec<usize> {
         self.list.iter().map(|x| x.index).collect::<Vec<_>>()
@@ -115,14 +252,15 @@ mod tests {
         ($name:ident, $values:expr, $pointers:expr, $want:expr) => {
             #[test]
             fn $name() {
+                let pointers: Vec<&str> = $pointers;
+                let values: Vec<&str> = $values;
                 let mut s = PairsListSettings::new();
-                for p in $pointers {
-                    let p: &str = p;
-                    s.add(p.to_string(), Order::Asc);
+                for p in pointers {
+                    s.add(Pointer::lenient(p), Order::Asc, None);
                 }
                 let mut b = s.builder();
-                for v in $values {
-                    b.add(value(v));
+                for v in values {
+                    b.add(&value(v)).unwrap();
                 }
                 let mut a = b.build();
                 a.sort();
@@ -178,6 +316,108 @@ mod tests {
         vec!["/i", "/j"],
         vec![3, 2, 0, 1]
     );
+    #[test]
+    fn compare_same_as_sort() {
+        let mut s = PairsListSettings::new();
+        s.add(Pointer::lenient("/j"), Order::Desc, None);
+        s.add(Pointer::lenient("/i"), Order::Asc, None);
+        let mut b = s.builder();
+        let values: Vec<Value> = (0..100)
+            .map(|i| value(&format!(r#"{{"i":{},"j":{}}}"#, i % 7, i % 13)))
+            .collect();
+        for v in &values {
+            b.add(v).unwrap();
+        }
+        let mut want: Vec<usize> = (0..values.len()).collect();
+        want.sort_by(|x, y| b.compare(&values[*x], &values[*y]));
+        let mut a = b.build();
+        a.sort();
+        assert_eq!(want, a.indexes());
+    }
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn par_sort() {
+        let mut s = PairsListSettings::new();
+        s.add(Pointer::lenient("/j"), Order::Desc, None);
+        s.add(Pointer::lenient("/i"), Order::Asc, None);
+        let mut b = s.builder();
+        for i in 0..1000 {
+            b.add(&value(&format!(r#"{{"i":{},"j":{}}}"#, i % 7, i % 13)))
+                .unwrap();
+        }
+        let mut a = b.build();
+        a.sort();
+        let want = a.indexes();
+        a.par_sort(4);
+        assert_eq!(want, a.indexes());
+    }
+    #[test]
+    fn strict_types() {
+        let mut s = PairsListSettings::new();
+        s.add(Pointer::lenient("/i"), Order::Asc, None);
+        s.strict_types();
+        let mut b = s.builder();
+        b.add(&value(r#"{"i":null}"#)).unwrap();
+        b.add(&value(r#"{"i":1}"#)).unwrap();
+        b.add(&value(r#"{}"#)).unwrap();
+        let e = b.add(&value(r#"{"i":"1"}"#)).unwrap_err();
+        assert_eq!(crate::error::ErrorKind::TypeMismatch, e.kind());
+        b.add(&value(r#"{"i":0}"#)).unwrap();
+        let mut a = b.build();
+        a.sort();
+        assert_eq!(vec![0, 2, 3, 1], a.indexes());
+    }
+
+    #[test]
+    fn sort_by_datetime() {
+        let mut s = PairsListSettings::new();
+        s.add(
+            Pointer::lenient("/t"),
+            Order::Asc,
+            Some((
+                KeyParser::Datetime(DatetimeFormat::DEFAULT.to_vec()),
+                Unparsable::Null,
+            )),
+        );
+        let mut b = s.builder();
+        let values = vec![
+            r#"{"t":"2000-01-01T09:00:00+09:00"}"#,
+            r#"{"t":"2000-01-01T01:00:00+00:00"}"#,
+            r#"{"t":"unknown"}"#,
+            r#"{"t":946684000}"#,
+        ];
+        for v in &values {
+            b.add(&value(v)).unwrap();
+        }
+        let want = vec![2, 3, 0, 1];
+        let mut got: Vec<usize> = (0..values.len()).collect();
+        got.sort_by(|x, y| b.compare(&value(values[*x]), &value(values[*y])));
+        assert_eq!(want, got);
+        let mut a = b.build();
+        a.sort();
+        assert_eq!(want, a.indexes());
+    }
+
+    #[test]
+    fn sort_by_number() {
+        let mut s = PairsListSettings::new();
+        s.add(
+            Pointer::lenient("/n"),
+            Order::Asc,
+            Some((KeyParser::Number, Unparsable::Error)),
+        );
+        let mut b = s.builder();
+        b.add(&value(r#"{"n":"10"}"#)).unwrap();
+        b.add(&value(r#"{"n":9}"#)).unwrap();
+        b.add(&value(r#"{"n":null}"#)).unwrap();
+        let e = b.add(&value(r#"{"n":"ten"}"#)).unwrap_err();
+        assert_eq!(crate::error::ErrorKind::InvalidTarget, e.kind());
+        b.add(&value(r#"{"n":"9.5"}"#)).unwrap();
+        let mut a = b.build();
+        a.sort();
+        assert_eq!(vec![2, 1, 3, 0], a.indexes());
+    }
+
     test_sort!(
         sort_by_ji,
         vec![
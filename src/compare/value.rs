@@ -1,26 +1,131 @@
+use crate::raw_query::JsonType;
 use serde_json::value::Value;
 use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::Arc;
 
-/// Wrap [`Value`] for comparing.
-pub(crate) struct PairValue(Value);
+/// Sort key of [`Value`].
+///
+/// Keep only what is needed for comparing, ignoring inner values of array and object.
+pub(crate) enum PairValue {
+    Null,
+    Array,
+    Object,
+    Bool(bool),
+    Number(f64),
+    String(Arc<str>),
+    /// Parsed from a string, sorted as a string among the other types.
+    #[cfg(feature = "semver")]
+    Version(Arc<semver::Version>),
+}
+
+impl From<&Value> for PairValue {
+    fn from(v: &Value) -> PairValue {
+        PairValue::new(v, &mut Interner::default())
+    }
+}
+
+impl PairValue {
+    /// Return the index of the type in the default order.
+    pub(crate) fn kind(&self) -> usize {
+        match self {
+            PairValue::Null => 0,
+            PairValue::Array => 1,
+            PairValue::Object => 2,
+            PairValue::Bool(_) => 3,
+            PairValue::Number(_) => 4,
+            PairValue::String(_) => 5,
+            #[cfg(feature = "semver")]
+            PairValue::Version(_) => 5,
+        }
+    }
+    /// Return the name of the type of [`PairValue::kind`].
+    pub(crate) fn kind_name(kind: usize) -> &'static str {
+        ["null", "array", "object", "bool", "number", "string"][kind]
+    }
+    /// Compare the keys, the different types by `order`.
+    pub(crate) fn compare(&self, other: &Self, order: &TypeOrder) -> Ordering {
+        let (x, y) = (self.kind(), other.kind());
+        if x == y {
+            self.cmp(other)
+        } else {
+            order.0[x].cmp(&order.0[y])
+        }
+    }
+    /// Return the key of `v`, sharing the same strings by `interner`.
+    pub(crate) fn new(v: &Value, interner: &mut Interner) -> PairValue {
+        match v {
+            Value::Null => PairValue::Null,
+            Value::Array(_) => PairValue::Array,
+            Value::Object(_) => PairValue::Object,
+            Value::Bool(x) => PairValue::Bool(*x),
+            Value::Number(x) => PairValue::Number(x.as_f64().unwrap()),
+            Value::String(x) => PairValue::String(interner.intern(x)),
+        }
+    }
+}
 
-impl From<Value> for PairValue {
-    fn from(v: Value) -> PairValue {
-        PairValue(v)
+/// Precedence of the types of the sort keys, indexed by [`PairValue::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TypeOrder([usize; 6]);
+
+impl Default for TypeOrder {
+    fn default() -> Self {
+        TypeOrder([0, 1, 2, 3, 4, 5])
+    }
+}
+
+impl TypeOrder {
+    /// Return the order of `types` first, then the others in the default order.
+    pub(crate) fn new(types: &[JsonType]) -> TypeOrder {
+        let kind = |t: &JsonType| match t {
+            JsonType::Null => 0,
+            JsonType::Array => 1,
+            JsonType::Object => 2,
+            JsonType::Bool => 3,
+            JsonType::Number => 4,
+            JsonType::String => 5,
+        };
+        let mut ranks = [usize::MAX; 6];
+        let mut n = 0;
+        for k in types.iter().map(kind).chain(0..6) {
+            if ranks[k] == usize::MAX {
+                ranks[k] = n;
+                n += 1;
+            }
+        }
+        TypeOrder(ranks)
+    }
+}
+
+/// Pool of the strings of the sort keys.
+#[derive(Default)]
+pub(crate) struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    fn intern(&mut self, v: &str) -> Arc<str> {
+        match self.0.get(v) {
+            Some(x) => Arc::clone(x),
+            None => {
+                let x: Arc<str> = Arc::from(v);
+                self.0.insert(Arc::clone(&x));
+                x
+            }
+        }
     }
 }
 
 impl PartialEq for PairValue {
     fn eq(&self, other: &Self) -> bool {
-        match (&self.0, &other.0) {
-            (Value::Null, Value::Null) => true,
-            (Value::Array(_), Value::Array(_)) => true,
-            (Value::Object(_), Value::Object(_)) => true,
-            (Value::Bool(x), Value::Bool(y)) => x == y,
-            (Value::Number(x), Value::Number(y)) => {
-                (x.as_f64().unwrap() - y.as_f64().unwrap()).abs() <= f64::EPSILON
-            }
-            (Value::String(x), Value::String(y)) => x.cmp(y) == Ordering::Equal,
+        match (self, other) {
+            (PairValue::Null, PairValue::Null) => true,
+            (PairValue::Array, PairValue::Array) => true,
+            (PairValue::Object, PairValue::Object) => true,
+            (PairValue::Bool(x), PairValue::Bool(y)) => x == y,
+            (PairValue::Number(x), PairValue::Number(y)) => (x - y).abs() <= f64::EPSILON,
+            (PairValue::String(x), PairValue::String(y)) => x.cmp(y) == Ordering::Equal,
+            #[cfg(feature = "semver")]
+            (PairValue::Version(x), PairValue::Version(y)) => x == y,
             (_, _) => false,
         }
     }
@@ -28,45 +133,50 @@ impl PartialEq for PairValue {
 
 impl PartialOrd for PairValue {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Eq for PairValue {}
+
+impl Ord for PairValue {
+    fn cmp(&self, other: &Self) -> Ordering {
         if self == other {
-            return Some(Ordering::Equal);
+            return Ordering::Equal;
         }
-        let r = match (&self.0, &other.0) {
-            (Value::Null, _) => Ordering::Less,
-            (Value::Array(_), Value::Null) => Ordering::Greater,
-            (Value::Array(_), _) => Ordering::Less,
-            (Value::Object(_), Value::Null | Value::Array(_)) => Ordering::Greater,
-            (Value::Object(_), _) => Ordering::Less,
-            (Value::Bool(_), Value::Null | Value::Array(_) | Value::Object(_)) => Ordering::Greater,
-            (Value::Bool(false), Value::Bool(true)) => Ordering::Less,
-            (Value::Bool(true), Value::Bool(false)) => Ordering::Greater,
-            (Value::Bool(_), _) => Ordering::Less,
+        match (self, other) {
+            (PairValue::Null, _) => Ordering::Less,
+            (PairValue::Array, PairValue::Null) => Ordering::Greater,
+            (PairValue::Array, _) => Ordering::Less,
+            (PairValue::Object, PairValue::Null | PairValue::Array) => Ordering::Greater,
+            (PairValue::Object, _) => Ordering::Less,
+            (PairValue::Bool(_), PairValue::Null | PairValue::Array | PairValue::Object) => {
+                Ordering::Greater
+            }
+            (PairValue::Bool(false), PairValue::Bool(true)) => Ordering::Less,
+            (PairValue::Bool(true), PairValue::Bool(false)) => Ordering::Greater,
+            (PairValue::Bool(_), _) => Ordering::Less,
             (
-                Value::Number(_),
-                Value::Null | Value::Array(_) | Value::Object(_) | Value::Bool(_),
+                PairValue::Number(_),
+                PairValue::Null | PairValue::Array | PairValue::Object | PairValue::Bool(_),
             ) => Ordering::Greater,
-            (Value::Number(x), Value::Number(y)) => {
-                let a = x.as_f64().unwrap();
-                let b = y.as_f64().unwrap();
+            (PairValue::Number(a), PairValue::Number(b)) => {
                 if a < b {
                     Ordering::Less
                 } else {
                     Ordering::Greater
                 }
             }
-            (Value::Number(_), _) => Ordering::Less,
-            (Value::String(x), Value::String(y)) => x.cmp(y),
-            (Value::String(_), _) => Ordering::Greater,
-        };
-        Some(r)
-    }
-}
-
-impl Eq for PairValue {}
-
-impl Ord for PairValue {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+            (PairValue::Number(_), _) => Ordering::Less,
+            (PairValue::String(x), PairValue::String(y)) => x.cmp(y),
+            #[cfg(feature = "semver")]
+            (PairValue::String(_), PairValue::Version(_)) => Ordering::Less,
+            (PairValue::String(_), _) => Ordering::Greater,
+            #[cfg(feature = "semver")]
+            (PairValue::Version(x), PairValue::Version(y)) => x.cmp(y),
+            #[cfg(feature = "semver")]
+            (PairValue::Version(_), _) => Ordering::Greater,
+        }
     }
 }
 
@@ -98,13 +208,11 @@ mod tests {
     struct P(usize, PairValue);
 
     fn to_indexed_values(values: Vec<Value>) -> Vec<P> {
-        let mut v = Vec::new();
-        let mut i: usize = 0;
-        for x in values {
-            v.push(P(i, PairValue(x)));
-            i += 1;
-        }
-        v
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| P(i, PairValue::from(&x)))
+            .collect()
     }
 
     macro_rules! test_sort {
@@ -137,6 +245,31 @@ mod tests {
         vec![int_value(3), float_value(1.2), int_value(2)],
         vec![1, 2, 0]
     );
+    #[test]
+    fn sort_type_order() {
+        let order = TypeOrder::new(&[JsonType::String, JsonType::Number]);
+        let mut v = to_indexed_values(vec![
+            int_value(2),
+            NULL,
+            string_value("moon"),
+            int_value(1),
+            TRUE,
+        ]);
+        v.sort_by(|a, b| a.1.compare(&b.1, &order));
+        let got: Vec<usize> = v.iter().map(|x| x.0).collect();
+        assert_eq!(vec![2, 3, 0, 1, 4], got);
+    }
+    #[test]
+    fn intern_strings() {
+        let mut interner = Interner::default();
+        let a = PairValue::new(&string_value("moon"), &mut interner);
+        let b = PairValue::new(&string_value("moon"), &mut interner);
+        match (a, b) {
+            (PairValue::String(x), PairValue::String(y)) => assert!(Arc::ptr_eq(&x, &y)),
+            _ => panic!("not strings"),
+        }
+    }
+
     test_sort!(
         sort_strings,
         vec![
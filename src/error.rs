@@ -1,3 +1,4 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde_json;
 use std::fmt;
 use std::io;
@@ -30,16 +31,109 @@ impl Error {
             }),
         }
     }
+    /// Return the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.err.code.kind()
+    }
+
     pub fn is_filtered(&self) -> bool {
-        matches!(self.err.code, ErrorCode::FilteredByQuery)
+        self.kind() == ErrorKind::FilteredByQuery
+    }
+
+    pub fn is_type_mismatch(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::TypeMismatch | ErrorKind::MatcherTypeMismatch
+        )
     }
 
     pub fn is_io(&self) -> bool {
-        matches!(self.err.code, ErrorCode::Io(_))
+        self.kind() == ErrorKind::Io
+    }
+
+    /// Return a string identifying the cause of the error,
+    /// ignoring the values of the line that caused it.
+    pub fn signature(&self) -> String {
+        let code = &self.err.code;
+        match code {
+            ErrorCode::Json(_) | ErrorCode::InvalidJson { .. } => code.name().to_owned(),
+            ErrorCode::TypeMismatch { want, by, .. } => {
+                format!("{} (want {:?}, by {:?})", code.name(), want, by)
+            }
+            ErrorCode::MatcherTypeMismatch {
+                matcher_type,
+                matcher_value,
+                by,
+                ..
+            } => format!(
+                "{} (matcher_type {:?}, matcher_value {:?}, by {:?})",
+                code.name(),
+                matcher_type,
+                matcher_value,
+                by
+            ),
+            ErrorCode::InvalidTarget { pointer, .. }
+            | ErrorCode::InvalidPointer { pointer, .. } => {
+                format!("{} (pointer: {:?})", code.name(), pointer)
+            }
+            _ => format!("{}", code),
+        }
     }
 
     pub fn is_json(&self) -> bool {
-        matches!(self.err.code, ErrorCode::Json(_))
+        self.kind() == ErrorKind::Json
+    }
+}
+
+/// Serialize as a json object with the error code name, the fields of the code and the message.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        let code = &self.err.code;
+        let mut m = serializer.serialize_map(None)?;
+        m.serialize_entry("code", code.name())?;
+        match code {
+            ErrorCode::InvalidJson {
+                offset, excerpt, ..
+            } => {
+                m.serialize_entry("offset", offset)?;
+                m.serialize_entry("excerpt", excerpt)?;
+            }
+            ErrorCode::InvalidRegex(x) => m.serialize_entry("pattern", x)?,
+            ErrorCode::TypeMismatch { got, want, by } => {
+                m.serialize_entry("got", got)?;
+                m.serialize_entry("want", want)?;
+                m.serialize_entry("by", by)?;
+            }
+            ErrorCode::NoChildren { by } => m.serialize_entry("by", by)?,
+            ErrorCode::MatcherTypeMismatch {
+                matcher_type,
+                matcher_value,
+                target,
+                by,
+            } => {
+                m.serialize_entry("matcher_type", matcher_type)?;
+                m.serialize_entry("matcher_value", matcher_value)?;
+                m.serialize_entry("target", target)?;
+                m.serialize_entry("by", by)?;
+            }
+            ErrorCode::InvalidTarget { pointer, value }
+            | ErrorCode::InvalidPointer { pointer, value } => {
+                m.serialize_entry("pointer", pointer)?;
+                m.serialize_entry("value", value)?;
+            }
+            ErrorCode::InvalidOption(x) => m.serialize_entry("reason", x)?,
+            ErrorCode::TooManyErrors { max } => m.serialize_entry("max", max)?,
+            ErrorCode::MalformedPointer(x) => m.serialize_entry("pointer", x)?,
+            ErrorCode::UnknownMatcher(x) => m.serialize_entry("name", x)?,
+            ErrorCode::InvalidTime(x) => m.serialize_entry("value", x)?,
+            ErrorCode::InvalidExpression { expr, reason } => {
+                m.serialize_entry("expr", expr)?;
+                m.serialize_entry("reason", reason)?;
+            }
+            _ => {}
+        }
+        m.serialize_entry("message", &format!("{}", code))?;
+        m.end()
     }
 }
 
@@ -60,6 +154,12 @@ pub enum ErrorCode {
     Json(#[from] serde_json::error::Error),
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("Invalid json (offset: {offset}, near: {excerpt:?}): {source}")]
+    InvalidJson {
+        offset: usize,
+        excerpt: String,
+        source: serde_json::error::Error,
+    },
     #[error("Invalid regex ({0})")]
     InvalidRegex(String),
     #[error("Unreachable")]
@@ -87,4 +187,143 @@ pub enum ErrorCode {
     FilteredByQuery,
     #[error("InvalidOption ({0})")]
     InvalidOption(String),
+    #[error("Too many errors (max: {max})")]
+    TooManyErrors { max: usize },
+    #[error("Malformed pointer ({0:?})")]
+    MalformedPointer(String),
+    #[error("Unknown matcher ({0:?})")]
+    UnknownMatcher(String),
+    #[error("Invalid expression ({reason}: {expr:?})")]
+    InvalidExpression { expr: String, reason: String },
+    #[error("Invalid time ({0:?})")]
+    InvalidTime(String),
+}
+
+/// Category of [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Invalid json syntax.
+    Json,
+    /// I/O error.
+    Io,
+    /// Invalid regular expression in the query.
+    InvalidRegex,
+    /// Bug.
+    Unreachable,
+    /// The type of the value does not match the condition.
+    TypeMismatch,
+    /// The condition requires children.
+    NoChildren,
+    /// The type of the value does not match the matcher.
+    MatcherTypeMismatch,
+    /// The value pointed is not a valid target.
+    InvalidTarget,
+    /// The pointer does not point any value.
+    InvalidPointer,
+    /// The json does not satisfy the query.
+    FilteredByQuery,
+    /// Invalid command line option.
+    InvalidOption,
+    /// Too many error lines.
+    TooManyErrors,
+    /// The pointer is not a valid JSON pointer.
+    MalformedPointer,
+    /// The custom matcher is not registered.
+    UnknownMatcher,
+    /// The query expression is not valid.
+    InvalidExpression,
+    /// The duration or the timestamp in the query is not valid.
+    InvalidTime,
+}
+
+impl ErrorCode {
+    /// Return the category of the code.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ErrorCode::Json(_) | ErrorCode::InvalidJson { .. } => ErrorKind::Json,
+            ErrorCode::Io(_) => ErrorKind::Io,
+            ErrorCode::InvalidRegex(_) => ErrorKind::InvalidRegex,
+            ErrorCode::Unreachable => ErrorKind::Unreachable,
+            ErrorCode::TypeMismatch { .. } => ErrorKind::TypeMismatch,
+            ErrorCode::NoChildren { .. } => ErrorKind::NoChildren,
+            ErrorCode::MatcherTypeMismatch { .. } => ErrorKind::MatcherTypeMismatch,
+            ErrorCode::InvalidTarget { .. } => ErrorKind::InvalidTarget,
+            ErrorCode::InvalidPointer { .. } => ErrorKind::InvalidPointer,
+            ErrorCode::FilteredByQuery => ErrorKind::FilteredByQuery,
+            ErrorCode::InvalidOption(_) => ErrorKind::InvalidOption,
+            ErrorCode::TooManyErrors { .. } => ErrorKind::TooManyErrors,
+            ErrorCode::MalformedPointer(_) => ErrorKind::MalformedPointer,
+            ErrorCode::UnknownMatcher(_) => ErrorKind::UnknownMatcher,
+            ErrorCode::InvalidExpression { .. } => ErrorKind::InvalidExpression,
+            ErrorCode::InvalidTime(_) => ErrorKind::InvalidTime,
+        }
+    }
+    /// Return the name of the variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCode::Json(_) => "Json",
+            ErrorCode::Io(_) => "Io",
+            ErrorCode::InvalidJson { .. } => "InvalidJson",
+            ErrorCode::InvalidRegex(_) => "InvalidRegex",
+            ErrorCode::Unreachable => "Unreachable",
+            ErrorCode::TypeMismatch { .. } => "TypeMismatch",
+            ErrorCode::NoChildren { .. } => "NoChildren",
+            ErrorCode::MatcherTypeMismatch { .. } => "MatcherTypeMismatch",
+            ErrorCode::InvalidTarget { .. } => "InvalidTarget",
+            ErrorCode::InvalidPointer { .. } => "InvalidPointer",
+            ErrorCode::FilteredByQuery => "FilteredByQuery",
+            ErrorCode::InvalidOption(_) => "InvalidOption",
+            ErrorCode::TooManyErrors { .. } => "TooManyErrors",
+            ErrorCode::MalformedPointer(_) => "MalformedPointer",
+            ErrorCode::UnknownMatcher(_) => "UnknownMatcher",
+            ErrorCode::InvalidExpression { .. } => "InvalidExpression",
+            ErrorCode::InvalidTime(_) => "InvalidTime",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, to_value};
+
+    #[test]
+    fn serialize_invalid_pointer() {
+        let e = Error::new(ErrorCode::InvalidPointer {
+            pointer: "/x".to_owned(),
+            value: "{}".to_owned(),
+        });
+        let want = json!({
+            "code": "InvalidPointer",
+            "pointer": "/x",
+            "value": "{}",
+            "message": r#"Invalid pointer (pointer: "/x", value: "{}")"#,
+        });
+        assert_eq!(want, to_value(&e).unwrap());
+    }
+    #[test]
+    fn kind_json() {
+        let e = serde_json::from_str::<serde_json::Value>("x").unwrap_err();
+        assert_eq!(ErrorKind::Json, Error::new(ErrorCode::Json(e)).kind());
+    }
+    #[test]
+    fn signature_ignores_value() {
+        let e = |v: &str| {
+            Error::new(ErrorCode::InvalidPointer {
+                pointer: "/x".to_owned(),
+                value: v.to_owned(),
+            })
+        };
+        assert_eq!(e("{}").signature(), e(r#"{"y":1}"#).signature());
+    }
+    #[test]
+    fn serialize_filtered() {
+        let e = Error::new(ErrorCode::FilteredByQuery);
+        let want = json!({
+            "code": "FilteredByQuery",
+            "message": "Filtered by query",
+        });
+        assert_eq!(want, to_value(&e).unwrap());
+    }
 }
@@ -1,5 +1,5 @@
 mod cond;
-mod matcher;
+pub(crate) mod document;
 mod query;
 mod query_condition;
 mod query_pair;
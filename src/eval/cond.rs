@@ -1,8 +1,10 @@
 use crate::error::{Error, ErrorCode, Result};
-use crate::eval::matcher::Matcher;
+use crate::matcher::Builtin;
 use crate::query::{Condition, EvaluableCondition, MatchType, Value};
+use crate::raw_query::JsonType;
 use crate::util;
 use std::cmp;
+use std::vec;
 
 impl EvaluableCondition for Condition {
     fn eval(&self, value: &Value) -> Result<bool> {
@@ -14,23 +16,268 @@ impl EvaluableCondition for Condition {
             Condition::And(_) => self.and(value),
             Condition::Or(_) => self.or(value),
             Condition::Match(_, _) => self.test(value),
+            #[cfg(feature = "regex")]
+            Condition::AnyRegex(_) => self.test_any(value),
+            Condition::Exists => Ok(true),
+            Condition::IsType(t) => Ok(*t == Self::json_type(value)),
+            Condition::Len(_) => self.len(value),
+            Condition::NKeys(_) => self.nkeys(value),
+            Condition::EmailDomain(_) => self.email_domain(value),
+            Condition::Empty => self.empty(value),
+            Condition::NotEmpty => self.empty(value).map(|x| !x),
+            #[cfg(feature = "datetime")]
+            Condition::Within(_) => self.within(value),
+            #[cfg(feature = "url")]
+            Condition::Url(_) => self.url(value),
         }
     }
+    fn eval_lenient(&self, value: &Value) -> Result<bool> {
+        self.eval_unknown(value).map(|x| x.unwrap_or(false))
+    }
 }
 
 impl Condition {
+    /// Evaluate the condition, `None` if the types mismatch.
+    ///
+    /// The mismatch is unknown as the null of SQL:
+    /// `not` keeps it, `and` is false and `or` is true if any other child decides it.
+    pub(crate) fn eval_unknown(&self, value: &Value) -> Result<Option<bool>> {
+        match self {
+            Condition::Not(l) => l.eval_unknown(value).map(|x| x.map(|b| !b)),
+            Condition::And(l) if !l.is_empty() => {
+                let mut r = Some(true);
+                for v in l {
+                    match v.eval_unknown(value)? {
+                        Some(false) => return Ok(Some(false)),
+                        None => r = None,
+                        _ => continue,
+                    }
+                }
+                Ok(r)
+            }
+            Condition::Or(l) if !l.is_empty() => {
+                let mut r = Some(false);
+                for v in l {
+                    match v.eval_unknown(value)? {
+                        Some(true) => return Ok(Some(true)),
+                        None => r = None,
+                        _ => continue,
+                    }
+                }
+                Ok(r)
+            }
+            _ => match self.eval(value) {
+                Err(x) if x.is_type_mismatch() => Ok(None),
+                x => x.map(Some),
+            },
+        }
+    }
     fn type_name<T>(v: T) -> String {
         util::type_name(v).to_string()
     }
+    fn json_type(v: &Value) -> JsonType {
+        match v {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Bool,
+            Value::Int(_) | Value::Float(_) => JsonType::Number,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+    /// Report whether the condition compares a given value against a number.
+    pub(crate) fn compares_number(&self) -> bool {
+        match self {
+            Condition::Equal(x) | Condition::GreaterThan(x) | Condition::LessThan(x) => {
+                matches!(x, Value::Int(_) | Value::Float(_))
+            }
+            Condition::Not(x) => x.compares_number(),
+            Condition::And(x) | Condition::Or(x) => x.iter().any(|c| c.compares_number()),
+            _ => false,
+        }
+    }
+    /// Evaluate the condition against the missing value, `None` if it requires a value.
+    pub(crate) fn eval_missing(&self) -> Option<bool> {
+        match self {
+            Condition::Exists => Some(false),
+            Condition::Not(x) => x.eval_missing().map(|b| !b),
+            Condition::And(x) if !x.is_empty() => {
+                let r: vec::Vec<_> = x.iter().map(|c| c.eval_missing()).collect();
+                if r.contains(&Some(false)) {
+                    Some(false)
+                } else {
+                    r.into_iter().collect::<Option<vec::Vec<_>>>().map(|_| true)
+                }
+            }
+            Condition::Or(x) if !x.is_empty() => {
+                let r: vec::Vec<_> = x.iter().map(|c| c.eval_missing()).collect();
+                if r.contains(&Some(true)) {
+                    Some(true)
+                } else {
+                    r.into_iter()
+                        .collect::<Option<vec::Vec<_>>>()
+                        .map(|_| false)
+                }
+            }
+            _ => None,
+        }
+    }
+    fn len(&self, r: &Value) -> Result<bool> {
+        if let Condition::Len(l) = self {
+            let n = match r {
+                Value::String(x) => x.chars().count(),
+                Value::Array(x) => x.len(),
+                Value::Object(x) => x.len(),
+                _ => {
+                    return Err(Error::new(ErrorCode::TypeMismatch {
+                        want: "String, Array or Object".to_owned(),
+                        got: format!("{}", r),
+                        by: Self::type_name(self),
+                    }))
+                }
+            };
+            l.eval(&Value::Int(n as i32))
+        } else {
+            Err(Error::unreachable())
+        }
+    }
+    fn nkeys(&self, r: &Value) -> Result<bool> {
+        match (self, r) {
+            (Condition::NKeys(l), Value::Object(x)) => l.eval(&Value::Int(x.len() as i32)),
+            (Condition::NKeys(_), _) => Err(Error::new(ErrorCode::TypeMismatch {
+                want: "Object".to_owned(),
+                got: format!("{}", r),
+                by: Self::type_name(self),
+            })),
+            _ => Err(Error::unreachable()),
+        }
+    }
+    fn email_domain(&self, r: &Value) -> Result<bool> {
+        if let Condition::EmailDomain(l) = self {
+            // split at the last @, the local part may be quoted or plus-addressed
+            let domain = match r {
+                Value::String(x) => x
+                    .rsplit_once('@')
+                    .filter(|(local, domain)| !local.is_empty() && !domain.is_empty())
+                    .map(|(_, domain)| domain.trim_end_matches('.').to_lowercase()),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error::new(ErrorCode::TypeMismatch {
+                    want: "email address".to_owned(),
+                    got: format!("{}", r),
+                    by: Self::type_name(self),
+                })
+            })?;
+            l.eval(&Value::String(domain))
+        } else {
+            Err(Error::unreachable())
+        }
+    }
+    fn empty(&self, r: &Value) -> Result<bool> {
+        match r {
+            Value::Array(x) => Ok(x.is_empty()),
+            Value::Object(x) => Ok(x.is_empty()),
+            _ => Err(Error::new(ErrorCode::TypeMismatch {
+                want: "Array or Object".to_owned(),
+                got: format!("{}", r),
+                by: Self::type_name(self),
+            })),
+        }
+    }
+    #[cfg(feature = "datetime")]
+    fn within(&self, r: &Value) -> Result<bool> {
+        if let Condition::Within(l) = self {
+            let t = match r {
+                Value::String(x) => crate::timestamp::parse_system_time(x),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error::new(ErrorCode::TypeMismatch {
+                    want: "RFC 3339 timestamp".to_owned(),
+                    got: format!("{}", r),
+                    by: Self::type_name(self),
+                })
+            })?;
+            let now = l.now.unwrap_or_else(std::time::SystemTime::now);
+            // a future time is an error of duration_since
+            Ok(matches!(now.duration_since(t), Ok(d) if d <= l.duration))
+        } else {
+            Err(Error::unreachable())
+        }
+    }
+    #[cfg(feature = "url")]
+    fn url(&self, r: &Value) -> Result<bool> {
+        if let Condition::Url(l) = self {
+            let u = match r {
+                Value::String(x) => url::Url::parse(x).ok(),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error::new(ErrorCode::TypeMismatch {
+                    want: "URL".to_owned(),
+                    got: format!("{}", r),
+                    by: Self::type_name(self),
+                })
+            })?;
+            // a missing component is evaluated as a missing value
+            let eval = |c: &Condition, v: Option<Value>| match v {
+                Some(x) => c.eval(&x),
+                None => Ok(c.eval_missing().unwrap_or(false)),
+            };
+            let parts = [
+                (&l.scheme, Some(u.scheme())),
+                (&l.host, u.host_str()),
+                (&l.path, Some(u.path())),
+                (&l.fragment, u.fragment()),
+            ];
+            for (c, v) in parts {
+                if let Some(c) = c {
+                    if !eval(c, v.map(|x| Value::String(x.to_owned())))? {
+                        return Ok(false);
+                    }
+                }
+            }
+            if let Some(c) = &l.port {
+                if !eval(c, u.port_or_known_default().map(|x| Value::Int(x.into())))? {
+                    return Ok(false);
+                }
+            }
+            for (k, c) in &l.query {
+                let mut values = u
+                    .query_pairs()
+                    .filter(|(x, _)| x == k)
+                    .map(|(_, v)| Value::String(v.into_owned()))
+                    .peekable();
+                let matched = if values.peek().is_none() {
+                    eval(c, None)?
+                } else {
+                    // stop at the first match or error
+                    values
+                        .map(|v| c.eval(&v))
+                        .find(|x| !matches!(x, Ok(false)))
+                        .unwrap_or(Ok(false))?
+                };
+                if !matched {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        } else {
+            Err(Error::unreachable())
+        }
+    }
     fn test(&self, r: &Value) -> Result<bool> {
         if let Condition::Match(l, t) = self {
             match (l, t, r) {
                 (Value::String(x), _, Value::String(y)) => match t {
-                    MatchType::Contain => Matcher::Raw(x).test(y),
-                    MatchType::Regex => Matcher::Regex(x).test(y),
+                    MatchType::Contain => Builtin::Raw(x).test(y),
+                    #[cfg(feature = "regex")]
+                    MatchType::Regex(r) => Builtin::Regex(r).test(y),
+                    MatchType::Custom(c) => c.matcher.test(x, y),
                 },
                 _ => Err(Error::new(ErrorCode::MatcherTypeMismatch {
-                    matcher_type: format!("{:?}", t),
+                    matcher_type: format!("{}", t),
                     matcher_value: format!("{}", l),
                     target: format!("{}", r),
                     by: Self::type_name(self),
@@ -40,6 +287,22 @@ impl Condition {
             Err(Error::unreachable())
         }
     }
+    #[cfg(feature = "regex")]
+    fn test_any(&self, r: &Value) -> Result<bool> {
+        if let Condition::AnyRegex(l) = self {
+            match r {
+                Value::String(y) => Ok(l.0.is_match(y)),
+                _ => Err(Error::new(ErrorCode::MatcherTypeMismatch {
+                    matcher_type: "AnyRegex".to_owned(),
+                    matcher_value: format!("{:?}", l.0.patterns()),
+                    target: format!("{}", r),
+                    by: Self::type_name(self),
+                })),
+            }
+        } else {
+            Err(Error::unreachable())
+        }
+    }
     fn equal(&self, r: &Value) -> Result<bool> {
         if let Condition::Equal(l) = self {
             match (l, r) {
@@ -48,6 +311,8 @@ impl Condition {
                 (Value::Int(x), Value::Int(y)) => Ok(*x == *y),
                 (Value::Float(x), Value::Float(y)) => Ok((*x - *y).abs() <= f64::EPSILON),
                 (Value::String(x), Value::String(y)) => Ok(*x == *y),
+                (Value::Array(x), Value::Array(y)) => Ok(*x == *y),
+                (Value::Object(x), Value::Object(y)) => Ok(*x == *y),
                 _ => Err(Error::new(ErrorCode::TypeMismatch {
                     want: Self::type_name(l),
                     got: format!("{}", r),
@@ -371,6 +636,96 @@ mod tests {
         true
     );
 
+    macro_rules! test_lenient {
+        ($name:ident, $cond:expr, $value:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let got = $cond.eval_lenient(&$value).unwrap();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_lenient!(
+        lenient_eq_type_diff,
+        Condition::Equal(Value::Int(1)),
+        Value::String("1".to_owned()),
+        false
+    );
+    test_lenient!(
+        lenient_match_type_diff,
+        Condition::Match(Value::String("1".to_owned()), MatchType::Contain),
+        Value::Int(1),
+        false
+    );
+    test_lenient!(
+        lenient_or_type_diff,
+        Condition::Or(vec![
+            Condition::Equal(Value::Int(1)),
+            Condition::Equal(Value::String("1".to_owned()))
+        ]),
+        Value::String("1".to_owned()),
+        true
+    );
+    test_lenient!(
+        lenient_and_type_diff,
+        Condition::And(vec![
+            Condition::GreaterThan(Value::Int(1)),
+            Condition::Equal(Value::String("1".to_owned()))
+        ]),
+        Value::String("1".to_owned()),
+        false
+    );
+
+    test_lenient!(
+        lenient_not_type_diff,
+        Condition::Not(Box::new(Condition::Equal(Value::Bool(true)))),
+        Value::String("true".to_owned()),
+        false
+    );
+    test_lenient!(
+        lenient_not_and_type_diff,
+        Condition::Not(Box::new(Condition::And(vec![
+            Condition::Equal(Value::Int(1)),
+            Condition::Equal(Value::String("2".to_owned()))
+        ]))),
+        Value::String("1".to_owned()),
+        true
+    );
+    test_lenient!(
+        lenient_not_or_type_diff,
+        Condition::Not(Box::new(Condition::Or(vec![
+            Condition::Equal(Value::Int(1)),
+            Condition::Equal(Value::String("2".to_owned()))
+        ]))),
+        Value::String("1".to_owned()),
+        false
+    );
+
+    #[test]
+    #[should_panic]
+    fn lenient_no_children() {
+        Condition::Or(vec![]).eval_lenient(&Value::Null).unwrap();
+    }
+
+    #[cfg(feature = "regex")]
+    fn any_regex(patterns: &[&str]) -> Condition {
+        Condition::AnyRegex(crate::query::AnyRegex(
+            regex::RegexSet::new(patterns).unwrap(),
+        ))
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn any_regex_match() {
+        let c = any_regex(&["^s", "s$"]);
+        assert!(c.eval(&Value::String("sirius".to_owned())).unwrap());
+        assert!(c.eval(&Value::String("polaris".to_owned())).unwrap());
+        assert!(!c.eval(&Value::String("vega".to_owned())).unwrap());
+        assert!(c.eval(&Value::Int(1)).err().unwrap().is_type_mismatch());
+        assert!(!c.eval_lenient(&Value::Int(1)).unwrap());
+    }
+
     macro_rules! test_equal {
         ($name:ident, $left:expr, $right:expr, $want:expr) => {
             #[test]
@@ -408,4 +763,143 @@ mod tests {
         Value::String("white".to_owned()),
         false
     );
+    test_equal!(
+        eq_object,
+        Value::from(&serde_json::json!({"a":[1,{"b":null}]})),
+        Value::from(&serde_json::json!({"a":[1,{"b":null}]})),
+        true
+    );
+    test_equal!(
+        eq_object_diff,
+        Value::from(&serde_json::json!({"a":[1,{"b":null}]})),
+        Value::from(&serde_json::json!({"a":[1,{"b":0}]})),
+        false
+    );
+
+    macro_rules! test_eval {
+        ($name:ident, $cond:expr, $value:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let got = $cond.eval(&Value::from(&$value));
+                assert_eq!($want, got.map_err(|x| x.kind()));
+            }
+        };
+    }
+
+    test_eval!(exists, Condition::Exists, serde_json::json!(null), Ok(true));
+    test_eval!(
+        is_type_number,
+        Condition::IsType(JsonType::Number),
+        serde_json::json!(1.5),
+        Ok(true)
+    );
+    test_eval!(
+        is_type_array,
+        Condition::IsType(JsonType::Array),
+        serde_json::json!({}),
+        Ok(false)
+    );
+    test_eval!(
+        len_string,
+        Condition::Len(Box::new(Condition::Equal(Value::Int(3)))),
+        serde_json::json!("αβγ"),
+        Ok(true)
+    );
+    test_eval!(
+        len_array,
+        Condition::Len(Box::new(Condition::GreaterThan(Value::Int(1)))),
+        serde_json::json!([1, 2]),
+        Ok(true)
+    );
+    test_eval!(
+        len_number,
+        Condition::Len(Box::new(Condition::Equal(Value::Int(1)))),
+        serde_json::json!(1),
+        Err(crate::error::ErrorKind::TypeMismatch)
+    );
+    test_eval!(
+        nkeys,
+        Condition::NKeys(Box::new(Condition::LessThan(Value::Int(3)))),
+        serde_json::json!({"a":1,"b":{"c":2}}),
+        Ok(true)
+    );
+    test_eval!(
+        nkeys_array,
+        Condition::NKeys(Box::new(Condition::LessThan(Value::Int(3)))),
+        serde_json::json!([1]),
+        Err(crate::error::ErrorKind::TypeMismatch)
+    );
+    test_eval!(
+        email_domain,
+        Condition::EmailDomain(Box::new(Condition::Equal(Value::String(
+            "example.com".to_owned()
+        )))),
+        serde_json::json!("Alice+news@Example.COM"),
+        Ok(true)
+    );
+    test_eval!(
+        email_domain_quoted,
+        Condition::EmailDomain(Box::new(Condition::Equal(Value::String(
+            "example.com".to_owned()
+        )))),
+        serde_json::json!(r#""a@b"@example.org"#),
+        Ok(false)
+    );
+    test_eval!(
+        email_domain_not_email,
+        Condition::EmailDomain(Box::new(Condition::Equal(Value::String(
+            "example.com".to_owned()
+        )))),
+        serde_json::json!("example.com"),
+        Err(crate::error::ErrorKind::TypeMismatch)
+    );
+    test_eval!(
+        empty_array,
+        Condition::Empty,
+        serde_json::json!([]),
+        Ok(true)
+    );
+    test_eval!(
+        empty_object,
+        Condition::Empty,
+        serde_json::json!({"a":null}),
+        Ok(false)
+    );
+    test_eval!(
+        not_empty_object,
+        Condition::NotEmpty,
+        serde_json::json!({"a":null}),
+        Ok(true)
+    );
+    test_eval!(
+        empty_string,
+        Condition::Empty,
+        serde_json::json!(""),
+        Err(crate::error::ErrorKind::TypeMismatch)
+    );
+
+    macro_rules! test_eval_missing {
+        ($name:ident, $cond:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($want, $cond.eval_missing());
+            }
+        };
+    }
+
+    test_eval_missing!(missing_exists, Condition::Exists, Some(false));
+    test_eval_missing!(missing_eq, Condition::Equal(Value::Null), None);
+    test_eval_missing!(
+        missing_or,
+        Condition::Or(vec![
+            Condition::Equal(Value::Null),
+            Condition::Not(Box::new(Condition::Exists))
+        ]),
+        Some(true)
+    );
+    test_eval_missing!(
+        missing_or_undecided,
+        Condition::Or(vec![Condition::Equal(Value::Null), Condition::Exists]),
+        None
+    );
 }
@@ -0,0 +1,103 @@
+use crate::error::{Error, ErrorCode, Result};
+use crate::pointer::Pointer;
+use serde_json::from_str;
+use serde_json::value::{RawValue, Value as JSONValue};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+/// JSON whose values are located by JSON pointers.
+pub(crate) trait Document: fmt::Display {
+    /// Return the value at `pointer`, `None` if not found.
+    fn pointed(&self, pointer: &Pointer) -> Result<Option<Cow<'_, JSONValue>>>;
+}
+
+impl Document for JSONValue {
+    fn pointed(&self, pointer: &Pointer) -> Result<Option<Cow<'_, JSONValue>>> {
+        Ok(pointer.get(self).map(Cow::Borrowed))
+    }
+}
+
+/// Parse only the values on the path to `pointer`.
+impl Document for RawValue {
+    fn pointed(&self, pointer: &Pointer) -> Result<Option<Cow<'_, JSONValue>>> {
+        match raw_pointer(self, pointer)? {
+            Some(x) => from_str(x.get())
+                .map(|x| Some(Cow::Owned(x)))
+                .map_err(|x| Error::new(ErrorCode::Json(x))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Same as [`Pointer::get`] but for [`RawValue`].
+fn raw_pointer<'a>(v: &'a RawValue, pointer: &Pointer) -> Result<Option<&'a RawValue>> {
+    if !pointer.is_valid() {
+        return Ok(None);
+    }
+    let mut target = v;
+    for token in pointer.tokens() {
+        let next = match target.get().trim_start().as_bytes().first() {
+            Some(b'{') => from_str::<HashMap<String, &'a RawValue>>(target.get())
+                .map_err(|x| Error::new(ErrorCode::Json(x)))?
+                .remove(token.as_str()),
+            Some(b'[') => {
+                let mut a = from_str::<Vec<&'a RawValue>>(target.get())
+                    .map_err(|x| Error::new(ErrorCode::Json(x)))?;
+                token
+                    .index()
+                    .filter(|i| *i < a.len())
+                    .map(|i| a.swap_remove(i))
+            }
+            _ => None,
+        };
+        match next {
+            Some(x) => target = x,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+{
+    "n": null,
+    "d": {
+        "i": 1,
+        "a": [
+            "one",
+            {"t/w~o": 2}
+        ]
+    }
+}"#;
+
+    macro_rules! test_raw_pointer {
+        ($name:ident, $pointer:literal) => {
+            #[test]
+            fn $name() {
+                let raw: &RawValue = from_str(SAMPLE).unwrap();
+                let value: JSONValue = from_str(SAMPLE).unwrap();
+                let pointer = Pointer::lenient($pointer);
+                let got = raw.pointed(&pointer).unwrap().map(|x| x.into_owned());
+                let want = value.pointed(&pointer).unwrap().map(|x| x.into_owned());
+                assert_eq!(want, got);
+            }
+        };
+    }
+
+    test_raw_pointer!(raw_pointer_root, "");
+    test_raw_pointer!(raw_pointer_null, "/n");
+    test_raw_pointer!(raw_pointer_object, "/d");
+    test_raw_pointer!(raw_pointer_nested, "/d/i");
+    test_raw_pointer!(raw_pointer_index, "/d/a/0");
+    test_raw_pointer!(raw_pointer_escaped, "/d/a/1/t~1w~0o");
+    test_raw_pointer!(raw_pointer_out_of_bounds, "/d/a/2");
+    test_raw_pointer!(raw_pointer_leading_zero, "/d/a/01");
+    test_raw_pointer!(raw_pointer_not_found, "/x");
+    test_raw_pointer!(raw_pointer_scalar, "/d/i/0");
+    test_raw_pointer!(raw_pointer_no_slash, "d");
+}
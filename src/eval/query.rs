@@ -1,9 +1,12 @@
 use crate::error::Result;
 use crate::query::{EvaluableQuery, Query};
-use serde_json::value::Value as JSONValue;
+use serde_json::value::{RawValue, Value as JSONValue};
 
 impl EvaluableQuery for Query {
     fn eval(&self, value: &JSONValue) -> Result<bool> {
-        self.query.eval(value)
+        self.query.eval_document(value)
+    }
+    fn eval_raw(&self, value: &RawValue) -> Result<bool> {
+        self.query.eval_document(value)
     }
 }
@@ -1,13 +1,21 @@
 use crate::error::{Error, ErrorCode, Result};
+use crate::eval::document::Document;
+use crate::pointer::Pointer;
 use crate::query::{EvaluableQueryCondition, QueryCondition};
 use crate::util;
 use serde_json::value::Value as JSONValue;
 
 impl EvaluableQueryCondition for QueryCondition {
     fn eval(&self, value: &JSONValue) -> Result<bool> {
+        self.eval_document(value)
+    }
+}
+
+impl QueryCondition {
+    pub(crate) fn eval_document<D: Document + ?Sized>(&self, value: &D) -> Result<bool> {
         match self {
-            QueryCondition::Raw(x) => x.eval(value),
-            QueryCondition::Not(x) => x.eval(value).map(|b| !b),
+            QueryCondition::Raw(x) => x.eval_document(value),
+            QueryCondition::Not(x) => x.eval_document(value).map(|b| !b),
             QueryCondition::And(x) => {
                 if x.is_empty() {
                     return Err(Error::new(ErrorCode::NoChildren {
@@ -15,7 +23,7 @@ impl EvaluableQueryCondition for QueryCondition {
                     }));
                 }
                 for v in x {
-                    let r = v.eval(value);
+                    let r = v.eval_document(value);
                     match r {
                         Err(_) | Ok(false) => return r,
                         _ => continue,
@@ -30,7 +38,7 @@ impl EvaluableQueryCondition for QueryCondition {
                     }));
                 }
                 for v in x {
-                    let r = v.eval(value);
+                    let r = v.eval_document(value);
                     match r {
                         Err(_) | Ok(true) => return r,
                         _ => continue,
@@ -40,19 +48,43 @@ impl EvaluableQueryCondition for QueryCondition {
             }
         }
     }
+    /// Collect the pointers of the pairs that accept the value, the errors as denials.
+    pub(crate) fn collect_matched<'a, D: Document + ?Sized>(
+        &'a self,
+        value: &D,
+        r: &mut Vec<&'a Pointer>,
+    ) {
+        match self {
+            QueryCondition::Raw(x) => {
+                if let Ok(true) = x.eval_document(value) {
+                    r.push(&x.pointer);
+                }
+            }
+            QueryCondition::Not(x) => x.collect_matched(value, r),
+            QueryCondition::And(x) | QueryCondition::Or(x) => {
+                x.iter().for_each(|c| c.collect_matched(value, r))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::query::EvaluableQueryPair;
+    use crate::query::{Condition, MismatchPolicy, QueryPair, Value};
 
     const NULL: JSONValue = JSONValue::Null;
 
-    struct MockQueryPair(bool);
-    impl EvaluableQueryPair for MockQueryPair {
-        fn eval(&self, _: &JSONValue) -> Result<bool> {
-            Ok(self.0)
+    /// Return a pair that evaluates [`NULL`] as `b`.
+    fn pair(b: bool) -> QueryPair {
+        let c = Condition::Equal(Value::Null);
+        QueryPair {
+            pointer: Pointer::lenient(""),
+            condition: if b { c } else { Condition::Not(Box::new(c)) },
+            on_mismatch: MismatchPolicy::Error,
+            coerce: false,
+            trim: false,
+            collapse_ws: false,
         }
     }
 
@@ -70,29 +102,21 @@ mod tests {
     fn or_no_children() {
         QueryCondition::Or(vec![]).eval(&NULL).unwrap();
     }
-    test_or!(
-        or_one,
-        vec![QueryCondition::Raw(Box::new(MockQueryPair(true)))],
-        true
-    );
-    test_or!(
-        or_one_not,
-        vec![QueryCondition::Raw(Box::new(MockQueryPair(false)))],
-        false
-    );
+    test_or!(or_one, vec![QueryCondition::Raw(pair(true))], true);
+    test_or!(or_one_not, vec![QueryCondition::Raw(pair(false))], false);
     test_or!(
         or_two,
         vec![
-            QueryCondition::Raw(Box::new(MockQueryPair(false))),
-            QueryCondition::Raw(Box::new(MockQueryPair(true)))
+            QueryCondition::Raw(pair(false)),
+            QueryCondition::Raw(pair(true))
         ],
         true
     );
     test_or!(
         or_two_not,
         vec![
-            QueryCondition::Raw(Box::new(MockQueryPair(false))),
-            QueryCondition::Raw(Box::new(MockQueryPair(false)))
+            QueryCondition::Raw(pair(false)),
+            QueryCondition::Raw(pair(false))
         ],
         false
     );
@@ -111,49 +135,37 @@ mod tests {
     fn and_no_children() {
         QueryCondition::And(vec![]).eval(&NULL).unwrap();
     }
-    test_and!(
-        and_one,
-        vec![QueryCondition::Raw(Box::new(MockQueryPair(true)))],
-        true
-    );
-    test_and!(
-        and_one_not,
-        vec![QueryCondition::Raw(Box::new(MockQueryPair(false)))],
-        false
-    );
+    test_and!(and_one, vec![QueryCondition::Raw(pair(true))], true);
+    test_and!(and_one_not, vec![QueryCondition::Raw(pair(false))], false);
     test_and!(
         and_two,
         vec![
-            QueryCondition::Raw(Box::new(MockQueryPair(true))),
-            QueryCondition::Raw(Box::new(MockQueryPair(true)))
+            QueryCondition::Raw(pair(true)),
+            QueryCondition::Raw(pair(true))
         ],
         true
     );
     test_and!(
         and_two_not,
         vec![
-            QueryCondition::Raw(Box::new(MockQueryPair(true))),
-            QueryCondition::Raw(Box::new(MockQueryPair(false)))
+            QueryCondition::Raw(pair(true)),
+            QueryCondition::Raw(pair(false))
         ],
         false
     );
 
     #[test]
     fn raw_true() {
-        assert!(QueryCondition::Raw(Box::new(MockQueryPair(true)))
-            .eval(&NULL)
-            .unwrap());
+        assert!(QueryCondition::Raw(pair(true)).eval(&NULL).unwrap());
     }
     #[test]
     fn raw_false() {
-        assert!(!QueryCondition::Raw(Box::new(MockQueryPair(false)))
-            .eval(&NULL)
-            .unwrap());
+        assert!(!QueryCondition::Raw(pair(false)).eval(&NULL).unwrap());
     }
     #[test]
     fn not_true() {
         assert!(
-            !QueryCondition::Not(Box::new(QueryCondition::Raw(Box::new(MockQueryPair(true)))))
+            !QueryCondition::Not(Box::new(QueryCondition::Raw(pair(true))))
                 .eval(&NULL)
                 .unwrap()
         );
@@ -161,11 +173,9 @@ mod tests {
     #[test]
     fn not_false() {
         assert!(
-            QueryCondition::Not(Box::new(QueryCondition::Raw(Box::new(MockQueryPair(
-                false
-            )))))
-            .eval(&NULL)
-            .unwrap()
+            QueryCondition::Not(Box::new(QueryCondition::Raw(pair(false))))
+                .eval(&NULL)
+                .unwrap()
         );
     }
 }
@@ -1,38 +1,94 @@
-use crate::error::{Error, ErrorCode, Result};
-use crate::query::{EvaluableQueryPair, QueryPair, Value};
+use crate::error::{Error, ErrorCode, ErrorKind, Result};
+use crate::eval::document::Document;
+use crate::pointer::Pointer;
+use crate::query::{
+    Condition, EvaluableCondition, EvaluableQueryPair, MismatchPolicy, QueryPair, Value,
+};
+use crate::util;
 use serde_json::value::Value as JSONValue;
+use std::vec;
 
 impl QueryPair {
-    fn to_value(pointer: &str, v: &JSONValue) -> Result<Value> {
-        let p = v.pointer(pointer).ok_or_else(|| {
+    pub(crate) fn to_value<D: Document + ?Sized>(pointer: &Pointer, v: &D) -> Result<Value> {
+        let p = v.pointed(pointer)?.ok_or_else(|| {
             Error::new(ErrorCode::InvalidPointer {
-                pointer: pointer.to_owned(),
+                pointer: pointer.to_string(),
                 value: format!("{}", v),
             })
         })?;
-        match p {
-            JSONValue::Null => Ok(Value::Null),
-            JSONValue::Bool(x) => Ok(Value::Bool(*x)),
-            JSONValue::Number(x) => {
-                if x.is_i64() {
-                    Ok(Value::Int(x.as_i64().unwrap() as i32))
-                } else {
-                    Ok(Value::Float(x.as_f64().unwrap()))
-                }
+        Ok(Value::from(p.as_ref()))
+    }
+    /// Return the value preprocessed by the flags of the pair, `None` if unchanged.
+    pub(crate) fn preprocess(&self, v: &Value) -> Option<Value> {
+        let normalized = match v {
+            Value::String(x) if self.collapse_ws => Some(Value::String(
+                x.split_whitespace().collect::<vec::Vec<_>>().join(" "),
+            )),
+            Value::String(x) if self.trim => Some(Value::String(x.trim().to_owned())),
+            _ => None,
+        };
+        if self.coerce && self.condition.compares_number() {
+            if let Some(x) = Self::coerce(normalized.as_ref().unwrap_or(v)) {
+                return Some(x);
             }
-            JSONValue::String(x) => Ok(Value::String(x.as_str().to_string())),
-            _ => Err(Error::new(ErrorCode::InvalidTarget {
-                pointer: pointer.to_owned(),
-                value: format!("{}", v),
-            })),
+        }
+        normalized
+    }
+    /// Report whether [`QueryPair::preprocess`] may change the values.
+    pub(crate) fn preprocesses(&self) -> bool {
+        self.trim || self.collapse_ws || self.coerce && self.condition.compares_number()
+    }
+    /// Return the number of the numeric string `v`, `None` if `v` is not the one.
+    pub(crate) fn coerce(v: &Value) -> Option<Value> {
+        match v {
+            Value::String(x) => x
+                .trim()
+                .parse::<serde_json::Number>()
+                .ok()
+                .map(|n| Value::from(&JSONValue::Number(n))),
+            _ => None,
         }
     }
+    /// Evaluate `condition` against the value at `pointer`,
+    /// or against the missing value if the condition accepts it, e.g. `exists`.
+    pub(crate) fn eval_pointed<D: Document + ?Sized>(
+        pointer: &Pointer,
+        v: &D,
+        eval: impl FnOnce(&Value) -> Result<bool>,
+        condition: &Condition,
+    ) -> Result<bool> {
+        let r = match Self::to_value(pointer, v) {
+            Ok(x) => eval(&x),
+            Err(e) if e.kind() == ErrorKind::InvalidPointer => condition.eval_missing().ok_or(e),
+            Err(e) => Err(e),
+        };
+        util::event!(trace, pointer = %pointer, outcome = ?r.as_ref().ok(), "evaluated pair");
+        r
+    }
 }
 
 impl EvaluableQueryPair for QueryPair {
     fn eval(&self, value: &JSONValue) -> Result<bool> {
-        let v = Self::to_value(&self.pointer, value)?;
-        self.condition.eval(&v)
+        self.eval_document(value)
+    }
+}
+
+impl QueryPair {
+    pub(crate) fn eval_document<D: Document + ?Sized>(&self, value: &D) -> Result<bool> {
+        let preprocesses = self.preprocesses();
+        let eval = |v: &Value| {
+            let preprocessed = if preprocesses {
+                self.preprocess(v)
+            } else {
+                None
+            };
+            let v = preprocessed.as_ref().unwrap_or(v);
+            match self.on_mismatch {
+                MismatchPolicy::Error => self.condition.eval(v),
+                MismatchPolicy::False => self.condition.eval_lenient(v),
+            }
+        };
+        Self::eval_pointed(&self.pointer, value, eval, &self.condition)
     }
 }
 
@@ -40,10 +96,15 @@ impl EvaluableQueryPair for QueryPair {
 mod tests {
     use super::*;
     use serde_json::from_str;
+    use std::convert::TryFrom;
 
     const SAMPLE: &str = r#"
 {
     "n": null,
+    "s": "42",
+    "w": " a  b\t",
+    "t": "2024-01-02T00:00:00Z",
+    "u": "https://example.com/api/v1?page=2&tag=a&tag=b",
     "d": {
         "i": 1,
         "f": 1.2,
@@ -59,8 +120,8 @@ mod tests {
         ($name:ident, $pointer:literal, $want:expr) => {
             #[test]
             fn $name() {
-                let j = from_str(SAMPLE).unwrap();
-                let got = QueryPair::to_value($pointer, &j).unwrap();
+                let j: JSONValue = from_str(SAMPLE).unwrap();
+                let got = QueryPair::to_value(&Pointer::lenient($pointer), &j).unwrap();
                 assert_eq!($want, got);
             }
         };
@@ -70,19 +131,189 @@ mod tests {
     test_to_value!(to_value_int, "/d/i", Value::Int(1));
     test_to_value!(to_value_float, "/d/f", Value::Float(1.2));
     test_to_value!(to_value_str, "/d/a/1", Value::String("two".to_owned()));
+    test_to_value!(
+        to_value_array,
+        "/d/a",
+        Value::from(&serde_json::json!(["one", "two", "three"]))
+    );
+    test_to_value!(
+        to_value_root,
+        "",
+        Value::from(&from_str::<JSONValue>(SAMPLE).unwrap())
+    );
 
     macro_rules! test_to_value_fail {
         ($name:ident, $pointer:literal) => {
             #[test]
             #[should_panic]
             fn $name() {
-                let j = from_str(SAMPLE).unwrap();
-                QueryPair::to_value($pointer, &j).unwrap();
+                let j: JSONValue = from_str(SAMPLE).unwrap();
+                QueryPair::to_value(&Pointer::lenient($pointer), &j).unwrap();
             }
         };
     }
 
     test_to_value_fail!(to_value_fail_out_of_bounds, "/X");
-    test_to_value_fail!(to_value_fail_array, "/d/a");
-    test_to_value_fail!(to_value_fail_object, "/d");
+
+    macro_rules! test_eval_document {
+        ($name:ident, $pair:literal, $want:expr) => {
+            #[test]
+            fn $name() {
+                let j: JSONValue = from_str(SAMPLE).unwrap();
+                let raw: crate::raw_query::QueryPair = from_str($pair).unwrap();
+                let got = QueryPair::try_from(raw).unwrap().eval(&j);
+                assert_eq!($want, got.map_err(|x| x.kind()));
+            }
+        };
+    }
+
+    test_eval_document!(
+        eval_trim,
+        r#"{"p":"/w","trim":true,"cond":{"type":"eq","value":{"type":"string","value":"a  b"}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_collapse_ws,
+        r#"{"p":"/w","collapse_ws":true,"cond":{"type":"eq","value":{"type":"string","value":"a b"}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_collapse_ws_coerce,
+        r#"{"p":"/w","collapse_ws":true,"coerce":true,"cond":{"type":"eq","value":{"type":"string","value":"a b"}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_no_trim,
+        r#"{"p":"/w","cond":{"type":"eq","value":{"type":"string","value":"a  b"}}}"#,
+        Ok(false)
+    );
+    test_eval_document!(
+        eval_coerce,
+        r#"{"p":"/s","coerce":true,"cond":{"type":"gt","value":{"type":"number","value":41}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_coerce_eq_string,
+        r#"{"p":"/s","coerce":true,"cond":{"type":"eq","value":{"type":"string","value":"42"}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_coerce_not_numeric,
+        r#"{"p":"/d/a/0","coerce":true,"cond":{"type":"eq","value":{"type":"number","value":1}}}"#,
+        Err(ErrorKind::TypeMismatch)
+    );
+    test_eval_document!(
+        eval_no_coerce,
+        r#"{"p":"/s","cond":{"type":"eq","value":{"type":"number","value":42}}}"#,
+        Err(ErrorKind::TypeMismatch)
+    );
+    #[cfg(feature = "datetime")]
+    test_eval_document!(
+        eval_within,
+        r#"{"p":"/t","cond":{"type":"within","duration":"24h","now":"2024-01-02T12:00:00Z"}}"#,
+        Ok(true)
+    );
+    #[cfg(feature = "datetime")]
+    test_eval_document!(
+        eval_within_before,
+        r#"{"p":"/t","cond":{"type":"within","duration":"1h","now":"2024-01-02T12:00:00Z"}}"#,
+        Ok(false)
+    );
+    #[cfg(feature = "datetime")]
+    test_eval_document!(
+        eval_within_future,
+        r#"{"p":"/t","cond":{"type":"within","duration":"1d","now":"2024-01-01T12:00:00Z"}}"#,
+        Ok(false)
+    );
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn eval_within_offset() {
+        let j: JSONValue = from_str(r#"{"t":"2024-01-02T09:00:00+09:00"}"#).unwrap();
+        let raw: crate::raw_query::QueryPair = from_str(
+            r#"{"p":"/t","cond":{"type":"within","duration":"1h","now":"2024-01-02T00:30:00Z"}}"#,
+        )
+        .unwrap();
+        assert!(QueryPair::try_from(raw).unwrap().eval(&j).unwrap());
+    }
+    #[cfg(feature = "datetime")]
+    test_eval_document!(
+        eval_within_not_timestamp,
+        r#"{"p":"/s","cond":{"type":"within","duration":"1d"}}"#,
+        Err(ErrorKind::TypeMismatch)
+    );
+    #[cfg(feature = "url")]
+    test_eval_document!(
+        eval_url,
+        r#"{"p":"/u","cond":{"type":"url",
+"host":{"type":"eq","value":{"type":"string","value":"example.com"}},
+"port":{"type":"eq","value":{"type":"number","value":443}},
+"path":{"type":"match","mtype":"regex","value":{"type":"string","value":"^/api/"}},
+"query":{"tag":{"type":"eq","value":{"type":"string","value":"b"}}}}}"#,
+        Ok(true)
+    );
+    #[cfg(feature = "url")]
+    test_eval_document!(
+        eval_url_host,
+        r#"{"p":"/u","cond":{"type":"url","host":{"type":"eq","value":{"type":"string","value":"example.org"}}}}"#,
+        Ok(false)
+    );
+    #[cfg(feature = "url")]
+    test_eval_document!(
+        eval_url_missing_param,
+        r#"{"p":"/u","cond":{"type":"url","query":{
+"page":{"type":"exists"},
+"q":{"type":"not","value":{"type":"exists"}}}}}"#,
+        Ok(true)
+    );
+    #[cfg(feature = "url")]
+    test_eval_document!(
+        eval_url_not_url,
+        r#"{"p":"/s","cond":{"type":"url","host":{"type":"exists"}}}"#,
+        Err(ErrorKind::TypeMismatch)
+    );
+    test_eval_document!(
+        eval_exists,
+        r#"{"p":"/d/a","cond":{"type":"exists"}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_exists_missing,
+        r#"{"p":"/X","cond":{"type":"exists"}}"#,
+        Ok(false)
+    );
+    test_eval_document!(
+        eval_not_exists_missing,
+        r#"{"p":"/X","cond":{"type":"not","value":{"type":"exists"}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_exists_and_missing,
+        r#"{"p":"/X","cond":{"type":"and","value":[{"type":"exists"},{"type":"eq","value":{"type":"null"}}]}}"#,
+        Ok(false)
+    );
+    test_eval_document!(
+        eval_eq_missing,
+        r#"{"p":"/X","cond":{"type":"eq","value":{"type":"null"}}}"#,
+        Err(ErrorKind::InvalidPointer)
+    );
+    test_eval_document!(
+        eval_root_is_object,
+        r#"{"p":"","cond":{"type":"is_type","value":"object"}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_root_len,
+        r#"{"p":"","cond":{"type":"len","cond":{"type":"eq","value":{"type":"number","value":6}}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_eq_array,
+        r#"{"p":"/d/a","cond":{"type":"eq","value":{"type":"json","value":["one","two","three"]}}}"#,
+        Ok(true)
+    );
+    test_eval_document!(
+        eval_eq_array_mismatch,
+        r#"{"p":"/d/a","cond":{"type":"eq","value":{"type":"string","value":"one"}}}"#,
+        Err(ErrorKind::TypeMismatch)
+    );
 }
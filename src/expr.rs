@@ -0,0 +1,309 @@
+//! Compact query expression.
+//!
+//! An expression is `pointer op value` triples combined by `and`, `or`, `not` and parentheses,
+//! `and` binds tighter than `or`.
+//!
+//! | op | condition |
+//! |----|-----------|
+//! | `eq`, `==` | equal to |
+//! | `ne`, `!=` | not equal to |
+//! | `gt`, `>` | greater than |
+//! | `lt`, `<` | less than |
+//! | `ge`, `>=` | not less than |
+//! | `le`, `<=` | not greater than |
+//! | `contains` | contains the string |
+//! | `regex`, `=~` | matches the regular expression |
+//!
+//! The pointers, the ops and the values are separated by whitespace.
+//! A value is a json literal, a string is double quoted.
+//! A pointer may also be double quoted.
+//!
+//! ```
+//! # use jsongrep::expr;
+//! # use jsongrep::query::Query;
+//! # use jsongrep::select;
+//! # use std::convert::TryFrom;
+//!
+//! let raw = expr::parse(r#"/level eq "error" and not (/code lt 500)"#).unwrap();
+//! let sel = select::Query::new(Box::new(Query::try_from(raw).unwrap()));
+//! assert!(sel.matches(r#"{"level":"error","code":503}"#).unwrap());
+//! assert!(!sel.matches(r#"{"level":"error","code":404}"#).unwrap());
+//! ```
+use crate::error::{Error, ErrorCode, Result};
+use crate::raw_query as raw;
+use serde_json::from_str;
+use serde_json::value::Value as JSONValue;
+use std::fmt;
+use std::iter::Peekable;
+use std::vec;
+
+/// Parse the expression into a raw query.
+pub fn parse(expr: &str) -> Result<raw::Query> {
+    let mut p = Parser {
+        expr,
+        tokens: tokenize(expr)?.into_iter().peekable(),
+    };
+    let query = p.or()?;
+    match p.tokens.next() {
+        None => Ok(raw::Query {
+            query,
+            on_mismatch: None,
+        }),
+        Some(x) => Err(p.error(format!("unexpected {}", x))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Bare word.
+    Word(String),
+    /// Double quoted string, with the quotes.
+    Quoted(String),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(x) | Token::Quoted(x) => write!(f, "{}", x),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn invalid(expr: &str, reason: impl Into<String>) -> Error {
+    Error::new(ErrorCode::InvalidExpression {
+        expr: expr.to_owned(),
+        reason: reason.into(),
+    })
+}
+
+fn tokenize(expr: &str) -> Result<vec::Vec<Token>> {
+    let mut tokens = vec::Vec::new();
+    let mut chars = expr.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => continue,
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' => {
+                let mut escaped = false;
+                let end = loop {
+                    match chars.next() {
+                        Some((j, '"')) if !escaped => break j,
+                        Some((_, x)) => escaped = !escaped && x == '\\',
+                        None => return Err(invalid(expr, "unterminated string")),
+                    }
+                };
+                tokens.push(Token::Quoted(expr[i..=end].to_owned()));
+            }
+            _ => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, x)) = chars.peek() {
+                    if x.is_whitespace() || *x == '(' || *x == ')' || *x == '"' {
+                        break;
+                    }
+                    end = j + x.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(expr[i..end].to_owned()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    expr: &'a str,
+    tokens: Peekable<vec::IntoIter<Token>>,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: impl Into<String>) -> Error {
+        invalid(self.expr, reason)
+    }
+    fn next(&mut self, want: &str) -> Result<Token> {
+        self.tokens
+            .next()
+            .ok_or_else(|| invalid(self.expr, format!("{} is missing", want)))
+    }
+    fn keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.tokens.peek(), Some(Token::Word(x)) if x == keyword) {
+            self.tokens.next();
+            true
+        } else {
+            false
+        }
+    }
+    fn or(&mut self) -> Result<raw::QueryCondition> {
+        let mut pair = vec![self.and()?];
+        while self.keyword("or") {
+            pair.push(self.and()?);
+        }
+        Ok(if pair.len() == 1 {
+            pair.pop().unwrap()
+        } else {
+            raw::QueryCondition::Or { pair }
+        })
+    }
+    fn and(&mut self) -> Result<raw::QueryCondition> {
+        let mut pair = vec![self.unary()?];
+        while self.keyword("and") {
+            pair.push(self.unary()?);
+        }
+        Ok(if pair.len() == 1 {
+            pair.pop().unwrap()
+        } else {
+            raw::QueryCondition::And { pair }
+        })
+    }
+    fn unary(&mut self) -> Result<raw::QueryCondition> {
+        if self.keyword("not") {
+            return Ok(raw::QueryCondition::Not {
+                pair: Box::new(self.unary()?),
+            });
+        }
+        if let Some(Token::Open) = self.tokens.peek() {
+            self.tokens.next();
+            let x = self.or()?;
+            return match self.next(")")? {
+                Token::Close => Ok(x),
+                t => Err(self.error(format!("unexpected {}, want )", t))),
+            };
+        }
+        self.triple()
+    }
+    fn triple(&mut self) -> Result<raw::QueryCondition> {
+        let pointer = match self.next("pointer")? {
+            Token::Word(x) => x,
+            Token::Quoted(x) => from_str(&x).map_err(|_| self.error("invalid string"))?,
+            t => return Err(self.error(format!("unexpected {}, want pointer", t))),
+        };
+        let op = match self.next("operator")? {
+            Token::Word(x) => x,
+            t => return Err(self.error(format!("unexpected {}, want operator", t))),
+        };
+        let value = self.value()?;
+        let condition = match op.as_str() {
+            "eq" | "==" => raw::Condition::Equal { value },
+            "ne" | "!=" => not(raw::Condition::Equal { value }),
+            "gt" | ">" => raw::Condition::GreaterThan { value },
+            "lt" | "<" => raw::Condition::LessThan { value },
+            "ge" | ">=" => not(raw::Condition::LessThan { value }),
+            "le" | "<=" => not(raw::Condition::GreaterThan { value }),
+            "contains" => raw::Condition::Match {
+                value,
+                mtype: raw::MatchType::Contain,
+                flags: None,
+                full: false,
+            },
+            "regex" | "=~" => raw::Condition::Match {
+                value,
+                mtype: raw::MatchType::Regex,
+                flags: None,
+                full: false,
+            },
+            x => return Err(self.error(format!("unknown operator {}", x))),
+        };
+        Ok(raw::QueryCondition::Raw {
+            pair: raw::QueryPair {
+                pointer,
+                condition,
+                on_mismatch: None,
+                coerce: false,
+                trim: false,
+                collapse_ws: false,
+            },
+        })
+    }
+    fn value(&mut self) -> Result<raw::Value> {
+        let (Token::Word(x) | Token::Quoted(x)) = self.next("value")? else {
+            return Err(self.error("unexpected parenthesis, want value"));
+        };
+        match from_str(&x) {
+            Ok(JSONValue::Null) => Ok(raw::Value::Null),
+            Ok(JSONValue::Bool(value)) => Ok(raw::Value::Bool { value }),
+            Ok(JSONValue::Number(v)) => Ok(raw::Value::Number {
+                value: v.as_f64().unwrap(),
+            }),
+            Ok(JSONValue::String(value)) => Ok(raw::Value::String { value }),
+            _ => Err(self.error(format!("invalid value {}", x))),
+        }
+    }
+}
+
+fn not(v: raw::Condition) -> raw::Condition {
+    raw::Condition::Not { value: Box::new(v) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{EvaluableQuery, Query};
+    use std::convert::TryFrom;
+
+    macro_rules! test_parse {
+        ($name:ident, $expr:literal, $json:literal, $want:expr) => {
+            #[test]
+            fn $name() {
+                let q = Query::try_from(parse($expr).unwrap()).unwrap();
+                assert_eq!($want, q.eval(&from_str($json).unwrap()).unwrap());
+            }
+        };
+    }
+
+    test_parse!(
+        parse_eq,
+        r#"/level eq "error""#,
+        r#"{"level":"error"}"#,
+        true
+    );
+    test_parse!(parse_symbol, "/i >= 2", r#"{"i":2}"#, true);
+    test_parse!(parse_ne, "/b != true", r#"{"b":false}"#, true);
+    test_parse!(parse_null, "/n == null", r#"{"n":null}"#, true);
+    test_parse!(
+        parse_contains,
+        r#"/s contains "ri""#,
+        r#"{"s":"sirius"}"#,
+        true
+    );
+    test_parse!(
+        parse_and_binds_tighter,
+        "/i eq 1 or /i eq 2 and /j eq 0",
+        r#"{"i":1,"j":1}"#,
+        true
+    );
+    test_parse!(
+        parse_parenthesis,
+        "(/i eq 1 or /i eq 2) and /j eq 0",
+        r#"{"i":1,"j":1}"#,
+        false
+    );
+    test_parse!(
+        parse_not,
+        r#"not /s eq "a \"b\"""#,
+        r#"{"s":"a \"b\""}"#,
+        false
+    );
+    test_parse!(parse_quoted_pointer, r#""/a b" le 2"#, r#"{"a b":1}"#, true);
+
+    macro_rules! test_parse_error {
+        ($name:ident, $expr:literal) => {
+            #[test]
+            fn $name() {
+                let e = parse($expr).unwrap_err();
+                assert_eq!(crate::error::ErrorKind::InvalidExpression, e.kind());
+            }
+        };
+    }
+
+    test_parse_error!(parse_error_empty, "");
+    test_parse_error!(parse_error_no_value, "/i eq");
+    test_parse_error!(parse_error_unknown_operator, "/i like 1");
+    test_parse_error!(parse_error_bare_string, "/s eq error");
+    test_parse_error!(parse_error_unterminated, r#"/s eq "error"#);
+    test_parse_error!(parse_error_unclosed, "(/i eq 1");
+    test_parse_error!(parse_error_trailing, "/i eq 1 /j eq 2");
+}
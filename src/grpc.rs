@@ -0,0 +1,278 @@
+//! gRPC service that filters a stream of json records by a query.
+//!
+//! ```proto
+//! syntax = "proto3";
+//! package jsongrep;
+//!
+//! message Record {
+//!   string json = 1;
+//! }
+//!
+//! service Filter {
+//!   rpc Filter(stream Record) returns (stream Record);
+//! }
+//! ```
+//!
+//! The query is given by the request metadata [`QUERY_METADATA`],
+//! the same json as [`raw_query::Query`],
+//! or by [`QUERY_BIN_METADATA`] if the query is not ascii.
+//! The records meeting the condition are returned in order,
+//! the others and the records that failed to be evaluated are skipped.
+//!
+//! ```no_run
+//! # use jsongrep::grpc::FilterServer;
+//! # async fn serve() -> Result<(), tonic::transport::Error> {
+//! tonic::transport::Server::builder()
+//!     .add_service(FilterServer::default())
+//!     .serve("127.0.0.1:50051".parse().unwrap())
+//!     .await
+//! # }
+//! ```
+// tonic::Status is the error of the service as is
+#![allow(clippy::result_large_err)]
+use crate::error::Error;
+use crate::query::Query;
+use crate::raw_query;
+use crate::select;
+use futures::future;
+use futures::stream::StreamExt;
+use std::convert::{Infallible, TryFrom};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tonic::codegen::{
+    empty_body, http, Body, BoxFuture, BoxStream, Context, Poll, Service, StdError,
+};
+use tonic::server::{Grpc, NamedService, StreamingService};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+/// Metadata key of the query.
+pub const QUERY_METADATA: &str = "jsongrep-query";
+/// Binary metadata key of the query.
+pub const QUERY_BIN_METADATA: &str = "jsongrep-query-bin";
+/// Path of the `Filter` method.
+pub const FILTER_PATH: &str = "/jsongrep.Filter/Filter";
+
+/// A json.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Record {
+    #[prost(string, tag = "1")]
+    pub json: String,
+}
+
+/// Callback invoked per record with the outcome and the time to evaluate it.
+pub trait Observer: Send + Sync {
+    /// `outcome` is `Ok(true)` if the record meets the condition.
+    fn observe(&self, outcome: Result<bool, &Error>, elapsed: Duration);
+}
+
+/// Server of the `jsongrep.Filter` service.
+#[derive(Clone, Default)]
+pub struct FilterServer {
+    observer: Option<Arc<dyn Observer>>,
+}
+
+impl fmt::Debug for FilterServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FilterServer")
+    }
+}
+
+impl NamedService for FilterServer {
+    const NAME: &'static str = "jsongrep.Filter";
+}
+
+/// Read the query from the metadata of `req`.
+fn query<T>(req: &Request<T>) -> Result<select::Query, Status> {
+    let m = req.metadata();
+    let raw = match (m.get_bin(QUERY_BIN_METADATA), m.get(QUERY_METADATA)) {
+        (Some(x), _) => {
+            let b = x
+                .to_bytes()
+                .map_err(|_| Status::invalid_argument("invalid query metadata"))?;
+            String::from_utf8(b.to_vec())
+                .map_err(|_| Status::invalid_argument("query is not utf-8"))?
+        }
+        (None, Some(x)) => x
+            .to_str()
+            .map_err(|_| Status::invalid_argument("invalid query metadata"))?
+            .to_owned(),
+        (None, None) => {
+            return Err(Status::invalid_argument(format!(
+                "{} is required",
+                QUERY_METADATA
+            )))
+        }
+    };
+    let q = raw_query::Query::try_from(&raw as &str)
+        .and_then(Query::try_from)
+        .map_err(|x| Status::invalid_argument(x.to_string()))?;
+    Ok(select::Query::new(Box::new(q.to_closure())))
+}
+
+impl FilterServer {
+    /// Set the callback invoked per record.
+    pub fn with_observer(mut self, observer: Arc<dyn Observer>) -> FilterServer {
+        self.observer = Some(observer);
+        self
+    }
+    fn filter(
+        &self,
+        req: Request<Streaming<Record>>,
+    ) -> Result<Response<BoxStream<Record>>, Status> {
+        let selector = query(&req)?;
+        let observer = self.observer.clone();
+        let stream = req.into_inner().filter_map(move |x| {
+            future::ready(match x {
+                Ok(r) => {
+                    let start = Instant::now();
+                    let outcome = selector.matches(&r.json);
+                    if let Some(o) = observer.as_ref() {
+                        o.observe(outcome.as_ref().copied(), start.elapsed());
+                    }
+                    match outcome {
+                        Ok(true) => Some(Ok(r)),
+                        _ => None,
+                    }
+                }
+                Err(e) => Some(Err(e)),
+            })
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+struct FilterSvc(FilterServer);
+
+impl StreamingService<Record> for FilterSvc {
+    type Response = Record;
+    type ResponseStream = BoxStream<Record>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+    fn call(&mut self, req: Request<Streaming<Record>>) -> Self::Future {
+        Box::pin(future::ready(self.0.filter(req)))
+    }
+}
+
+impl<B> Service<http::Request<B>> for FilterServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        if req.uri().path() != FILTER_PATH {
+            return Box::pin(future::ready(Ok(http::Response::builder()
+                .status(200)
+                .header("grpc-status", Code::Unimplemented as i32)
+                .header(http::header::CONTENT_TYPE, "application/grpc")
+                .body(empty_body())
+                .unwrap())));
+        }
+        let svc = FilterSvc(self.clone());
+        Box::pin(async move {
+            let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+            Ok(grpc.streaming(svc, req).await)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use futures::stream;
+    use tonic::codec::ProstCodec;
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::{Channel, Server};
+
+    async fn call(
+        server: FilterServer,
+        query: Option<&str>,
+        jsons: &[&str],
+    ) -> Result<Vec<String>, Status> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(server)
+                .serve_with_incoming(incoming),
+        );
+        let channel = Channel::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await.unwrap();
+        let records: Vec<_> = jsons
+            .iter()
+            .map(|x| Record {
+                json: x.to_string(),
+            })
+            .collect();
+        let mut req = Request::new(stream::iter(records));
+        if let Some(q) = query {
+            req.metadata_mut()
+                .insert(QUERY_METADATA, q.parse().unwrap());
+        }
+        let resp = client
+            .streaming::<_, Record, Record, _>(
+                req,
+                http::uri::PathAndQuery::from_static(FILTER_PATH),
+                ProstCodec::default(),
+            )
+            .await?;
+        let got: Vec<_> = resp.into_inner().collect().await;
+        got.into_iter().map(|x| x.map(|r| r.json)).collect()
+    }
+
+    #[tokio::test]
+    async fn filter() {
+        let got = call(
+            FilterServer::default(),
+            Some(r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}"#),
+            &[r#"{"i":1}"#, r#"{"i":2}"#, "not json", r#"{"i":3}"#],
+        )
+        .await
+        .unwrap();
+        assert_eq!(vec![r#"{"i":2}"#, r#"{"i":3}"#], got);
+    }
+
+    #[derive(Default)]
+    struct Counter(std::sync::Mutex<Vec<Result<bool, ErrorKind>>>);
+
+    impl Observer for Counter {
+        fn observe(&self, outcome: Result<bool, &Error>, _: Duration) {
+            self.0.lock().unwrap().push(outcome.map_err(|x| x.kind()));
+        }
+    }
+
+    #[tokio::test]
+    async fn filter_observed() {
+        let counter = Arc::new(Counter::default());
+        let server = FilterServer::default().with_observer(counter.clone());
+        call(
+            server,
+            Some(r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}"#),
+            &[r#"{"i":1}"#, r#"{"i":2}"#, "not json"],
+        )
+        .await
+        .unwrap();
+        let got = counter.0.lock().unwrap().clone();
+        assert_eq!(vec![Ok(false), Ok(true), Err(ErrorKind::Json)], got);
+    }
+
+    #[tokio::test]
+    async fn filter_without_query() {
+        let e = call(FilterServer::default(), None, &[r#"{"i":1}"#])
+            .await
+            .unwrap_err();
+        assert_eq!(Code::InvalidArgument, e.code());
+    }
+}
@@ -1,9 +1,35 @@
+//! Grep json.
+//!
+//! [`prelude`] re-exports the stable surface of the crate,
+//! the other modules expose the details which may change between minor versions.
+//! The modules and the items hidden from the docs are the helpers of the `jsongrep` command,
+//! not a part of the API.
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "sort")]
 mod compare;
 pub mod error;
 mod eval;
+#[doc(hidden)]
+pub mod expr;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod matcher;
+pub mod pointer;
+pub mod prelude;
 pub mod query;
 pub mod raw_query;
+#[cfg(feature = "sort")]
 pub mod raw_sort;
 pub mod select;
+#[cfg(feature = "sort")]
 pub mod sort;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[doc(hidden)]
+pub mod timestamp;
 mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,157 @@
+//! String matchers of [`Condition::Match`](crate::query::Condition::Match).
+//!
+//! Users can plug in their own matchers by implementing [`Matcher`],
+//! registering them to [`MatcherRegistry`] and compiling the query by [`Query::compile`].
+//!
+//! ```
+//! # use jsongrep::error::Result;
+//! # use jsongrep::matcher::{Matcher, MatcherRegistry};
+//! # use jsongrep::query::Query;
+//! # use jsongrep::raw_query as raw;
+//! # use jsongrep::select;
+//! # use std::convert::TryFrom;
+//!
+//! /// Match if a given value starts with the pattern.
+//! struct Prefix;
+//! impl Matcher for Prefix {
+//!     fn test(&self, pattern: &str, value: &str) -> Result<bool> {
+//!         Ok(value.starts_with(pattern))
+//!     }
+//! }
+//!
+//! let mut registry = MatcherRegistry::new();
+//! registry.register("prefix", Prefix);
+//! let raw = raw::Query::try_from(
+//!     r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","value":{"type":"string","value":"si"},"mtype":"prefix"}}}}"#,
+//! ).unwrap();
+//! let sel = select::Query::new(Box::new(Query::compile(raw, &registry).unwrap()));
+//! assert!(sel.matches(r#"{"s":"sirius"}"#).unwrap());
+//! assert!(!sel.matches(r#"{"s":"vesirius"}"#).unwrap());
+//! ```
+//!
+//! [`Query::compile`]: crate::query::Query::compile
+use crate::error;
+#[cfg(feature = "regex")]
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Custom string matcher, referred by [`MatchType::Custom`](crate::query::MatchType::Custom).
+pub trait Matcher: Send + Sync {
+    /// Return `true` if `value` matched with `pattern`,
+    /// the value of [`Condition::Match`](crate::query::Condition::Match).
+    fn test(&self, pattern: &str, value: &str) -> error::Result<bool>;
+}
+
+/// Custom matchers keyed by their names.
+#[derive(Default, Clone)]
+pub struct MatcherRegistry {
+    matchers: HashMap<String, Arc<dyn Matcher>>,
+}
+
+impl MatcherRegistry {
+    /// Return a new empty registry.
+    pub fn new() -> MatcherRegistry {
+        MatcherRegistry::default()
+    }
+    /// Register `matcher` as `name`, replacing the one already registered.
+    pub fn register(&mut self, name: impl Into<String>, matcher: impl Matcher + 'static) {
+        self.matchers.insert(name.into(), Arc::new(matcher));
+    }
+    /// Return the matcher registered as `name`.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Matcher>> {
+        self.matchers.get(name).cloned()
+    }
+}
+
+impl fmt::Debug for MatcherRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.matchers.keys()).finish()
+    }
+}
+
+#[cfg(feature = "regex")]
+/// Compile the regular expression.
+/// The compiled one is owned by the query, so no cache is needed.
+pub(crate) fn compile(pattern: &str) -> error::Result<Regex> {
+    Regex::new(pattern)
+        .map_err(|_| error::Error::new(error::ErrorCode::InvalidRegex(pattern.to_owned())))
+}
+
+/// Select string matched with specified pattern by the built-in matchers.
+pub(crate) enum Builtin<'a> {
+    Raw(&'a str),
+    #[cfg(feature = "regex")]
+    Regex(&'a Regex),
+}
+
+impl Builtin<'_> {
+    /// Return `true` if `value` matched with specified pattern.
+    pub(crate) fn test(&self, value: &str) -> error::Result<bool> {
+        match self {
+            Builtin::Raw(l) => Ok(value.contains(l)),
+            #[cfg(feature = "regex")]
+            Builtin::Regex(l) => Ok(l.is_match(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test_raw {
+        ($name:ident, $pattern:expr, $value:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let got = Builtin::Raw($pattern).test($value).unwrap();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_raw!(raw_eq, "dwarf", "dwarf", true);
+    test_raw!(raw_not, "dwarf", "giant", false);
+    test_raw!(raw_contain, "dwarf", "white dwarf", true);
+
+    macro_rules! test_regex {
+        ($name:ident, $pattern:expr, $value:expr, $want:expr) => {
+            #[cfg(feature = "regex")]
+            #[test]
+            fn $name() {
+                let got = Builtin::Regex(&compile($pattern).unwrap())
+                    .test($value)
+                    .unwrap();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_regex!(regex_eq, "dwarf", "dwarf", true);
+    test_regex!(regex_match1, r"s.*e", "slice", true);
+    test_regex!(regex_match2, r"s.*e", "slice ice", true);
+    test_regex!(regex_not, r"^dwarf", "brown dwarf", false);
+
+    struct Suffix;
+    impl Matcher for Suffix {
+        fn test(&self, pattern: &str, value: &str) -> error::Result<bool> {
+            Ok(value.ends_with(pattern))
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let mut r = MatcherRegistry::new();
+        r.register("suffix", Suffix);
+        assert!(r.get("suffix").unwrap().test("arf", "dwarf").unwrap());
+        assert!(r.get("prefix").is_none());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    #[should_panic]
+    fn regex_invalid() {
+        compile("(").unwrap();
+    }
+}
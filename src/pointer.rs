@@ -0,0 +1,238 @@
+//! JSON pointer parsed into the tokens.
+use crate::error::{Error, ErrorCode, Result};
+use serde_json::value::Value;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// A reference token of [`Pointer`], unescaped.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Token {
+    key: String,
+    /// `key` as an array index.
+    index: Option<usize>,
+}
+
+impl Token {
+    fn new(key: String) -> Token {
+        let index = parse_index(&key);
+        Token { key, index }
+    }
+    /// Return the token as an object key.
+    pub fn as_str(&self) -> &str {
+        &self.key
+    }
+    /// Return the token as an array index, `None` if not a valid index.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+}
+
+/// Parse an array index of JSON pointer.
+fn parse_index(s: &str) -> Option<usize> {
+    if s.starts_with('+') || (s.starts_with('0') && s.len() != 1) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// JSON pointer (RFC 6901), parsed once and used for every json.
+///
+/// ```
+/// # use jsongrep::pointer::Pointer;
+/// # use serde_json::json;
+///
+/// let p = Pointer::parse("/a/1/b~1c").unwrap();
+/// assert_eq!(vec!["a", "1", "b/c"], p.tokens().iter().map(|x| x.as_str()).collect::<Vec<_>>());
+/// assert_eq!(Some(&json!(2)), p.get(&json!({"a":[0,{"b/c":2}]})));
+/// assert!(Pointer::parse("a").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pointer {
+    raw: String,
+    /// `None` if `raw` is malformed, then the pointer points nothing.
+    tokens: Option<Vec<Token>>,
+}
+
+impl Pointer {
+    /// Parse the pointer.
+    /// Return `Err` if `s` is neither empty nor starts with `/`.
+    pub fn parse(s: &str) -> Result<Pointer> {
+        let p = Self::lenient(s);
+        if p.tokens.is_none() {
+            return Err(Error::new(ErrorCode::MalformedPointer(p.raw)));
+        }
+        Ok(p)
+    }
+    /// Parse the pointer, a malformed one points nothing.
+    pub(crate) fn lenient(s: &str) -> Pointer {
+        let tokens = if s.is_empty() {
+            Some(Vec::new())
+        } else if s.starts_with('/') {
+            Some(
+                s.split('/')
+                    .skip(1)
+                    .map(|x| Token::new(x.replace("~1", "/").replace("~0", "~")))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        Pointer {
+            raw: s.to_owned(),
+            tokens,
+        }
+    }
+    /// Return the tokens, empty if the pointer refers the whole json or is malformed.
+    pub fn tokens(&self) -> &[Token] {
+        self.tokens.as_deref().unwrap_or(&[])
+    }
+    /// Return `false` if the pointer points nothing.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.tokens.is_some()
+    }
+    /// Return the pointer as written.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+    /// Return the value at the pointer, same as [`Value::pointer`].
+    pub fn get<'a>(&self, v: &'a Value) -> Option<&'a Value> {
+        self.tokens
+            .as_ref()?
+            .iter()
+            .try_fold(v, |target, t| match target {
+                Value::Object(x) => x.get(t.as_str()),
+                Value::Array(x) => t.index().and_then(|i| x.get(i)),
+                _ => None,
+            })
+    }
+}
+
+/// Escape `key` as a reference token, `~` into `~0` and `/` into `~1`.
+#[doc(hidden)]
+pub fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Return all the values in `v` with their pointers in document order, starting from the root.
+///
+/// ```
+/// # use jsongrep::pointer;
+/// # use serde_json::json;
+///
+/// let v = json!({"a":[1,{"b/c":2}]});
+/// let got: Vec<_> = pointer::walk(&v).into_iter().map(|(p, _)| p.to_string()).collect();
+/// assert_eq!(vec!["", "/a", "/a/0", "/a/1", "/a/1/b~1c"], got);
+/// ```
+#[doc(hidden)]
+pub fn walk(v: &Value) -> Vec<(Pointer, &Value)> {
+    fn go<'a>(
+        v: &'a Value,
+        raw: &mut String,
+        tokens: &mut Vec<Token>,
+        acc: &mut Vec<(Pointer, &'a Value)>,
+    ) {
+        acc.push((
+            Pointer {
+                raw: raw.clone(),
+                tokens: Some(tokens.clone()),
+            },
+            v,
+        ));
+        let children: Box<dyn Iterator<Item = (String, &Value)>> = match v {
+            Value::Object(x) => Box::new(x.iter().map(|(k, v)| (k.clone(), v))),
+            Value::Array(x) => Box::new(x.iter().enumerate().map(|(i, v)| (i.to_string(), v))),
+            _ => return,
+        };
+        for (key, child) in children {
+            let len = raw.len();
+            raw.push('/');
+            raw.push_str(&escape(&key));
+            tokens.push(Token::new(key));
+            go(child, raw, tokens, acc);
+            tokens.pop();
+            raw.truncate(len);
+        }
+    }
+    let mut acc = Vec::new();
+    go(v, &mut String::new(), &mut Vec::new(), &mut acc);
+    acc
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl FromStr for Pointer {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<&str> for Pointer {
+    type Error = Error;
+    fn try_from(v: &str) -> Result<Self> {
+        Self::parse(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str;
+
+    const SAMPLE: &str = r#"
+{
+    "n": null,
+    "": {"": 0},
+    "d": {
+        "i": 1,
+        "a": [
+            "one",
+            {"t/w~o": 2}
+        ]
+    }
+}"#;
+
+    macro_rules! test_get {
+        ($name:ident, $pointer:literal) => {
+            #[test]
+            fn $name() {
+                let v: Value = from_str(SAMPLE).unwrap();
+                let got = Pointer::lenient($pointer).get(&v);
+                assert_eq!(v.pointer($pointer), got);
+            }
+        };
+    }
+
+    test_get!(get_root, "");
+    test_get!(get_null, "/n");
+    test_get!(get_empty_key, "//");
+    test_get!(get_nested, "/d/i");
+    test_get!(get_index, "/d/a/0");
+    test_get!(get_escaped, "/d/a/1/t~1w~0o");
+    test_get!(get_out_of_bounds, "/d/a/2");
+    test_get!(get_leading_zero, "/d/a/01");
+    test_get!(get_plus, "/d/a/+1");
+    test_get!(get_not_found, "/x");
+    test_get!(get_scalar, "/d/i/0");
+    test_get!(get_no_slash, "d");
+
+    #[test]
+    fn walk_round_trip() {
+        let v: Value = from_str(SAMPLE).unwrap();
+        for (p, x) in walk(&v) {
+            assert_eq!(Some(x), v.pointer(p.as_str()), "{}", p);
+            assert_eq!(Pointer::parse(p.as_str()).unwrap(), p);
+        }
+    }
+
+    #[test]
+    fn parse_malformed() {
+        let got = Pointer::parse("d/i").err().unwrap();
+        assert_eq!(crate::error::ErrorKind::MalformedPointer, got.kind());
+    }
+}
@@ -0,0 +1,29 @@
+//! Stable surface of the crate.
+//!
+//! The small set to build a query, select the jsons by it and sort them.
+//! The items re-exported here follow semver: they are not removed or changed incompatibly
+//! without a major version bump, however the modules they live in may be re-architected.
+//! The items only reachable from the modules, e.g. the variants of [`raw_query`](crate::raw_query),
+//! may change in minor versions.
+//!
+//! ```
+//! use jsongrep::prelude::*;
+//!
+//! let q = Query::builder().pointer("/i").gt(1).build().unwrap();
+//! let sel = Selector::new(Box::new(q));
+//! let got: Vec<_> = vec![r#"{"i":1}"#, r#"{"i":2}"#]
+//!     .into_iter()
+//!     .filter_selected(&sel)
+//!     .collect::<Result<_>>()
+//!     .unwrap();
+//! assert_eq!(vec![serde_json::json!({"i":2})], got);
+//! ```
+pub use crate::error::{Error, Result};
+pub use crate::query::builder::{Cond, QueryBuilder};
+pub use crate::query::{EvaluableQuery, Query};
+pub use crate::raw_query::MismatchPolicy;
+#[cfg(feature = "sort")]
+pub use crate::raw_sort::Order;
+pub use crate::select::{Query as Selector, Record, SelectExt};
+#[cfg(feature = "sort")]
+pub use crate::sort::Sort;
@@ -1,37 +1,131 @@
-use crate::error::Result;
+use crate::error::{Error, ErrorCode, Result};
+#[cfg(feature = "regex")]
+use crate::matcher;
+use crate::matcher::{Matcher, MatcherRegistry};
+use crate::pointer::Pointer;
 use crate::raw_query as raw;
-use serde_json::value::Value as JSONValue;
+use crate::util;
+#[cfg(feature = "regex")]
+use regex::{Regex, RegexSet};
+use serde::{Serialize, Serializer};
+use serde_json::from_str;
+use serde_json::value::{Map, RawValue, Value as JSONValue};
 use std::cmp;
 use std::convert;
 use std::fmt;
+use std::sync::Arc;
 use std::vec;
 
-pub trait EvaluableQuery {
+pub mod builder;
+pub mod closure;
+mod optimize;
+
+pub trait EvaluableQuery: Send + Sync {
     fn eval(&self, value: &JSONValue) -> Result<bool>;
+    /// Same as [`EvaluableQuery::eval`] but for the unparsed json.
+    ///
+    /// By default, parse the whole json.
+    fn eval_raw(&self, value: &RawValue) -> Result<bool> {
+        let v = from_str(value.get()).map_err(|x| Error::new(ErrorCode::Json(x)))?;
+        self.eval(&v)
+    }
 }
 
 /// JSON filter.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Query {
-    pub(crate) query: Box<dyn EvaluableQueryCondition>,
+    pub(crate) query: QueryCondition,
+}
+
+impl Query {
+    /// Return a new [`builder::QueryBuilder`].
+    pub fn builder() -> builder::QueryBuilder {
+        builder::QueryBuilder::new()
+    }
+    /// Return the pointers of the pairs in order of appearance.
+    pub fn pointers(&self) -> vec::Vec<&Pointer> {
+        let mut r = vec::Vec::new();
+        self.query.collect_pointers(&mut r);
+        r
+    }
+    /// Return the pointers and the values that every json meeting the query has,
+    /// the `eq` pairs of the scalars not under `not` nor `or`.
+    ///
+    /// e.g. to look up an index instead of evaluating all the jsons.
+    pub fn required_equals(&self) -> vec::Vec<(&Pointer, &Value)> {
+        let mut r = vec::Vec::new();
+        self.query.collect_required_equals(&mut r);
+        r
+    }
+    /// Return the pointers of the pairs that accept the value, without duplicates.
+    ///
+    /// e.g. to tell why the value meets the query.
+    pub fn matched_pointers(&self, value: &JSONValue) -> vec::Vec<&Pointer> {
+        let mut r = vec::Vec::new();
+        self.query.collect_matched(value, &mut r);
+        let mut seen = std::collections::HashSet::new();
+        r.retain(|p| seen.insert(p.as_str()));
+        r
+    }
+    /// Compile the query into [`closure::ClosureQuery`] to evaluate many jsons.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    pub fn to_closure(&self) -> closure::ClosureQuery {
+        closure::ClosureQuery::from(self)
+    }
+    /// Compile the raw query, resolving [`MatchType::Custom`] by `registry`.
+    /// Return `Err` if the query contains an invalid regular expression or an unknown matcher.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err(level = "debug"))
+    )]
+    pub fn compile(v: raw::Query, registry: &MatcherRegistry) -> Result<Self> {
+        let mut q = v.query;
+        if let Some(x) = v.on_mismatch {
+            q.set_default_on_mismatch(x);
+        }
+        let q = Query {
+            query: QueryCondition::compile(q, registry)?.optimize(),
+        };
+        util::event!(debug, pairs = q.pointers().len(), "compiled query");
+        Ok(q)
+    }
 }
 
-impl convert::From<raw::Query> for Query {
-    fn from(v: raw::Query) -> Self {
-        Query {
-            query: Box::new(QueryCondition::from(v.query)),
+/// Compile the raw query without custom matchers.
+/// Return `Err` if the query contains an invalid regular expression or a custom matcher.
+impl convert::TryFrom<raw::Query> for Query {
+    type Error = Error;
+    fn try_from(v: raw::Query) -> Result<Self> {
+        Self::compile(v, &MatcherRegistry::new())
+    }
+}
+
+impl convert::From<&Query> for raw::Query {
+    fn from(v: &Query) -> Self {
+        raw::Query {
+            query: raw::QueryCondition::from(&v.query),
+            on_mismatch: None,
         }
     }
 }
 
+/// Serialize as [`raw::Query`].
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        raw::Query::from(self).serialize(serializer)
+    }
+}
+
 /// Evaluate the query condition.
-pub trait EvaluableQueryCondition {
+pub trait EvaluableQueryCondition: Send + Sync {
     /// Report whether a given json value satifies the condition.
     fn eval(&self, value: &JSONValue) -> Result<bool>;
 }
 
 /// A top level element of [`Query`].
+#[derive(Debug, Clone, PartialEq)]
 pub enum QueryCondition {
-    Raw(Box<dyn EvaluableQueryPair>),
+    Raw(QueryPair),
     /// Match if a given condition denies a given value.
     Not(Box<QueryCondition>),
     /// Match if the all given conditions accept a given value.
@@ -40,47 +134,219 @@ pub enum QueryCondition {
     Or(vec::Vec<QueryCondition>),
 }
 
-impl convert::From<raw::QueryCondition> for QueryCondition {
-    fn from(v: raw::QueryCondition) -> Self {
-        match v {
+impl QueryCondition {
+    pub(crate) fn compile(v: raw::QueryCondition, registry: &MatcherRegistry) -> Result<Self> {
+        let compile_all = |pair: vec::Vec<raw::QueryCondition>| {
+            pair.into_iter()
+                .map(|x| Self::compile(x, registry))
+                .collect::<Result<_>>()
+        };
+        Ok(match v {
             raw::QueryCondition::Raw { pair } => {
-                QueryCondition::Raw(Box::new(QueryPair::from(pair)))
+                QueryCondition::Raw(QueryPair::compile(pair, registry)?)
             }
-            raw::QueryCondition::Not { pair } => QueryCondition::Not(Box::new(Self::from(*pair))),
-            raw::QueryCondition::And { pair } => {
-                QueryCondition::And(pair.into_iter().map(Self::from).collect())
+            raw::QueryCondition::Not { pair } => {
+                QueryCondition::Not(Box::new(Self::compile(*pair, registry)?))
             }
-            raw::QueryCondition::Or { pair } => {
-                QueryCondition::Or(pair.into_iter().map(Self::from).collect())
+            raw::QueryCondition::And { pair } => QueryCondition::And(compile_all(pair)?),
+            raw::QueryCondition::Or { pair } => QueryCondition::Or(compile_all(pair)?),
+            raw::QueryCondition::Exists { pointer } => QueryCondition::Raw(QueryPair {
+                pointer: Pointer::parse(&pointer)?,
+                condition: Condition::Exists,
+                on_mismatch: MismatchPolicy::Error,
+                coerce: false,
+                trim: false,
+                collapse_ws: false,
+            }),
+            raw::QueryCondition::AnyOf {
+                pointers,
+                condition,
+                on_mismatch,
+            } => QueryCondition::Or(Self::compile_each(
+                pointers,
+                condition,
+                on_mismatch,
+                registry,
+            )?),
+            raw::QueryCondition::AllOf {
+                pointers,
+                condition,
+                on_mismatch,
+            } => QueryCondition::And(Self::compile_each(
+                pointers,
+                condition,
+                on_mismatch,
+                registry,
+            )?),
+        })
+    }
+    /// Compile the pairs of each of `pointers` and `condition`,
+    /// requiring the values to exist so that the missing ones do not match.
+    fn compile_each(
+        pointers: vec::Vec<String>,
+        condition: raw::Condition,
+        on_mismatch: Option<raw::MismatchPolicy>,
+        registry: &MatcherRegistry,
+    ) -> Result<vec::Vec<Self>> {
+        pointers
+            .into_iter()
+            .map(|pointer| {
+                let pair = raw::QueryPair {
+                    pointer,
+                    condition: raw::Condition::And {
+                        value: vec![raw::Condition::Exists, condition.clone()],
+                    },
+                    on_mismatch,
+                    coerce: false,
+                    trim: false,
+                    collapse_ws: false,
+                };
+                QueryPair::compile(pair, registry).map(QueryCondition::Raw)
+            })
+            .collect()
+    }
+}
+
+impl QueryCondition {
+    fn collect_required_equals<'a>(&'a self, r: &mut vec::Vec<(&'a Pointer, &'a Value)>) {
+        match self {
+            QueryCondition::Raw(QueryPair {
+                pointer,
+                condition: Condition::Equal(v),
+                coerce: false,
+                trim: false,
+                collapse_ws: false,
+                ..
+            }) if !matches!(v, Value::Array(_) | Value::Object(_)) => r.push((pointer, v)),
+            QueryCondition::And(x) => x.iter().for_each(|c| c.collect_required_equals(r)),
+            _ => (),
+        }
+    }
+    fn collect_pointers<'a>(&'a self, r: &mut vec::Vec<&'a Pointer>) {
+        match self {
+            QueryCondition::Raw(x) => r.push(&x.pointer),
+            QueryCondition::Not(x) => x.collect_pointers(r),
+            QueryCondition::And(x) | QueryCondition::Or(x) => {
+                x.iter().for_each(|c| c.collect_pointers(r))
             }
         }
     }
 }
 
+impl convert::TryFrom<raw::QueryCondition> for QueryCondition {
+    type Error = Error;
+    fn try_from(v: raw::QueryCondition) -> Result<Self> {
+        Self::compile(v, &MatcherRegistry::new())
+    }
+}
+
+impl convert::From<&QueryCondition> for raw::QueryCondition {
+    fn from(v: &QueryCondition) -> Self {
+        match v {
+            QueryCondition::Raw(x) => raw::QueryCondition::Raw {
+                pair: raw::QueryPair::from(x),
+            },
+            QueryCondition::Not(x) => raw::QueryCondition::Not {
+                pair: Box::new(Self::from(x.as_ref())),
+            },
+            QueryCondition::And(x) => raw::QueryCondition::And {
+                pair: x.iter().map(Self::from).collect(),
+            },
+            QueryCondition::Or(x) => raw::QueryCondition::Or {
+                pair: x.iter().map(Self::from).collect(),
+            },
+        }
+    }
+}
+
 /// Evaluate the pair.
-pub trait EvaluableQueryPair {
+pub trait EvaluableQueryPair: Send + Sync {
     /// Report whether a given json value satifies the pair.
     fn eval(&self, value: &JSONValue) -> Result<bool>;
 }
 
 /// Query target and condition.
+#[derive(Debug, Clone, PartialEq)]
 pub struct QueryPair {
     /// JSON pointer, Location of data to be tested by `condition`.
-    pub(crate) pointer: String,
-    pub(crate) condition: Box<dyn EvaluableCondition>,
+    pub(crate) pointer: Pointer,
+    pub(crate) condition: Condition,
+    pub(crate) on_mismatch: MismatchPolicy,
+    /// Read the numeric strings as numbers.
+    pub(crate) coerce: bool,
+    /// Trim the string.
+    pub(crate) trim: bool,
+    /// Collapse the whitespace of the string.
+    pub(crate) collapse_ws: bool,
+}
+
+impl QueryPair {
+    pub(crate) fn compile(v: raw::QueryPair, registry: &MatcherRegistry) -> Result<Self> {
+        Ok(QueryPair {
+            pointer: Pointer::parse(&v.pointer)?,
+            condition: Condition::compile(v.condition, registry)?,
+            on_mismatch: v
+                .on_mismatch
+                .map_or(MismatchPolicy::Error, MismatchPolicy::from),
+            coerce: v.coerce,
+            trim: v.trim,
+            collapse_ws: v.collapse_ws,
+        })
+    }
+}
+
+impl convert::TryFrom<raw::QueryPair> for QueryPair {
+    type Error = Error;
+    fn try_from(v: raw::QueryPair) -> Result<Self> {
+        Self::compile(v, &MatcherRegistry::new())
+    }
+}
+
+impl convert::From<&QueryPair> for raw::QueryPair {
+    fn from(v: &QueryPair) -> Self {
+        raw::QueryPair {
+            pointer: v.pointer.to_string(),
+            condition: raw::Condition::from(&v.condition),
+            on_mismatch: match v.on_mismatch {
+                MismatchPolicy::Error => None,
+                x => Some(raw::MismatchPolicy::from(x)),
+            },
+            coerce: v.coerce,
+            trim: v.trim,
+            collapse_ws: v.collapse_ws,
+        }
+    }
 }
 
-impl convert::From<raw::QueryPair> for QueryPair {
-    fn from(v: raw::QueryPair) -> Self {
-        QueryPair {
-            pointer: v.pointer,
-            condition: Box::new(Condition::from(v.condition)),
+/// How to evaluate a condition against a value of the different type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MismatchPolicy {
+    /// Type mismatch is an error.
+    Error,
+    /// Type mismatch is evaluated as `false`.
+    False,
+}
+
+impl convert::From<raw::MismatchPolicy> for MismatchPolicy {
+    fn from(v: raw::MismatchPolicy) -> Self {
+        match v {
+            raw::MismatchPolicy::Error => MismatchPolicy::Error,
+            raw::MismatchPolicy::False => MismatchPolicy::False,
+        }
+    }
+}
+
+impl convert::From<MismatchPolicy> for raw::MismatchPolicy {
+    fn from(v: MismatchPolicy) -> Self {
+        match v {
+            MismatchPolicy::Error => raw::MismatchPolicy::Error,
+            MismatchPolicy::False => raw::MismatchPolicy::False,
         }
     }
 }
 
 /// Target value of [`Condition`].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
     /// JSON null.
     Null,
@@ -92,6 +358,39 @@ pub enum Value {
     Float(f64),
     /// JSON string.
     String(String),
+    /// JSON array, boxed to keep the scalars small.
+    Array(Box<vec::Vec<JSONValue>>),
+    /// JSON object, boxed to keep the scalars small.
+    Object(Box<Map<String, JSONValue>>),
+}
+
+impl Value {
+    /// Return the value of the scalar json, `None` if `v` is an array or an object.
+    ///
+    /// Use [`From<&JSONValue>`](#impl-From%3C%26Value%3E-for-Value) to convert any json.
+    pub fn from_json(v: &JSONValue) -> Option<Value> {
+        match v {
+            JSONValue::Null => Some(Value::Null),
+            JSONValue::Bool(x) => Some(Value::Bool(*x)),
+            JSONValue::Number(x) => Some(if x.is_i64() {
+                Value::Int(x.as_i64().unwrap() as i32)
+            } else {
+                Value::Float(x.as_f64().unwrap())
+            }),
+            JSONValue::String(x) => Some(Value::String(x.clone())),
+            JSONValue::Array(_) | JSONValue::Object(_) => None,
+        }
+    }
+}
+
+impl convert::From<&JSONValue> for Value {
+    fn from(v: &JSONValue) -> Self {
+        match v {
+            JSONValue::Array(x) => Value::Array(Box::new(x.clone())),
+            JSONValue::Object(x) => Value::Object(Box::new(x.clone())),
+            x => Value::from_json(x).unwrap(),
+        }
+    }
 }
 
 impl cmp::PartialEq for Value {
@@ -102,6 +401,8 @@ impl cmp::PartialEq for Value {
             (Value::Int(x), Value::Int(y)) => x == y,
             (Value::Float(x), Value::Float(y)) => (x - y).abs() <= f64::EPSILON,
             (Value::String(x), Value::String(y)) => x == y,
+            (Value::Array(x), Value::Array(y)) => x == y,
+            (Value::Object(x), Value::Object(y)) => x == y,
             _ => false,
         }
     }
@@ -122,6 +423,25 @@ impl convert::From<raw::Value> for Value {
                 }
             }
             raw::Value::String { value } => Value::String(value),
+            raw::Value::Json { value } => Value::from(&value),
+        }
+    }
+}
+
+impl convert::From<&Value> for raw::Value {
+    fn from(v: &Value) -> Self {
+        match v {
+            Value::Null => raw::Value::Null,
+            Value::Bool(x) => raw::Value::Bool { value: *x },
+            Value::Int(x) => raw::Value::Number { value: *x as f64 },
+            Value::Float(x) => raw::Value::Number { value: *x },
+            Value::String(x) => raw::Value::String { value: x.clone() },
+            Value::Array(x) => raw::Value::Json {
+                value: JSONValue::Array(x.as_ref().clone()),
+            },
+            Value::Object(x) => raw::Value::Json {
+                value: JSONValue::Object(x.as_ref().clone()),
+            },
         }
     }
 }
@@ -134,18 +454,28 @@ impl fmt::Display for Value {
             Value::Int(x) => write!(f, "Int({})", x),
             Value::Float(x) => write!(f, "Float({})", x),
             Value::String(x) => write!(f, "String({})", x),
+            Value::Array(x) => write!(f, "Array({})", serde_json::to_string(x).unwrap()),
+            Value::Object(x) => write!(f, "Object({})", serde_json::to_string(x).unwrap()),
         }
     }
 }
 
 /// Evaluate the condition.
-pub trait EvaluableCondition {
+pub trait EvaluableCondition: Send + Sync {
     /// Report whether a given json value satifies the condition.
     fn eval(&self, value: &Value) -> Result<bool>;
+    /// Same as [`EvaluableCondition::eval`] but a type mismatch is evaluated as `false`,
+    /// even if negated.
+    fn eval_lenient(&self, value: &Value) -> Result<bool> {
+        match self.eval(value) {
+            Err(x) if x.is_type_mismatch() => Ok(false),
+            x => x,
+        }
+    }
 }
 
 /// Condition part of [`QueryPair`].
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Condition {
     /// Match if a given value is equal to `Value`.
     Equal(Value),
@@ -161,42 +491,859 @@ pub enum Condition {
     And(vec::Vec<Condition>),
     /// Match if at least one of the given conditions accepts a given value.
     Or(vec::Vec<Condition>),
+    /// Match if a given value matches at least one of the regular expressions.
+    /// Compiled from [`Condition::Or`] of the regular expression [`Condition::Match`]es.
+    #[cfg(feature = "regex")]
+    AnyRegex(AnyRegex),
+    /// Match if the pointer points a value.
+    Exists,
+    /// Match if a given value is of the type.
+    IsType(raw::JsonType),
+    /// Match if the length of a given string, array or object meets the condition.
+    Len(Box<Condition>),
+    /// Match if the number of the keys of a given object meets the condition.
+    NKeys(Box<Condition>),
+    /// Match if the domain of a given email address, in lowercase, meets the condition.
+    EmailDomain(Box<Condition>),
+    /// Match if a given array or object has no elements.
+    Empty,
+    /// Match if a given array or object has elements.
+    NotEmpty,
+    /// Match if a given timestamp is within the duration before now.
+    #[cfg(feature = "datetime")]
+    Within(Within),
+    /// Match if a given string is a URL whose components meet the conditions.
+    #[cfg(feature = "url")]
+    Url(Box<UrlCondition>),
 }
 
-impl convert::From<raw::Condition> for Condition {
-    fn from(v: raw::Condition) -> Self {
-        match v {
+/// Conditions of the components of [`Condition::Url`].
+#[cfg(feature = "url")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlCondition {
+    pub(crate) scheme: Option<Condition>,
+    pub(crate) host: Option<Condition>,
+    pub(crate) port: Option<Condition>,
+    pub(crate) path: Option<Condition>,
+    pub(crate) fragment: Option<Condition>,
+    pub(crate) query: vec::Vec<(String, Condition)>,
+}
+
+/// Time range of [`Condition::Within`].
+#[cfg(feature = "datetime")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Within {
+    pub(crate) duration: std::time::Duration,
+    /// The current time if `None`.
+    pub(crate) now: Option<std::time::SystemTime>,
+}
+
+/// Regular expressions scanned at once.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct AnyRegex(pub(crate) RegexSet);
+
+#[cfg(feature = "regex")]
+impl cmp::PartialEq for AnyRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.patterns() == other.0.patterns()
+    }
+}
+
+fn invalid_flags(flags: &str) -> Error {
+    Error::new(ErrorCode::InvalidRegex(format!("flags {:?}", flags)))
+}
+
+fn full_without_regex() -> Error {
+    Error::new(ErrorCode::InvalidRegex("full without regex".to_owned()))
+}
+
+impl Condition {
+    pub(crate) fn compile(v: raw::Condition, registry: &MatcherRegistry) -> Result<Self> {
+        let compile_all = |value: vec::Vec<raw::Condition>| {
+            value
+                .into_iter()
+                .map(|x| Self::compile(x, registry))
+                .collect::<Result<_>>()
+        };
+        Ok(match v {
             raw::Condition::Equal { value } => Condition::Equal(Value::from(value)),
             raw::Condition::GreaterThan { value } => Condition::GreaterThan(Value::from(value)),
             raw::Condition::LessThan { value } => Condition::LessThan(Value::from(value)),
-            raw::Condition::Match { value, mtype } => {
-                Condition::Match(Value::from(value), MatchType::from(mtype))
+            raw::Condition::Match {
+                value,
+                mtype,
+                flags,
+                full,
+            } => {
+                let value = Value::from(match (&mtype, value, flags) {
+                    (raw::MatchType::Regex, raw::Value::String { value }, flags) => {
+                        raw::Value::String {
+                            value: Self::regex_pattern(value, flags, full)?,
+                        }
+                    }
+                    (_, _, Some(x)) => return Err(invalid_flags(&x)),
+                    (_, _, None) if full => return Err(full_without_regex()),
+                    (_, x, None) => x,
+                });
+                let mtype = match (mtype, &value) {
+                    (raw::MatchType::Contain, _) => MatchType::Contain,
+                    #[cfg(feature = "regex")]
+                    (raw::MatchType::Regex, Value::String(x)) => {
+                        MatchType::Regex(matcher::compile(x)?)
+                    }
+                    #[cfg(feature = "regex")]
+                    (raw::MatchType::Regex, x) => {
+                        return Err(Error::new(ErrorCode::InvalidRegex(format!("{}", x))))
+                    }
+                    #[cfg(not(feature = "regex"))]
+                    (raw::MatchType::Regex, _) => {
+                        return Err(Error::new(ErrorCode::UnknownMatcher("regex".to_owned())))
+                    }
+                    (raw::MatchType::Custom(name), _) => match registry.get(&name) {
+                        Some(matcher) => MatchType::Custom(CustomMatcher { name, matcher }),
+                        None => return Err(Error::new(ErrorCode::UnknownMatcher(name))),
+                    },
+                };
+                Condition::Match(value, mtype)
+            }
+            raw::Condition::Not { value } => {
+                Condition::Not(Box::new(Self::compile(*value, registry)?))
+            }
+            raw::Condition::And { value } => Condition::And(compile_all(value)?),
+            raw::Condition::Exists => Condition::Exists,
+            raw::Condition::IsType { value } => Condition::IsType(value),
+            raw::Condition::Len { condition } => {
+                Condition::Len(Box::new(Self::compile(*condition, registry)?))
+            }
+            raw::Condition::NKeys { condition } => {
+                Condition::NKeys(Box::new(Self::compile(*condition, registry)?))
+            }
+            raw::Condition::EmailDomain { condition } => Condition::EmailDomain(Box::new(
+                Self::compile(Self::lowercase(*condition), registry)?,
+            )),
+            raw::Condition::Empty => Condition::Empty,
+            raw::Condition::NotEmpty => Condition::NotEmpty,
+            #[cfg(feature = "datetime")]
+            raw::Condition::Within { duration, now } => Condition::Within(Within {
+                duration: humantime::parse_duration(&duration)
+                    .map_err(|_| Error::new(ErrorCode::InvalidTime(duration)))?,
+                now: now
+                    .map(|x| {
+                        crate::timestamp::parse_system_time(&x)
+                            .ok_or_else(|| Error::new(ErrorCode::InvalidTime(x)))
+                    })
+                    .transpose()?,
+            }),
+            #[cfg(not(feature = "datetime"))]
+            raw::Condition::Within { .. } => {
+                return Err(Error::new(ErrorCode::UnknownMatcher("within".to_owned())))
+            }
+            #[cfg(feature = "url")]
+            raw::Condition::Url {
+                scheme,
+                host,
+                port,
+                path,
+                fragment,
+                query,
+            } => {
+                let compile_part = |x: Option<Box<raw::Condition>>| {
+                    x.map(|c| Self::compile(*c, registry)).transpose()
+                };
+                Condition::Url(Box::new(UrlCondition {
+                    scheme: compile_part(scheme)?,
+                    host: compile_part(host)?,
+                    port: compile_part(port)?,
+                    path: compile_part(path)?,
+                    fragment: compile_part(fragment)?,
+                    query: query
+                        .into_iter()
+                        .map(|(k, c)| Ok((k, Self::compile(c, registry)?)))
+                        .collect::<Result<_>>()?,
+                }))
             }
-            raw::Condition::Not { value } => Condition::Not(Box::new(Self::from(*value))),
-            raw::Condition::And { value } => {
-                Condition::And(value.into_iter().map(Self::from).collect())
+            #[cfg(not(feature = "url"))]
+            raw::Condition::Url { .. } => {
+                return Err(Error::new(ErrorCode::UnknownMatcher("url".to_owned())))
             }
-            raw::Condition::Or { value } => {
-                Condition::Or(value.into_iter().map(Self::from).collect())
+            #[cfg(feature = "regex")]
+            raw::Condition::Or { value } => Self::compile_or(value, registry)?,
+            #[cfg(not(feature = "regex"))]
+            raw::Condition::Or { value } => Condition::Or(compile_all(value)?),
+        })
+    }
+    /// Return the regular expression with the inline `flags`, e.g. `(?i)x`,
+    /// anchored to the whole string if `full`,
+    /// to keep them when the query is serialized again.
+    /// Lowercase the string literals of `c` and make its regular expressions case-insensitive,
+    /// to compare with the lowercased email domain.
+    fn lowercase(c: raw::Condition) -> raw::Condition {
+        let lower = |v| match v {
+            raw::Value::String { value } => raw::Value::String {
+                value: value.to_lowercase(),
+            },
+            x => x,
+        };
+        match c {
+            raw::Condition::Equal { value } => raw::Condition::Equal {
+                value: lower(value),
+            },
+            raw::Condition::GreaterThan { value } => raw::Condition::GreaterThan {
+                value: lower(value),
+            },
+            raw::Condition::LessThan { value } => raw::Condition::LessThan {
+                value: lower(value),
+            },
+            raw::Condition::Match {
+                value,
+                mtype: raw::MatchType::Contain,
+                flags,
+                full,
+            } => raw::Condition::Match {
+                value: lower(value),
+                mtype: raw::MatchType::Contain,
+                flags,
+                full,
+            },
+            raw::Condition::Match {
+                value,
+                mtype: raw::MatchType::Regex,
+                flags,
+                full,
+            } => raw::Condition::Match {
+                value,
+                mtype: raw::MatchType::Regex,
+                flags: Some(match flags {
+                    Some(x) if x.contains('i') => x,
+                    Some(x) => x + "i",
+                    None => "i".to_owned(),
+                }),
+                full,
+            },
+            raw::Condition::Not { value } => raw::Condition::Not {
+                value: Box::new(Self::lowercase(*value)),
+            },
+            raw::Condition::And { value } => raw::Condition::And {
+                value: value.into_iter().map(Self::lowercase).collect(),
+            },
+            raw::Condition::Or { value } => raw::Condition::Or {
+                value: value.into_iter().map(Self::lowercase).collect(),
+            },
+            x => x,
+        }
+    }
+    fn regex_pattern(pattern: String, flags: Option<String>, full: bool) -> Result<String> {
+        let pattern = if full {
+            format!(r"\A(?:{})\z", pattern)
+        } else {
+            pattern
+        };
+        match flags {
+            None => Ok(pattern),
+            Some(x) if x.is_empty() => Ok(pattern),
+            Some(x) if x.chars().all(|c| "imsx".contains(c)) => Ok(format!("(?{}){}", x, pattern)),
+            Some(x) => Err(invalid_flags(&x)),
+        }
+    }
+    /// Compile the children of `or`,
+    /// merging each run of the adjacent regular expressions into a single [`AnyRegex`]
+    /// not to change the order of the evaluation.
+    #[cfg(feature = "regex")]
+    fn compile_or(value: vec::Vec<raw::Condition>, registry: &MatcherRegistry) -> Result<Self> {
+        let is_regex = |x: &raw::Condition| {
+            matches!(
+                x,
+                raw::Condition::Match {
+                    value: raw::Value::String { .. },
+                    mtype: raw::MatchType::Regex,
+                    ..
+                }
+            )
+        };
+        let mut children = vec::Vec::new();
+        let mut run = vec::Vec::new();
+        for x in value {
+            if is_regex(&x) {
+                run.push(x);
+                continue;
+            }
+            children.extend(Self::compile_regex_run(std::mem::take(&mut run), registry)?);
+            children.push(Self::compile(x, registry)?);
+        }
+        children.extend(Self::compile_regex_run(run, registry)?);
+        if children.len() == 1 && matches!(children[0], Condition::AnyRegex(_)) {
+            return Ok(children.remove(0));
+        }
+        Ok(Condition::Or(children))
+    }
+    /// Compile the adjacent regular expressions, into [`AnyRegex`] if more than one.
+    #[cfg(feature = "regex")]
+    fn compile_regex_run(
+        run: vec::Vec<raw::Condition>,
+        registry: &MatcherRegistry,
+    ) -> Result<Option<Self>> {
+        if run.len() < 2 {
+            return run
+                .into_iter()
+                .next()
+                .map(|x| Self::compile(x, registry))
+                .transpose();
+        }
+        let mut patterns = vec::Vec::with_capacity(run.len());
+        for x in run {
+            if let raw::Condition::Match {
+                value: raw::Value::String { value },
+                flags,
+                full,
+                ..
+            } = x
+            {
+                patterns.push(Self::regex_pattern(value, flags, full)?);
+            }
+        }
+        match RegexSet::new(&patterns) {
+            Ok(x) => Ok(Some(Condition::AnyRegex(AnyRegex(x)))),
+            // report the first invalid one
+            Err(_) => Err(patterns
+                .iter()
+                .find_map(|x| matcher::compile(x).err())
+                .unwrap_or_else(Error::unreachable)),
+        }
+    }
+}
+
+impl convert::TryFrom<raw::Condition> for Condition {
+    type Error = Error;
+    fn try_from(v: raw::Condition) -> Result<Self> {
+        Self::compile(v, &MatcherRegistry::new())
+    }
+}
+
+impl convert::From<&Condition> for raw::Condition {
+    fn from(v: &Condition) -> Self {
+        match v {
+            Condition::Equal(x) => raw::Condition::Equal {
+                value: raw::Value::from(x),
+            },
+            Condition::GreaterThan(x) => raw::Condition::GreaterThan {
+                value: raw::Value::from(x),
+            },
+            Condition::LessThan(x) => raw::Condition::LessThan {
+                value: raw::Value::from(x),
+            },
+            Condition::Match(x, t) => raw::Condition::Match {
+                value: raw::Value::from(x),
+                mtype: raw::MatchType::from(t),
+                flags: None,
+                full: false,
+            },
+            Condition::Not(x) => raw::Condition::Not {
+                value: Box::new(Self::from(x.as_ref())),
+            },
+            Condition::And(x) => raw::Condition::And {
+                value: x.iter().map(Self::from).collect(),
+            },
+            Condition::Exists => raw::Condition::Exists,
+            Condition::IsType(x) => raw::Condition::IsType { value: *x },
+            Condition::Len(x) => raw::Condition::Len {
+                condition: Box::new(Self::from(x.as_ref())),
+            },
+            Condition::NKeys(x) => raw::Condition::NKeys {
+                condition: Box::new(Self::from(x.as_ref())),
+            },
+            Condition::EmailDomain(x) => raw::Condition::EmailDomain {
+                condition: Box::new(Self::from(x.as_ref())),
+            },
+            Condition::Empty => raw::Condition::Empty,
+            Condition::NotEmpty => raw::Condition::NotEmpty,
+            #[cfg(feature = "datetime")]
+            Condition::Within(x) => raw::Condition::Within {
+                duration: humantime::format_duration(x.duration).to_string(),
+                now: x.now.map(|t| humantime::format_rfc3339(t).to_string()),
+            },
+            #[cfg(feature = "url")]
+            Condition::Url(x) => {
+                let part = |c: &Option<Condition>| c.as_ref().map(|c| Box::new(Self::from(c)));
+                raw::Condition::Url {
+                    scheme: part(&x.scheme),
+                    host: part(&x.host),
+                    port: part(&x.port),
+                    path: part(&x.path),
+                    fragment: part(&x.fragment),
+                    query: x
+                        .query
+                        .iter()
+                        .map(|(k, c)| (k.clone(), Self::from(c)))
+                        .collect(),
+                }
             }
+            Condition::Or(x) => raw::Condition::Or {
+                value: x
+                    .iter()
+                    .flat_map(|x| match (x, Self::from(x)) {
+                        // flatten the merged regular expressions
+                        #[cfg(feature = "regex")]
+                        (Condition::AnyRegex(_), raw::Condition::Or { value }) => value,
+                        (_, y) => vec![y],
+                    })
+                    .collect(),
+            },
+            #[cfg(feature = "regex")]
+            Condition::AnyRegex(x) => raw::Condition::Or {
+                value: x
+                    .0
+                    .patterns()
+                    .iter()
+                    .map(|x| raw::Condition::Match {
+                        value: raw::Value::String { value: x.clone() },
+                        mtype: raw::MatchType::Regex,
+                        flags: None,
+                        full: false,
+                    })
+                    .collect(),
+            },
         }
     }
 }
 
 /// Condition of matching string.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MatchType {
     // Substring.
     Contain,
-    // Regular expression.
-    Regex,
+    // Regular expression, compiled from the value of [`Condition::Match`].
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+    // Matcher registered to [`MatcherRegistry`].
+    Custom(CustomMatcher),
 }
 
-impl convert::From<raw::MatchType> for MatchType {
-    fn from(v: raw::MatchType) -> Self {
+/// [`Matcher`] resolved from [`MatcherRegistry`] and its name.
+#[derive(Clone)]
+pub struct CustomMatcher {
+    pub(crate) name: String,
+    pub(crate) matcher: Arc<dyn Matcher>,
+}
+
+impl fmt::Debug for CustomMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("CustomMatcher").field(&self.name).finish()
+    }
+}
+
+impl cmp::PartialEq for MatchType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MatchType::Contain, MatchType::Contain) => true,
+            #[cfg(feature = "regex")]
+            (MatchType::Regex(x), MatchType::Regex(y)) => x.as_str() == y.as_str(),
+            (MatchType::Custom(x), MatchType::Custom(y)) => x.name == y.name,
+            _ => false,
+        }
+    }
+}
+
+impl cmp::Eq for MatchType {}
+
+impl fmt::Display for MatchType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchType::Contain => write!(f, "Contain"),
+            #[cfg(feature = "regex")]
+            MatchType::Regex(_) => write!(f, "Regex"),
+            MatchType::Custom(x) => write!(f, "Custom({})", x.name),
+        }
+    }
+}
+
+impl convert::From<&MatchType> for raw::MatchType {
+    fn from(v: &MatchType) -> Self {
         match v {
-            raw::MatchType::Contain => MatchType::Contain,
-            raw::MatchType::Regex => MatchType::Regex,
+            MatchType::Contain => raw::MatchType::Contain,
+            #[cfg(feature = "regex")]
+            MatchType::Regex(_) => raw::MatchType::Regex,
+            MatchType::Custom(x) => raw::MatchType::Custom(x.name.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{from_str, to_value};
+    use std::convert::TryFrom;
+
+    macro_rules! test_round_trip {
+        ($name:ident, $query:literal) => {
+            #[test]
+            fn $name() {
+                let q = Query::try_from(raw::Query::try_from($query).unwrap()).unwrap();
+                let want: JSONValue = from_str($query).unwrap();
+                assert_eq!(want, to_value(&q).unwrap());
+            }
+        };
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<Query>();
+        assert_send_sync::<QueryCondition>();
+        assert_send_sync::<QueryPair>();
+        assert_send_sync::<Condition>();
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn clone_eq() {
+        let q = Query::builder()
+            .pointer("/s")
+            .regex("[sS]irius")
+            .pointer("/i")
+            .lt(1.5)
+            .build()
+            .unwrap();
+        assert_eq!(q, q.clone());
+        let r = Query::builder()
+            .pointer("/s")
+            .regex("[sS]irius")
+            .build()
+            .unwrap();
+        assert_ne!(q, r);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    #[should_panic]
+    fn invalid_regex() {
+        Query::builder().pointer("/s").regex("[").build().unwrap();
+    }
+
+    #[test]
+    fn unknown_matcher() {
+        let q = raw::Query::try_from(
+            r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","mtype":"phonetic","value":{"type":"string","value":"sirius"}}}}}"#,
+        )
+        .unwrap();
+        let err = Query::try_from(q).err().unwrap();
+        assert_eq!(crate::error::ErrorKind::UnknownMatcher, err.kind());
+    }
+
+    #[test]
+    fn pointers() {
+        let q = Query::builder()
+            .pointer("/i")
+            .eq(1)
+            .or(!Query::builder().pointer("/s").contain("a"))
+            .build()
+            .unwrap();
+        let got: vec::Vec<_> = q.pointers().iter().map(|x| x.as_str()).collect();
+        assert_eq!(vec!["/i", "/s"], got);
+    }
+    #[test]
+    fn matched_pointers() {
+        let q = Query::builder()
+            .pointer("/i")
+            .eq(1)
+            .or(Query::builder().pointer("/s").eq("a"))
+            .or(Query::builder().pointer("/i").gt(0))
+            .build()
+            .unwrap();
+        let v = serde_json::json!({"i": 1, "s": "b"});
+        let got: vec::Vec<_> = q.matched_pointers(&v).iter().map(|x| x.as_str()).collect();
+        assert_eq!(vec!["/i"], got);
+        let v = serde_json::json!({"s": "a"});
+        let got: vec::Vec<_> = q.matched_pointers(&v).iter().map(|x| x.as_str()).collect();
+        assert_eq!(vec!["/s"], got);
+    }
+    #[test]
+    fn required_equals() {
+        let q = Query::builder()
+            .pointer("/i")
+            .eq(1)
+            .and(Query::builder().pointer("/s").eq("a"))
+            .and(Query::builder().pointer("/j").gt(1))
+            .and(!Query::builder().pointer("/k").eq(2))
+            .build()
+            .unwrap();
+        let got: vec::Vec<_> = q
+            .required_equals()
+            .into_iter()
+            .map(|(p, v)| (p.as_str(), v.clone()))
+            .collect();
+        assert_eq!(
+            vec![("/i", Value::Int(1)), ("/s", Value::String("a".to_owned()))],
+            got
+        );
+        let q = Query::builder()
+            .pointer("/i")
+            .eq(1)
+            .or(Query::builder().pointer("/s").eq("a"))
+            .build()
+            .unwrap();
+        assert!(q.required_equals().is_empty());
+        // the indexes hold only the scalars
+        let q = Query::builder()
+            .pointer("/p")
+            .cond(builder::Cond::json(serde_json::json!({"a": 1})))
+            .build()
+            .unwrap();
+        assert!(q.required_equals().is_empty());
+    }
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn invalid_duration() {
+        let err = Query::builder()
+            .pointer("/t")
+            .cond(builder::Cond::within("yesterday"))
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(crate::error::ErrorKind::InvalidTime, err.kind());
+    }
+    #[cfg(feature = "datetime")]
+    #[test]
+    fn within_now() {
+        let q = Query::builder()
+            .pointer("/t")
+            .cond(builder::Cond::within("1m"))
+            .build()
+            .unwrap();
+        let now = humantime::format_rfc3339(std::time::SystemTime::now());
+        let json = serde_json::json!({ "t": now.to_string() });
+        assert!(q.eval(&json).unwrap());
+        let json = serde_json::json!({"t": "2024-01-02T00:00:00Z"});
+        assert!(!q.eval(&json).unwrap());
+    }
+    #[test]
+    fn query_exists() {
+        let q = Query::try_from(
+            raw::Query::try_from(
+                r#"{"query":{"type":"and","pair":[{"type":"exists","p":"/a/b"},{"type":"not","pair":{"type":"exists","p":"/c"}}]},"on_mismatch":"false"}"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(q.eval(&serde_json::json!({"a":{"b":null}})).unwrap());
+        assert!(!q.eval(&serde_json::json!({"a":{"b":1},"c":1})).unwrap());
+        assert!(!q.eval(&serde_json::json!({"a":[1]})).unwrap());
+        assert_eq!(
+            q,
+            Query::builder()
+                .pointer("/a/b")
+                .exists()
+                .and(!Query::builder().pointer("/c").exists())
+                .build()
+                .unwrap()
+        );
+    }
+    #[test]
+    fn query_any_of() {
+        let any = |s: &str| {
+            let q = format!(
+                r#"{{"query":{{"type":"{}","pointers":["/msg","/error"],"cond":{{"type":"match","mtype":"contain","value":{{"type":"string","value":"fail"}}}}}},"on_mismatch":"false"}}"#,
+                s
+            );
+            Query::try_from(raw::Query::try_from(q.as_str()).unwrap()).unwrap()
+        };
+        let (any_of, all_of) = (any("any_of"), any("all_of"));
+        let v = serde_json::json!({"error":"failed"});
+        assert!(any_of.eval(&v).unwrap());
+        assert!(!all_of.eval(&v).unwrap());
+        let v = serde_json::json!({"msg":"failed","error":"failure"});
+        assert!(any_of.eval(&v).unwrap());
+        assert!(all_of.eval(&v).unwrap());
+        let v = serde_json::json!({"msg":1,"error":"ok"});
+        assert!(!any_of.eval(&v).unwrap());
+    }
+    #[test]
+    fn malformed_pointer() {
+        let err = Query::builder().pointer("s").null().build().err().unwrap();
+        assert_eq!(crate::error::ErrorKind::MalformedPointer, err.kind());
+    }
+
+    struct Equal;
+    impl Matcher for Equal {
+        fn test(&self, pattern: &str, value: &str) -> Result<bool> {
+            Ok(pattern == value)
+        }
+    }
+
+    #[test]
+    fn round_trip_custom() {
+        let query = r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","mtype":"equal","value":{"type":"string","value":"sirius"}}}}}"#;
+        let mut registry = MatcherRegistry::new();
+        registry.register("equal", Equal);
+        let q = Query::compile(raw::Query::try_from(query).unwrap(), &registry).unwrap();
+        let want: JSONValue = from_str(query).unwrap();
+        assert_eq!(want, to_value(&q).unwrap());
+    }
+
+    #[cfg(feature = "regex")]
+    fn regex_cond(pattern: &str) -> raw::Condition {
+        raw::Condition::Match {
+            value: raw::Value::String {
+                value: pattern.to_owned(),
+            },
+            mtype: raw::MatchType::Regex,
+            flags: None,
+            full: false,
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn compile_or_regex_set() {
+        let got = Condition::try_from(raw::Condition::Or {
+            value: vec![regex_cond("^s"), regex_cond("s$")],
+        })
+        .unwrap();
+        assert!(matches!(got, Condition::AnyRegex(_)));
+        let got = Condition::try_from(raw::Condition::Or {
+            value: vec![
+                raw::Condition::Equal {
+                    value: raw::Value::Null,
+                },
+                regex_cond("^s"),
+                regex_cond("s$"),
+            ],
+        })
+        .unwrap();
+        match got {
+            Condition::Or(x) => {
+                assert_eq!(2, x.len());
+                assert!(matches!(x[1], Condition::AnyRegex(_)));
+            }
+            x => panic!("{:?}", x),
+        }
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn compile_or_regex_set_keep_order() {
+        let got = Condition::try_from(raw::Condition::Or {
+            value: vec![
+                regex_cond("^a"),
+                raw::Condition::Equal {
+                    value: raw::Value::Number { value: 1.0 },
+                },
+                regex_cond("^b"),
+                regex_cond("b$"),
+            ],
+        })
+        .unwrap();
+        match &got {
+            Condition::Or(x) => {
+                assert_eq!(3, x.len());
+                assert!(matches!(x[0], Condition::Match(..)));
+                assert!(matches!(x[2], Condition::AnyRegex(_)));
+            }
+            x => panic!("{:?}", x),
+        }
+        // eq 1 fails before the regular expressions after it
+        assert!(got
+            .eval(&Value::String("b".to_owned()))
+            .err()
+            .unwrap()
+            .is_type_mismatch());
+        assert!(got.eval(&Value::String("a".to_owned())).unwrap());
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn compile_or_regex_set_invalid() {
+        let got = Condition::try_from(raw::Condition::Or {
+            value: vec![regex_cond("^s"), regex_cond("[")],
+        })
+        .err()
+        .unwrap();
+        assert_eq!("Invalid regex ([)", format!("{}", got));
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_flags() {
+        let q = Query::try_from(
+            raw::Query::try_from(
+                r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","mtype":"regex","flags":"i","value":{"type":"string","value":"^sirius$"}}}}}"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(q.eval(&serde_json::json!({"s": "SIRIUS"})).unwrap());
+        let want = r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","value":{"type":"string","value":"(?i)^sirius$"},"mtype":"regex"}}}}"#;
+        assert_eq!(want, serde_json::to_string(&q).unwrap());
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_flags_in_set() {
+        let mut a = regex_cond("^s");
+        if let raw::Condition::Match { flags, .. } = &mut a {
+            *flags = Some("i".to_owned());
         }
+        let got = Condition::try_from(raw::Condition::Or {
+            value: vec![a, regex_cond("x$")],
+        })
+        .unwrap();
+        assert!(got.eval(&Value::String("Sirius".to_owned())).unwrap());
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_full() {
+        let mut c = regex_cond("s|x");
+        if let raw::Condition::Match { full, .. } = &mut c {
+            *full = true;
+        }
+        let got = Condition::try_from(c).unwrap();
+        assert!(got.eval(&Value::String("x".to_owned())).unwrap());
+        assert!(!got.eval(&Value::String("sirius".to_owned())).unwrap());
+    }
+    #[test]
+    fn email_domain_case_insensitive() {
+        let email_domain = |cond: &str| {
+            let q = format!(
+                r#"{{"query":{{"type":"raw","pair":{{"p":"/e","cond":{{"type":"email_domain","cond":{}}}}}}}}}"#,
+                cond
+            );
+            Query::try_from(raw::Query::try_from(q.as_str()).unwrap()).unwrap()
+        };
+        let v = serde_json::json!({"e": "bob+ci@Mail.Example.com"});
+        for cond in [
+            r#"{"type":"eq","value":{"type":"string","value":"MAIL.Example.COM"}}"#,
+            r#"{"type":"not","value":{"type":"eq","value":{"type":"string","value":"Other.COM"}}}"#,
+            r#"{"type":"match","mtype":"contain","value":{"type":"string","value":"EXAMPLE"}}"#,
+            #[cfg(feature = "regex")]
+            r#"{"type":"match","mtype":"regex","value":{"type":"string","value":"(^|\\.)Example\\.COM$"}}"#,
+            #[cfg(feature = "regex")]
+            r#"{"type":"or","value":[{"type":"eq","value":{"type":"string","value":"X"}},{"type":"match","mtype":"regex","flags":"s","value":{"type":"string","value":"^MAIL"}}]}"#,
+        ] {
+            assert!(email_domain(cond).eval(&v).unwrap(), "{}", cond);
+        }
+    }
+    #[test]
+    fn invalid_regex_flags() {
+        let got = Condition::try_from(raw::Condition::Match {
+            value: raw::Value::String {
+                value: "s".to_owned(),
+            },
+            mtype: raw::MatchType::Contain,
+            flags: Some("i".to_owned()),
+            full: false,
+        })
+        .err()
+        .unwrap();
+        assert_eq!(crate::error::ErrorKind::InvalidRegex, got.kind());
     }
+    #[cfg(feature = "regex")]
+    test_round_trip!(
+        round_trip_regex_set,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"or","value":[{"type":"eq","value":{"type":"string","value":"X"}},{"type":"match","mtype":"regex","value":{"type":"string","value":"^s"}},{"type":"match","mtype":"regex","value":{"type":"string","value":"s$"}}]}}}}"#
+    );
+    #[cfg(feature = "regex")]
+    test_round_trip!(
+        round_trip_raw,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","mtype":"regex","value":{"type":"string","value":"[sS]irius"}}}}}"#
+    );
+    test_round_trip!(
+        round_trip_nested,
+        r#"{"query":{"type":"or","pair":[{"type":"not","pair":{"type":"raw","pair":{"p":"/i","cond":{"type":"and","value":[{"type":"gt","value":{"type":"number","value":1.5}},{"type":"not","value":{"type":"eq","value":{"type":"null"}}}]}}}},{"type":"raw","pair":{"p":"/b","cond":{"type":"eq","value":{"type":"bool","value":true}},"on_mismatch":"false"}}]}}"#
+    );
 }
@@ -0,0 +1,547 @@
+//! Fluent API to build [`Query`] without writing the raw json.
+//!
+//! ```
+//! # use jsongrep::query::Query;
+//! # use jsongrep::query::builder::Cond;
+//! # use jsongrep::select;
+//! # #[cfg(feature = "regex")]
+//! # {
+//!
+//! /// /s matches [sS]irius and /i is 1 or 2
+//! let q = Query::builder()
+//!     .pointer("/s")
+//!     .regex("[sS]irius")
+//!     .pointer("/i")
+//!     .cond(Cond::eq(1).or(Cond::eq(2)))
+//!     .build()
+//!     .unwrap();
+//! let sel = select::Query::new(Box::new(q));
+//! assert!(sel.select(r#"{"s":"Sirius","i":2}"#).is_ok());
+//! assert!(sel.select(r#"{"s":"Sirius","i":3}"#).err().unwrap().is_filtered());
+//! # }
+//! ```
+use crate::error::{Error, ErrorCode, Result};
+use crate::matcher::MatcherRegistry;
+use crate::query::Query;
+use crate::raw_query as raw;
+use crate::util;
+use std::convert::TryFrom;
+use std::ops;
+
+impl From<bool> for raw::Value {
+    fn from(v: bool) -> Self {
+        raw::Value::Bool { value: v }
+    }
+}
+
+impl From<i32> for raw::Value {
+    fn from(v: i32) -> Self {
+        raw::Value::Number { value: v.into() }
+    }
+}
+
+impl From<f64> for raw::Value {
+    fn from(v: f64) -> Self {
+        raw::Value::Number { value: v }
+    }
+}
+
+impl From<&str> for raw::Value {
+    fn from(v: &str) -> Self {
+        raw::Value::String {
+            value: v.to_owned(),
+        }
+    }
+}
+
+impl From<String> for raw::Value {
+    fn from(v: String) -> Self {
+        raw::Value::String { value: v }
+    }
+}
+
+/// Condition part of a pair.
+pub struct Cond(raw::Condition);
+
+impl Cond {
+    /// Match if a given value is equal to `v`.
+    pub fn eq(v: impl Into<raw::Value>) -> Cond {
+        Cond(raw::Condition::Equal { value: v.into() })
+    }
+    /// Match if a given value is null.
+    pub fn null() -> Cond {
+        Cond(raw::Condition::Equal {
+            value: raw::Value::Null,
+        })
+    }
+    /// Match if the pointer points a value, even null.
+    pub fn exists() -> Cond {
+        Cond(raw::Condition::Exists)
+    }
+    /// Match if a given value is of the type `t`.
+    pub fn is_type(t: raw::JsonType) -> Cond {
+        Cond(raw::Condition::IsType { value: t })
+    }
+    /// Match if the length of a given string, array or object meets `c`.
+    pub fn len(c: Cond) -> Cond {
+        Cond(raw::Condition::Len {
+            condition: Box::new(c.0),
+        })
+    }
+    /// Match if the number of the keys of a given object meets `c`.
+    pub fn nkeys(c: Cond) -> Cond {
+        Cond(raw::Condition::NKeys {
+            condition: Box::new(c.0),
+        })
+    }
+    /// Match if the domain of a given email address, in lowercase, meets `c`.
+    pub fn email_domain(c: Cond) -> Cond {
+        Cond(raw::Condition::EmailDomain {
+            condition: Box::new(c.0),
+        })
+    }
+    /// Match if a given array or object has no elements.
+    pub fn empty() -> Cond {
+        Cond(raw::Condition::Empty)
+    }
+    /// Match if a given array or object has elements.
+    pub fn not_empty() -> Cond {
+        Cond(raw::Condition::NotEmpty)
+    }
+    /// Match if a given RFC 3339 timestamp is within `duration`, e.g. `"24h"`, before now.
+    pub fn within(duration: impl Into<String>) -> Cond {
+        Cond(raw::Condition::Within {
+            duration: duration.into(),
+            now: None,
+        })
+    }
+    /// Match if a given value is equal to the json `v`, compared deeply if an array or an object.
+    pub fn json(v: serde_json::Value) -> Cond {
+        Cond(raw::Condition::Equal {
+            value: raw::Value::Json { value: v },
+        })
+    }
+    /// Match if a given value is greater than `v`.
+    pub fn gt(v: impl Into<raw::Value>) -> Cond {
+        Cond(raw::Condition::GreaterThan { value: v.into() })
+    }
+    /// Match if a given value is less than `v`.
+    pub fn lt(v: impl Into<raw::Value>) -> Cond {
+        Cond(raw::Condition::LessThan { value: v.into() })
+    }
+    /// Match if a given value contains `v`.
+    pub fn contain(v: &str) -> Cond {
+        Cond(raw::Condition::Match {
+            value: v.into(),
+            mtype: raw::MatchType::Contain,
+            flags: None,
+            full: false,
+        })
+    }
+    /// Match if a given value matches the regular expression `v`.
+    #[cfg(feature = "regex")]
+    pub fn regex(v: &str) -> Cond {
+        Cond(raw::Condition::Match {
+            value: v.into(),
+            mtype: raw::MatchType::Regex,
+            flags: None,
+            full: false,
+        })
+    }
+    /// Match if the whole of a given value matches the regular expression `v`.
+    #[cfg(feature = "regex")]
+    pub fn full_regex(v: &str) -> Cond {
+        Cond(raw::Condition::Match {
+            value: v.into(),
+            mtype: raw::MatchType::Regex,
+            flags: None,
+            full: true,
+        })
+    }
+    /// Match if the custom matcher registered as `name` accepts a given value with `v`.
+    pub fn custom(name: impl Into<String>, v: &str) -> Cond {
+        Cond(raw::Condition::Match {
+            value: v.into(),
+            mtype: raw::MatchType::Custom(name.into()),
+            flags: None,
+            full: false,
+        })
+    }
+    /// Match if both of the conditions accept a given value.
+    pub fn and(self, other: Cond) -> Cond {
+        match self.0 {
+            raw::Condition::And { mut value } => {
+                value.push(other.0);
+                Cond(raw::Condition::And { value })
+            }
+            x => Cond(raw::Condition::And {
+                value: vec![x, other.0],
+            }),
+        }
+    }
+    /// Match if at least one of the conditions accepts a given value.
+    pub fn or(self, other: Cond) -> Cond {
+        match self.0 {
+            raw::Condition::Or { mut value } => {
+                value.push(other.0);
+                Cond(raw::Condition::Or { value })
+            }
+            x => Cond(raw::Condition::Or {
+                value: vec![x, other.0],
+            }),
+        }
+    }
+}
+
+/// Negate the condition.
+impl ops::Not for Cond {
+    type Output = Cond;
+    fn not(self) -> Cond {
+        Cond(raw::Condition::Not {
+            value: Box::new(self.0),
+        })
+    }
+}
+
+impl From<Cond> for raw::Condition {
+    fn from(v: Cond) -> Self {
+        v.0
+    }
+}
+
+/// [`Query`] builder.
+///
+/// The pairs added sequentially are combined by `and`.
+#[derive(Default)]
+pub struct QueryBuilder {
+    query: Option<raw::QueryCondition>,
+    on_mismatch: Option<raw::MismatchPolicy>,
+}
+
+impl QueryBuilder {
+    /// Return a new empty builder.
+    pub fn new() -> QueryBuilder {
+        QueryBuilder::default()
+    }
+    /// Start a new pair that tests the value at `pointer`.
+    pub fn pointer(self, pointer: impl Into<String>) -> PairBuilder {
+        PairBuilder {
+            parent: self,
+            pointer: pointer.into(),
+            on_mismatch: None,
+            coerce: false,
+            trim: false,
+            collapse_ws: false,
+        }
+    }
+    /// Set the default [`raw::MismatchPolicy`] of the pairs.
+    pub fn on_mismatch(mut self, policy: raw::MismatchPolicy) -> QueryBuilder {
+        self.on_mismatch = Some(policy);
+        self
+    }
+    /// Return the query with the default [`raw::MismatchPolicy`] set to the pairs,
+    /// not to lose it by combining the query into another.
+    fn into_query(self) -> Option<raw::QueryCondition> {
+        let mut query = self.query;
+        if let (Some(x), Some(p)) = (query.as_mut(), self.on_mismatch) {
+            x.set_default_on_mismatch(p);
+        }
+        query
+    }
+    /// Match if both of the queries accept a given json.
+    pub fn and(mut self, other: QueryBuilder) -> QueryBuilder {
+        if let Some(x) = other.into_query() {
+            self.query = Some(match self.query {
+                None => x,
+                Some(raw::QueryCondition::And { mut pair }) => {
+                    pair.push(x);
+                    raw::QueryCondition::And { pair }
+                }
+                Some(y) => raw::QueryCondition::And { pair: vec![y, x] },
+            });
+        }
+        self
+    }
+    /// Match if at least one of the queries accepts a given json.
+    pub fn or(mut self, other: QueryBuilder) -> QueryBuilder {
+        if let Some(x) = other.into_query() {
+            self.query = Some(match self.query {
+                None => x,
+                Some(raw::QueryCondition::Or { mut pair }) => {
+                    pair.push(x);
+                    raw::QueryCondition::Or { pair }
+                }
+                Some(y) => raw::QueryCondition::Or { pair: vec![y, x] },
+            });
+        }
+        self
+    }
+    /// Return the raw query.
+    /// Return `Err` if no pairs are added.
+    pub fn build_raw(self) -> Result<raw::Query> {
+        match self.query {
+            Some(query) => Ok(raw::Query {
+                query,
+                on_mismatch: self.on_mismatch,
+            }),
+            None => Err(Error::new(ErrorCode::NoChildren {
+                by: util::type_name(&self).to_owned(),
+            })),
+        }
+    }
+    /// Return the query.
+    /// Return `Err` if no pairs are added or the query is invalid.
+    pub fn build(self) -> Result<Query> {
+        self.build_raw().and_then(Query::try_from)
+    }
+    /// Same as [`QueryBuilder::build`] but resolve the custom matchers by `registry`.
+    pub fn build_with(self, registry: &MatcherRegistry) -> Result<Query> {
+        self.build_raw().and_then(|x| Query::compile(x, registry))
+    }
+}
+
+/// Negate the query.
+impl ops::Not for QueryBuilder {
+    type Output = QueryBuilder;
+    fn not(mut self) -> QueryBuilder {
+        self.query = self
+            .query
+            .map(|x| raw::QueryCondition::Not { pair: Box::new(x) });
+        self
+    }
+}
+
+/// Builder of a pair, a pointer and a condition.
+pub struct PairBuilder {
+    parent: QueryBuilder,
+    pointer: String,
+    on_mismatch: Option<raw::MismatchPolicy>,
+    coerce: bool,
+    trim: bool,
+    collapse_ws: bool,
+}
+
+impl PairBuilder {
+    /// Set the [`raw::MismatchPolicy`] of the pair.
+    pub fn on_mismatch(mut self, policy: raw::MismatchPolicy) -> PairBuilder {
+        self.on_mismatch = Some(policy);
+        self
+    }
+    /// Read the numeric strings as numbers when the condition compares against a number.
+    pub fn coerce(mut self) -> PairBuilder {
+        self.coerce = true;
+        self
+    }
+    /// Remove the leading and the trailing whitespace of a given string.
+    pub fn trim(mut self) -> PairBuilder {
+        self.trim = true;
+        self
+    }
+    /// Replace the runs of whitespace of a given string with a space, and trim it.
+    pub fn collapse_ws(mut self) -> PairBuilder {
+        self.collapse_ws = true;
+        self
+    }
+    /// Finish the pair with `cond`.
+    pub fn cond(self, cond: Cond) -> QueryBuilder {
+        let pair = raw::QueryCondition::Raw {
+            pair: raw::QueryPair {
+                pointer: self.pointer,
+                condition: cond.into(),
+                on_mismatch: self.on_mismatch,
+                coerce: self.coerce,
+                trim: self.trim,
+                collapse_ws: self.collapse_ws,
+            },
+        };
+        self.parent.and(QueryBuilder {
+            query: Some(pair),
+            on_mismatch: None,
+        })
+    }
+    /// Finish the pair with [`Cond::eq`].
+    pub fn eq(self, v: impl Into<raw::Value>) -> QueryBuilder {
+        self.cond(Cond::eq(v))
+    }
+    /// Finish the pair with [`Cond::null`].
+    pub fn null(self) -> QueryBuilder {
+        self.cond(Cond::null())
+    }
+    /// Finish the pair with [`Cond::exists`].
+    pub fn exists(self) -> QueryBuilder {
+        self.cond(Cond::exists())
+    }
+    /// Finish the pair with [`Cond::gt`].
+    pub fn gt(self, v: impl Into<raw::Value>) -> QueryBuilder {
+        self.cond(Cond::gt(v))
+    }
+    /// Finish the pair with [`Cond::lt`].
+    pub fn lt(self, v: impl Into<raw::Value>) -> QueryBuilder {
+        self.cond(Cond::lt(v))
+    }
+    /// Finish the pair with [`Cond::contain`].
+    pub fn contain(self, v: &str) -> QueryBuilder {
+        self.cond(Cond::contain(v))
+    }
+    /// Finish the pair with [`Cond::regex`].
+    #[cfg(feature = "regex")]
+    pub fn regex(self, v: &str) -> QueryBuilder {
+        self.cond(Cond::regex(v))
+    }
+    /// Finish the pair with [`Cond::custom`].
+    pub fn custom(self, name: impl Into<String>, v: &str) -> QueryBuilder {
+        self.cond(Cond::custom(name, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::EvaluableQuery;
+    use serde_json::from_str;
+
+    macro_rules! test_build {
+        ($name:ident, $builder:expr, $json:literal, $want:expr) => {
+            #[test]
+            fn $name() {
+                let q = $builder.build().unwrap();
+                let got = q.eval(&from_str($json).unwrap()).unwrap();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_build!(
+        build_eq,
+        QueryBuilder::new().pointer("/i").eq(1),
+        r#"{"i":1}"#,
+        true
+    );
+    test_build!(
+        build_sequential_pairs,
+        QueryBuilder::new()
+            .pointer("/i")
+            .gt(0)
+            .pointer("/s")
+            .contain("ri"),
+        r#"{"i":1,"s":"sirius"}"#,
+        true
+    );
+    test_build!(
+        build_sequential_pairs_not,
+        QueryBuilder::new()
+            .pointer("/i")
+            .gt(0)
+            .pointer("/s")
+            .contain("ri"),
+        r#"{"i":1,"s":"spica"}"#,
+        false
+    );
+    #[cfg(feature = "regex")]
+    test_build!(
+        build_or,
+        QueryBuilder::new()
+            .pointer("/i")
+            .eq(1)
+            .or(QueryBuilder::new().pointer("/s").regex("^s")),
+        r#"{"i":0,"s":"spica"}"#,
+        true
+    );
+    test_build!(
+        build_not,
+        !QueryBuilder::new().pointer("/n").null(),
+        r#"{"n":null}"#,
+        false
+    );
+    test_build!(
+        build_cond,
+        QueryBuilder::new()
+            .pointer("/i")
+            .cond(!Cond::lt(0).or(Cond::gt(10))),
+        r#"{"i":5}"#,
+        true
+    );
+    test_build!(
+        build_whole_document,
+        QueryBuilder::new()
+            .pointer("")
+            .cond(Cond::is_type(raw::JsonType::Object).and(Cond::len(Cond::gt(1))))
+            .pointer("/a")
+            .cond(Cond::json(serde_json::json!([1, 2]))),
+        r#"{"a":[1,2],"b":{}}"#,
+        true
+    );
+    test_build!(
+        build_coerce,
+        QueryBuilder::new().pointer("/i").coerce().lt(10),
+        r#"{"i":"5"}"#,
+        true
+    );
+    #[cfg(feature = "regex")]
+    test_build!(
+        build_email_domain,
+        QueryBuilder::new()
+            .pointer("/email")
+            .cond(Cond::email_domain(Cond::regex(r"(^|\.)example\.com$"))),
+        r#"{"email":"bob+ci@Mail.Example.com"}"#,
+        true
+    );
+    test_build!(
+        build_exists,
+        !QueryBuilder::new().pointer("/x").cond(Cond::exists()),
+        r#"{"i":5}"#,
+        true
+    );
+
+    struct Prefix;
+    impl crate::matcher::Matcher for Prefix {
+        fn test(&self, pattern: &str, value: &str) -> Result<bool> {
+            Ok(value.starts_with(pattern))
+        }
+    }
+
+    #[test]
+    fn build_custom() {
+        let mut registry = MatcherRegistry::new();
+        registry.register("prefix", Prefix);
+        let q = QueryBuilder::new()
+            .pointer("/s")
+            .custom("prefix", "si")
+            .build_with(&registry)
+            .unwrap();
+        assert!(q.eval(&from_str(r#"{"s":"sirius"}"#).unwrap()).unwrap());
+        assert!(!q.eval(&from_str(r#"{"s":"spica"}"#).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn build_keep_on_mismatch() {
+        let q = QueryBuilder::new()
+            .pointer("/i")
+            .eq(1)
+            .or(QueryBuilder::new()
+                .on_mismatch(raw::MismatchPolicy::False)
+                .pointer("/s")
+                .gt(1)
+                .and(QueryBuilder::new().pointer("/b").eq(true)))
+            .build()
+            .unwrap();
+        assert!(!q
+            .eval(&from_str(r#"{"i":0,"s":"x","b":true}"#).unwrap())
+            .unwrap());
+        let raw = raw::Query::from(&q);
+        let json = serde_json::to_string(&raw).unwrap();
+        assert_eq!(
+            2,
+            json.matches(r#""on_mismatch":"false""#).count(),
+            "{}",
+            json
+        );
+        assert_eq!(q, Query::try_from(raw).unwrap());
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_empty() {
+        QueryBuilder::new().build().unwrap();
+    }
+}
@@ -0,0 +1,408 @@
+//! [`Query`] compiled into the nodes resolved at compile time.
+//!
+//! The tree of [`Query`] is kept as the form to build, compare and serialize,
+//! [`ClosureQuery`] is the form to evaluate many jsons by the same query.
+//! The comparisons are compiled into the enum variants specialized by the type of the operand,
+//! evaluated by static dispatch without `Box<dyn Fn>` calls,
+//! and the pairs are evaluated generically over [`Document`] without `dyn Document`.
+//!
+//! ```
+//! # use jsongrep::query::Query;
+//! # use jsongrep::select;
+//!
+//! let q = Query::builder().pointer("/i").gt(1).build().unwrap();
+//! let sel = select::Query::new(Box::new(q.to_closure()));
+//! assert!(sel.matches(r#"{"i":2}"#).unwrap());
+//! assert!(!sel.matches(r#"{"i":1}"#).unwrap());
+//! ```
+use crate::error::{Error, ErrorCode, Result};
+use crate::eval::document::Document;
+use crate::matcher::{Builtin, Matcher};
+use crate::pointer::Pointer;
+use crate::query::{
+    Condition, EvaluableCondition, EvaluableQuery, MatchType, MismatchPolicy, Query,
+    QueryCondition, QueryPair, Value,
+};
+use crate::util;
+#[cfg(feature = "regex")]
+use regex::{Regex, RegexSet};
+use serde_json::value::{RawValue, Value as JSONValue};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+/// [`Query`] compiled for the evaluation, evaluates the same as the original query.
+pub struct ClosureQuery {
+    root: Node,
+}
+
+impl fmt::Debug for ClosureQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ClosureQuery")
+    }
+}
+
+impl From<&Query> for ClosureQuery {
+    fn from(v: &Query) -> Self {
+        ClosureQuery {
+            root: Node::new(&v.query),
+        }
+    }
+}
+
+impl EvaluableQuery for ClosureQuery {
+    fn eval(&self, value: &JSONValue) -> Result<bool> {
+        self.root.eval(value)
+    }
+    fn eval_raw(&self, value: &RawValue) -> Result<bool> {
+        self.root.eval(&Raw(value))
+    }
+}
+
+/// [`RawValue`] as a sized [`Document`].
+struct Raw<'a>(&'a RawValue);
+
+impl fmt::Display for Raw<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.0, f)
+    }
+}
+
+impl Document for Raw<'_> {
+    fn pointed(&self, pointer: &Pointer) -> Result<Option<Cow<'_, JSONValue>>> {
+        self.0.pointed(pointer)
+    }
+}
+
+fn no_children<T>(v: T) -> Error {
+    Error::new(ErrorCode::NoChildren {
+        by: util::type_name(v).to_owned(),
+    })
+}
+
+/// Compiled [`QueryCondition`].
+enum Node {
+    Pair(Box<Pair>),
+    Not(Box<Node>),
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    /// `and` or `or` without children, always fails.
+    NoChildren(&'static str),
+}
+
+/// Compiled [`QueryPair`].
+struct Pair {
+    pointer: Pointer,
+    test: Test,
+    /// Ignore the type mismatches as [`EvaluableCondition::eval_lenient`].
+    lenient: bool,
+    /// The original pair to normalize the values, if it changes them.
+    preprocess: Option<QueryPair>,
+    /// The original condition to evaluate the missing value.
+    condition: Condition,
+}
+
+impl Node {
+    fn new(q: &QueryCondition) -> Node {
+        match q {
+            QueryCondition::Raw(x) => Node::Pair(Box::new(Pair {
+                pointer: x.pointer.clone(),
+                test: Test::new(&x.condition),
+                lenient: matches!(x.on_mismatch, MismatchPolicy::False),
+                preprocess: x.preprocesses().then(|| x.clone()),
+                condition: x.condition.clone(),
+            })),
+            QueryCondition::Not(x) => Node::Not(Box::new(Node::new(x))),
+            QueryCondition::And(x) if !x.is_empty() => Node::And(x.iter().map(Node::new).collect()),
+            QueryCondition::Or(x) if !x.is_empty() => Node::Or(x.iter().map(Node::new).collect()),
+            QueryCondition::And(_) | QueryCondition::Or(_) => Node::NoChildren(util::type_name(q)),
+        }
+    }
+    fn eval<D: Document + ?Sized>(&self, v: &D) -> Result<bool> {
+        match self {
+            Node::Pair(p) => p.eval(v),
+            Node::Not(x) => x.eval(v).map(|b| !b),
+            Node::And(x) => {
+                for c in x {
+                    let r = c.eval(v);
+                    match r {
+                        Err(_) | Ok(false) => return r,
+                        _ => continue,
+                    }
+                }
+                Ok(true)
+            }
+            Node::Or(x) => {
+                for c in x {
+                    let r = c.eval(v);
+                    match r {
+                        Err(_) | Ok(true) => return r,
+                        _ => continue,
+                    }
+                }
+                Ok(false)
+            }
+            Node::NoChildren(by) => Err(no_children(by)),
+        }
+    }
+}
+
+impl Pair {
+    fn eval<D: Document + ?Sized>(&self, v: &D) -> Result<bool> {
+        let test = |x: &Value| {
+            if self.lenient {
+                self.test.eval_unknown(x).map(|x| x.unwrap_or(false))
+            } else {
+                self.test.eval(x)
+            }
+        };
+        let eval = |x: &Value| match self.preprocess.as_ref().and_then(|p| p.preprocess(x)) {
+            Some(y) => test(&y),
+            None => test(x),
+        };
+        QueryPair::eval_pointed(&self.pointer, v, eval, &self.condition)
+    }
+}
+
+/// Compiled [`Condition`].
+enum Test {
+    Not(Box<Test>),
+    And(Vec<Test>),
+    Or(Vec<Test>),
+    /// Comparison by `Leaf` if a given value has the expected type,
+    /// otherwise by the original condition to report the same errors.
+    Leaf(Leaf, Box<Condition>),
+}
+
+/// Comparison specialized by the type of the operand.
+enum Leaf {
+    EqNull,
+    EqBool(bool),
+    EqInt(i32),
+    EqString(String),
+    GtInt(i32),
+    LtInt(i32),
+    Contain(String),
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+    Custom(String, Arc<dyn Matcher>),
+    #[cfg(feature = "regex")]
+    AnyRegex(RegexSet),
+    /// Not specialized, always by the original condition.
+    Other,
+}
+
+impl Test {
+    fn new(c: &Condition) -> Test {
+        match c {
+            Condition::Not(x) => Test::Not(Box::new(Test::new(x))),
+            Condition::And(x) if !x.is_empty() => Test::And(x.iter().map(Test::new).collect()),
+            Condition::Or(x) if !x.is_empty() => Test::Or(x.iter().map(Test::new).collect()),
+            _ => Test::Leaf(Leaf::new(c), Box::new(c.clone())),
+        }
+    }
+    fn eval(&self, v: &Value) -> Result<bool> {
+        match self {
+            Test::Not(x) => x.eval(v).map(|b| !b),
+            Test::And(x) => {
+                for c in x {
+                    let r = c.eval(v);
+                    match r {
+                        Err(_) | Ok(false) => return r,
+                        _ => continue,
+                    }
+                }
+                Ok(true)
+            }
+            Test::Or(x) => {
+                for c in x {
+                    let r = c.eval(v);
+                    match r {
+                        Err(_) | Ok(true) => return r,
+                        _ => continue,
+                    }
+                }
+                Ok(false)
+            }
+            Test::Leaf(l, c) => l.test(v).unwrap_or_else(|| c.eval(v)),
+        }
+    }
+    /// Evaluate `None` if the types mismatch, as [`Condition::eval_unknown`].
+    fn eval_unknown(&self, v: &Value) -> Result<Option<bool>> {
+        match self {
+            Test::Not(x) => x.eval_unknown(v).map(|x| x.map(|b| !b)),
+            Test::And(x) => {
+                let mut r = Some(true);
+                for c in x {
+                    match c.eval_unknown(v)? {
+                        Some(false) => return Ok(Some(false)),
+                        None => r = None,
+                        _ => continue,
+                    }
+                }
+                Ok(r)
+            }
+            Test::Or(x) => {
+                let mut r = Some(false);
+                for c in x {
+                    match c.eval_unknown(v)? {
+                        Some(true) => return Ok(Some(true)),
+                        None => r = None,
+                        _ => continue,
+                    }
+                }
+                Ok(r)
+            }
+            Test::Leaf(..) => match self.eval(v) {
+                Err(x) if x.is_type_mismatch() => Ok(None),
+                x => x.map(Some),
+            },
+        }
+    }
+}
+
+impl Leaf {
+    fn new(c: &Condition) -> Leaf {
+        match c {
+            Condition::Equal(Value::Null) => Leaf::EqNull,
+            Condition::Equal(Value::Bool(x)) => Leaf::EqBool(*x),
+            Condition::Equal(Value::Int(x)) => Leaf::EqInt(*x),
+            Condition::Equal(Value::String(x)) => Leaf::EqString(x.clone()),
+            Condition::GreaterThan(Value::Int(x)) => Leaf::GtInt(*x),
+            Condition::LessThan(Value::Int(x)) => Leaf::LtInt(*x),
+            Condition::Match(Value::String(x), MatchType::Contain) => Leaf::Contain(x.clone()),
+            #[cfg(feature = "regex")]
+            Condition::Match(Value::String(_), MatchType::Regex(r)) => Leaf::Regex(r.clone()),
+            Condition::Match(Value::String(x), MatchType::Custom(m)) => {
+                Leaf::Custom(x.clone(), m.matcher.clone())
+            }
+            #[cfg(feature = "regex")]
+            Condition::AnyRegex(x) => Leaf::AnyRegex(x.0.clone()),
+            _ => Leaf::Other,
+        }
+    }
+    /// Compare `v`, `None` if `v` is not of the expected type.
+    fn test(&self, v: &Value) -> Option<Result<bool>> {
+        match (self, v) {
+            (Leaf::EqNull, Value::Null) => Some(Ok(true)),
+            (Leaf::EqBool(x), Value::Bool(y)) => Some(Ok(x == y)),
+            (Leaf::EqInt(x), Value::Int(y)) => Some(Ok(x == y)),
+            (Leaf::EqString(x), Value::String(y)) => Some(Ok(x == y)),
+            (Leaf::GtInt(x), Value::Int(y)) => Some(Ok(x < y)),
+            (Leaf::LtInt(x), Value::Int(y)) => Some(Ok(x > y)),
+            (Leaf::Contain(x), Value::String(y)) => Some(Builtin::Raw(x).test(y)),
+            #[cfg(feature = "regex")]
+            (Leaf::Regex(r), Value::String(y)) => Some(Builtin::Regex(r).test(y)),
+            (Leaf::Custom(x, m), Value::String(y)) => Some(m.test(x, y)),
+            #[cfg(feature = "regex")]
+            (Leaf::AnyRegex(set), Value::String(y)) => Some(Ok(set.is_match(y))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_query as raw;
+    use serde_json::from_str;
+    use std::convert::TryFrom;
+
+    const VALUES: &[&str] = &[
+        r#"{"i":1,"f":1.5,"s":"sirius","b":true,"n":null}"#,
+        r#"{"i":3,"f":0.5,"s":"spica","b":false,"n":1}"#,
+        r#"{"i":"1","f":null,"s":2,"b":"true","n":"null"}"#,
+        r#"{"i":[1],"s":{"x":"sirius"}}"#,
+        r#"{}"#,
+    ];
+
+    /// Assert that the compiled query evaluates `VALUES` the same as the tree.
+    macro_rules! test_closure {
+        ($name:ident, $query:literal) => {
+            #[test]
+            fn $name() {
+                let q = Query::try_from(raw::Query::try_from($query).unwrap()).unwrap();
+                let c = q.to_closure();
+                for v in VALUES {
+                    let json: JSONValue = from_str(v).unwrap();
+                    let raw: &RawValue = from_str(v).unwrap();
+                    let want = q.eval(&json).map_err(|x| x.to_string());
+                    assert_eq!(want, c.eval(&json).map_err(|x| x.to_string()), "{}", v);
+                    assert_eq!(want, c.eval_raw(raw).map_err(|x| x.to_string()), "{}", v);
+                }
+            }
+        };
+    }
+
+    test_closure!(
+        closure_eq_int,
+        r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"eq","value":{"type":"number","value":1}}}}}"#
+    );
+    test_closure!(
+        closure_eq_null,
+        r#"{"query":{"type":"raw","pair":{"p":"/n","cond":{"type":"eq","value":{"type":"null"}}}}}"#
+    );
+    test_closure!(
+        closure_gt_float,
+        r#"{"query":{"type":"raw","pair":{"p":"/f","cond":{"type":"gt","value":{"type":"number","value":1.0}}}}}"#
+    );
+    test_closure!(
+        closure_lt_int,
+        r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"lt","value":{"type":"number","value":2}}}}}"#
+    );
+    test_closure!(
+        closure_contain,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","value":{"type":"string","value":"ri"},"mtype":"contain"}}}}"#
+    );
+    #[cfg(feature = "regex")]
+    test_closure!(
+        closure_any_regex,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"or","value":[
+{"type":"match","value":{"type":"string","value":"^sp"},"mtype":"regex"},
+{"type":"match","value":{"type":"string","value":"us$"},"mtype":"regex"}]}}}}"#
+    );
+    test_closure!(
+        closure_lenient,
+        r#"{"query":{"type":"raw","pair":{"p":"/b","on_mismatch":"false","cond":{"type":"not","value":
+{"type":"eq","value":{"type":"bool","value":true}}}}}}"#
+    );
+    test_closure!(
+        closure_lenient_not_or,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","on_mismatch":"false","cond":{"type":"not","value":
+{"type":"or","value":[{"type":"eq","value":{"type":"number","value":1}},
+{"type":"eq","value":{"type":"string","value":"vega"}}]}}}}}"#
+    );
+    test_closure!(
+        closure_and_or,
+        r#"{"query":{"type":"or","pair":[
+{"type":"and","pair":[
+{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":0}}}},
+{"type":"raw","pair":{"p":"/b","cond":{"type":"eq","value":{"type":"bool","value":false}}}}]},
+{"type":"not","pair":{"type":"raw","pair":{"p":"/s","cond":{"type":"eq","value":{"type":"string","value":"sirius"}}}}}]}}"#
+    );
+    test_closure!(
+        closure_exists,
+        r#"{"query":{"type":"raw","pair":{"p":"/n","cond":{"type":"not","value":{"type":"exists"}}}}}"#
+    );
+    test_closure!(
+        closure_len,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","on_mismatch":"false","cond":{"type":"len","cond":
+{"type":"gt","value":{"type":"number","value":5}}}}}}"#
+    );
+    test_closure!(
+        closure_not_empty,
+        r#"{"query":{"type":"raw","pair":{"p":"/i","on_mismatch":"false","cond":{"type":"not_empty"}}}}"#
+    );
+    test_closure!(
+        closure_coerce,
+        r#"{"query":{"type":"raw","pair":{"p":"/i","coerce":true,"on_mismatch":"false","cond":{"type":"gt","value":{"type":"number","value":0}}}}}"#
+    );
+    test_closure!(
+        closure_trim,
+        r#"{"query":{"type":"raw","pair":{"p":"/s","trim":true,"on_mismatch":"false","cond":{"type":"eq","value":{"type":"string","value":"sirius"}}}}}"#
+    );
+    test_closure!(
+        closure_no_children,
+        r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"and","value":[]}}}}"#
+    );
+}
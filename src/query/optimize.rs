@@ -0,0 +1,335 @@
+//! Simplification of the compiled query.
+//!
+//! - Flatten nested `and` and `or`.
+//! - Fold double `not`.
+//! - Remove the duplicated children of `and` and `or`.
+//! - Reorder the children of `and` to evaluate the cheap ones first.
+//!
+//! Only the children that never fail are reordered, and only among the adjacent ones,
+//! the children that may fail on a missing or mistyped value keep their positions,
+//! e.g. `/kind eq "x"` still guards `/n gt 5` in `/kind eq "x" and /n gt 5`.
+//! So reordering changes neither the result nor the error.
+#[cfg(feature = "url")]
+use crate::query::UrlCondition;
+use crate::query::{Condition, MatchType, MismatchPolicy, QueryCondition, QueryPair, Value};
+use std::vec;
+
+/// Rough evaluation cost of a leaf condition.
+const COST_SCALAR: usize = 1;
+const COST_STRING: usize = 2;
+const COST_CONTAIN: usize = 3;
+const COST_CUSTOM: usize = 4;
+#[cfg(feature = "regex")]
+const COST_REGEX: usize = 5;
+#[cfg(feature = "url")]
+const COST_URL: usize = 5;
+
+/// Remove the duplicated items keeping the first ones.
+fn dedup<T: PartialEq>(v: vec::Vec<T>) -> vec::Vec<T> {
+    let mut r: vec::Vec<T> = vec::Vec::with_capacity(v.len());
+    for x in v {
+        if !r.contains(&x) {
+            r.push(x);
+        }
+    }
+    r
+}
+
+/// Optimize the children and merge the grandchildren extracted by `split`,
+/// then remove the duplicates.
+fn flatten<T, F>(x: vec::Vec<T>, optimize: impl Fn(T) -> T, split: F) -> vec::Vec<T>
+where
+    T: PartialEq,
+    F: Fn(T) -> Result<vec::Vec<T>, T>,
+{
+    let mut r = vec::Vec::with_capacity(x.len());
+    for c in x {
+        match split(optimize(c)) {
+            Ok(y) => r.extend(y),
+            Err(y) => r.push(y),
+        }
+    }
+    dedup(r)
+}
+
+/// Sort the children between the fallible ones by the cost.
+fn reorder<T>(x: &mut [T], fallible: impl Fn(&T) -> bool, cost: impl Fn(&T) -> usize) {
+    for run in x.split_mut(|c| fallible(c)) {
+        run.sort_by_key(|c| cost(c));
+    }
+}
+
+/// Return the only child or the children combined by `f`.
+fn unwrap_single<T>(mut x: vec::Vec<T>, f: fn(vec::Vec<T>) -> T) -> T {
+    if x.len() == 1 {
+        x.remove(0)
+    } else {
+        f(x)
+    }
+}
+
+impl QueryCondition {
+    /// Return the simplified condition.
+    pub(crate) fn optimize(self) -> Self {
+        match self {
+            QueryCondition::Raw(x) => QueryCondition::Raw(x.optimize()),
+            QueryCondition::Not(x) => match x.optimize() {
+                QueryCondition::Not(y) => *y,
+                y => QueryCondition::Not(Box::new(y)),
+            },
+            QueryCondition::And(x) => {
+                let mut x = flatten(x, Self::optimize, |c| match c {
+                    QueryCondition::And(y) if !y.is_empty() => Ok(y),
+                    y => Err(y),
+                });
+                reorder(&mut x, Self::fallible, Self::cost);
+                unwrap_single(x, QueryCondition::And)
+            }
+            QueryCondition::Or(x) => {
+                let x = flatten(x, Self::optimize, |c| match c {
+                    QueryCondition::Or(y) if !y.is_empty() => Ok(y),
+                    y => Err(y),
+                });
+                unwrap_single(x, QueryCondition::Or)
+            }
+        }
+    }
+    /// Report whether the evaluation may fail.
+    fn fallible(&self) -> bool {
+        match self {
+            QueryCondition::Raw(x) => {
+                let lenient = matches!(x.on_mismatch, MismatchPolicy::False);
+                // a missing value fails unless the condition tells the result
+                x.condition.eval_missing().is_none() || x.condition.fallible(lenient)
+            }
+            QueryCondition::Not(x) => x.fallible(),
+            QueryCondition::And(x) | QueryCondition::Or(x) => {
+                x.is_empty() || x.iter().any(|c| c.fallible())
+            }
+        }
+    }
+    fn cost(&self) -> usize {
+        match self {
+            QueryCondition::Raw(x) => x.condition.cost(),
+            QueryCondition::Not(x) => x.cost(),
+            QueryCondition::And(x) | QueryCondition::Or(x) => x.iter().map(|c| c.cost()).sum(),
+        }
+    }
+}
+
+impl QueryPair {
+    fn optimize(self) -> Self {
+        let lenient = matches!(self.on_mismatch, MismatchPolicy::False);
+        QueryPair {
+            condition: self.condition.optimize(lenient),
+            ..self
+        }
+    }
+}
+
+impl Condition {
+    /// Return the simplified condition, evaluated with the type mismatches as false if `lenient`.
+    pub(crate) fn optimize(self, lenient: bool) -> Self {
+        match self {
+            Condition::Not(x) => match x.optimize(lenient) {
+                Condition::Not(y) => *y,
+                y => Condition::Not(Box::new(y)),
+            },
+            Condition::And(x) => {
+                let mut x = flatten(
+                    x,
+                    |c| c.optimize(lenient),
+                    |c| match c {
+                        Condition::And(y) if !y.is_empty() => Ok(y),
+                        y => Err(y),
+                    },
+                );
+                reorder(&mut x, |c| c.fallible(lenient), Self::cost);
+                unwrap_single(x, Condition::And)
+            }
+            Condition::Or(x) => {
+                let x = flatten(
+                    x,
+                    |c| c.optimize(lenient),
+                    |c| match c {
+                        Condition::Or(y) if !y.is_empty() => Ok(y),
+                        y => Err(y),
+                    },
+                );
+                unwrap_single(x, Condition::Or)
+            }
+            Condition::Len(x) => Condition::Len(Box::new(x.optimize(lenient))),
+            Condition::NKeys(x) => Condition::NKeys(Box::new(x.optimize(lenient))),
+            Condition::EmailDomain(x) => Condition::EmailDomain(Box::new(x.optimize(lenient))),
+            #[cfg(feature = "url")]
+            Condition::Url(x) => {
+                let x = *x;
+                let part = |c: Option<Condition>| c.map(|c| c.optimize(lenient));
+                Condition::Url(Box::new(UrlCondition {
+                    scheme: part(x.scheme),
+                    host: part(x.host),
+                    port: part(x.port),
+                    path: part(x.path),
+                    fragment: part(x.fragment),
+                    query: x
+                        .query
+                        .into_iter()
+                        .map(|(k, c)| (k, c.optimize(lenient)))
+                        .collect(),
+                }))
+            }
+            x => x,
+        }
+    }
+    /// Report whether the evaluation of a present value may fail,
+    /// the type mismatches do not if `lenient`.
+    fn fallible(&self, lenient: bool) -> bool {
+        match self {
+            Condition::Exists | Condition::IsType(_) => false,
+            Condition::Match(_, MatchType::Custom(_)) => true,
+            Condition::Not(x) => x.fallible(lenient),
+            Condition::Len(x) | Condition::NKeys(x) | Condition::EmailDomain(x) => {
+                !lenient || x.fallible(lenient)
+            }
+            Condition::And(x) | Condition::Or(x) => {
+                x.is_empty() || x.iter().any(|c| c.fallible(lenient))
+            }
+            _ => !lenient,
+        }
+    }
+    fn cost(&self) -> usize {
+        match self {
+            Condition::Equal(x) | Condition::GreaterThan(x) | Condition::LessThan(x) => match x {
+                Value::String(_) => COST_STRING,
+                _ => COST_SCALAR,
+            },
+            Condition::Match(_, t) => match t {
+                MatchType::Contain => COST_CONTAIN,
+                #[cfg(feature = "regex")]
+                MatchType::Regex(_) => COST_REGEX,
+                MatchType::Custom(_) => COST_CUSTOM,
+            },
+            #[cfg(feature = "regex")]
+            Condition::AnyRegex(_) => COST_REGEX,
+            #[cfg(feature = "datetime")]
+            Condition::Within(_) => COST_STRING,
+            #[cfg(feature = "url")]
+            Condition::Url(_) => COST_URL,
+            Condition::Exists | Condition::IsType(_) | Condition::Empty | Condition::NotEmpty => {
+                COST_SCALAR
+            }
+            Condition::Not(x) | Condition::Len(x) | Condition::NKeys(x) => x.cost(),
+            Condition::EmailDomain(x) => COST_STRING + x.cost(),
+            Condition::And(x) | Condition::Or(x) => x.iter().map(|c| c.cost()).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::MatcherRegistry;
+    use crate::query::builder::{Cond, QueryBuilder};
+    use crate::query::Query;
+
+    fn cond(q: Query) -> Condition {
+        match q.query {
+            QueryCondition::Raw(x) => x.condition,
+            x => panic!("not a pair: {:?}", x),
+        }
+    }
+
+    macro_rules! test_optimize_cond {
+        ($name:ident, $cond:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let got = cond(
+                    QueryBuilder::new()
+                        .pointer("/x")
+                        .on_mismatch(crate::raw_query::MismatchPolicy::False)
+                        .cond($cond)
+                        .build()
+                        .unwrap(),
+                );
+                let want = Condition::compile($want.into(), &MatcherRegistry::new()).unwrap();
+                assert_eq!(want, got);
+            }
+        };
+    }
+
+    test_optimize_cond!(optimize_double_not, !!Cond::eq(1), Cond::eq(1));
+    test_optimize_cond!(
+        optimize_flatten_and,
+        Cond::eq(1).and(Cond::gt(0).and(Cond::lt(2))),
+        Cond::eq(1).and(Cond::gt(0)).and(Cond::lt(2))
+    );
+    test_optimize_cond!(
+        optimize_dedup_or,
+        Cond::eq(1).or(Cond::eq(2)).or(Cond::eq(1)),
+        Cond::eq(1).or(Cond::eq(2))
+    );
+    test_optimize_cond!(optimize_single, Cond::eq(1).and(Cond::eq(1)), Cond::eq(1));
+    test_optimize_cond!(
+        optimize_reorder_and,
+        Cond::contain("a").and(Cond::eq("b")).and(Cond::eq(1)),
+        Cond::eq(1).and(Cond::eq("b")).and(Cond::contain("a"))
+    );
+
+    #[test]
+    fn optimize_keep_fallible_order() {
+        let got = cond(
+            QueryBuilder::new()
+                .pointer("/x")
+                .cond(Cond::contain("a").and(Cond::eq(1)))
+                .build()
+                .unwrap(),
+        );
+        let want = Condition::compile(
+            Cond::contain("a").and(Cond::eq(1)).into(),
+            &MatcherRegistry::new(),
+        )
+        .unwrap();
+        assert_eq!(want, got);
+    }
+    #[test]
+    fn optimize_guard() {
+        use crate::query::EvaluableQuery;
+        let q = QueryBuilder::new()
+            .pointer("/kind")
+            .eq("x")
+            .and(QueryBuilder::new().pointer("/n").gt(5))
+            .build()
+            .unwrap();
+        let v = serde_json::json!({"kind": "y"});
+        assert!(!q.eval(&v).unwrap());
+        assert!(!q.to_closure().eval(&v).unwrap());
+    }
+    #[test]
+    fn optimize_query_condition() {
+        let got = (!!QueryBuilder::new()
+            .pointer("/s")
+            .contain("a")
+            .and(QueryBuilder::new().pointer("/i").eq(1)))
+        .build()
+        .unwrap();
+        let want = QueryCondition::And(vec![
+            QueryCondition::Raw(QueryPair {
+                pointer: crate::pointer::Pointer::lenient("/s"),
+                condition: Condition::Match(Value::String("a".to_owned()), MatchType::Contain),
+                on_mismatch: crate::query::MismatchPolicy::Error,
+                coerce: false,
+                trim: false,
+                collapse_ws: false,
+            }),
+            QueryCondition::Raw(QueryPair {
+                pointer: crate::pointer::Pointer::lenient("/i"),
+                condition: Condition::Equal(Value::Int(1)),
+                on_mismatch: crate::query::MismatchPolicy::Error,
+                coerce: false,
+                trim: false,
+                collapse_ws: false,
+            }),
+        ]);
+        assert_eq!(want, got.query);
+    }
+}
@@ -1,10 +1,13 @@
 use crate::error;
-use serde::{Deserialize, Serialize};
+use crate::util;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::from_str;
+use std::collections::BTreeMap;
 use std::convert;
 use std::vec;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum Value {
     #[serde(rename = "null")]
@@ -15,9 +18,26 @@ pub enum Value {
     Number { value: f64 },
     #[serde(rename = "string")]
     String { value: String },
+    /// Any json, to compare the arrays and the objects by `eq`.
+    #[serde(rename = "json")]
+    Json { value: serde_json::Value },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Type of a json value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum JsonType {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum Condition {
     #[serde(rename = "eq")]
@@ -27,32 +47,169 @@ pub enum Condition {
     #[serde(rename = "lt")]
     LessThan { value: Value },
     #[serde(rename = "match")]
-    Match { value: Value, mtype: MatchType },
+    Match {
+        value: Value,
+        mtype: MatchType,
+        /// Flags of the regular expression, any of `"i"`, `"m"`, `"s"` and `"x"`, e.g. `"im"`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        flags: Option<String>,
+        /// Require the regular expression to match the whole string.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        full: bool,
+    },
     #[serde(rename = "not")]
     Not { value: Box<Condition> },
     #[serde(rename = "and")]
     And { value: vec::Vec<Condition> },
     #[serde(rename = "or")]
     Or { value: vec::Vec<Condition> },
+    /// Match if the pointer points a value, the only condition that accepts a missing value.
+    #[serde(rename = "exists")]
+    Exists,
+    /// Match if the value is of the type.
+    #[serde(rename = "is_type")]
+    IsType { value: JsonType },
+    /// Match if the length of the string, the array or the object meets the condition.
+    #[serde(rename = "len")]
+    Len {
+        #[serde(rename = "cond")]
+        condition: Box<Condition>,
+    },
+    /// Match if the number of the keys of the object meets the condition.
+    #[serde(rename = "nkeys")]
+    NKeys {
+        #[serde(rename = "cond")]
+        condition: Box<Condition>,
+    },
+    /// Match if the domain of the email address meets the condition, case-insensitively:
+    /// the domain and the string literals of the condition are lowercased.
+    #[serde(rename = "email_domain")]
+    EmailDomain {
+        #[serde(rename = "cond")]
+        condition: Box<Condition>,
+    },
+    /// Match if the array has no elements or the object has no keys.
+    #[serde(rename = "empty")]
+    Empty,
+    /// Match if the array has elements or the object has keys.
+    #[serde(rename = "not_empty")]
+    NotEmpty,
+    /// Match if the RFC 3339 timestamp is within `duration`, e.g. `"24h"`, before now.
+    #[serde(rename = "within")]
+    Within {
+        duration: String,
+        /// RFC 3339 timestamp used instead of the current time.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        now: Option<String>,
+    },
+    /// Match if the string is a URL whose components meet the conditions.
+    /// A missing component, e.g. the host of `mailto:`, is evaluated as the missing value.
+    #[serde(rename = "url")]
+    Url {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scheme: Option<Box<Condition>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        host: Option<Box<Condition>>,
+        /// The port, or the default port of the scheme.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        port: Option<Box<Condition>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<Box<Condition>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fragment: Option<Box<Condition>>,
+        /// Conditions of the query parameters by the names,
+        /// match if one of the values of the parameter meets the condition.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        query: BTreeMap<String, Condition>,
+    },
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Serialized as a string, `"contain"`, `"regex"` or the name of the custom matcher.
+#[derive(Debug, Clone)]
 pub enum MatchType {
-    #[serde(rename = "contain")]
     Contain,
-    #[serde(rename = "regex")]
     Regex,
+    /// Name of the matcher registered to [`MatcherRegistry`](crate::matcher::MatcherRegistry).
+    Custom(String),
+}
+
+impl MatchType {
+    fn name(&self) -> &str {
+        match self {
+            MatchType::Contain => "contain",
+            MatchType::Regex => "regex",
+            MatchType::Custom(x) => x,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Serialize for MatchType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for MatchType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(match name.as_str() {
+            "contain" => MatchType::Contain,
+            "regex" => MatchType::Regex,
+            _ => MatchType::Custom(name),
+        })
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for MatchType {
+    fn schema_name() -> String {
+        "MatchType".to_owned()
+    }
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        let mut s = String::json_schema(gen).into_object();
+        s.metadata().description =
+            Some(r#""contain", "regex" or the name of the custom matcher."#.to_owned());
+        s.into()
+    }
+}
+
+/// How to evaluate a condition against a value of the different type.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum MismatchPolicy {
+    /// Type mismatch is an error.
+    #[serde(rename = "error")]
+    Error,
+    /// Type mismatch is evaluated as `false`.
+    ///
+    /// The mismatch is neither true nor false inside the condition of the pair,
+    /// so that `not` of the mismatch is also `false`; the pair is decided as `false`.
+    #[serde(rename = "false")]
+    False,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct QueryPair {
     #[serde(rename = "p")]
     pub pointer: String,
     #[serde(rename = "cond")]
     pub condition: Condition,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_mismatch: Option<MismatchPolicy>,
+    /// Read the numeric strings as numbers when the condition compares against a number.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub coerce: bool,
+    /// Remove the leading and the trailing whitespace of the string.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trim: bool,
+    /// Replace the runs of whitespace of the string with a space, and trim it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapse_ws: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum QueryCondition {
     #[serde(rename = "raw")]
@@ -63,16 +220,69 @@ pub enum QueryCondition {
     And { pair: vec::Vec<QueryCondition> },
     #[serde(rename = "or")]
     Or { pair: vec::Vec<QueryCondition> },
+    /// Match if the pointer points a value,
+    /// the shorthand of the pair of the pointer and [`Condition::Exists`].
+    #[serde(rename = "exists")]
+    Exists {
+        #[serde(rename = "p")]
+        pointer: String,
+    },
+    /// Match if the value at any of the pointers meets the condition, ignoring the missing values.
+    #[serde(rename = "any_of")]
+    AnyOf {
+        pointers: vec::Vec<String>,
+        #[serde(rename = "cond")]
+        condition: Condition,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        on_mismatch: Option<MismatchPolicy>,
+    },
+    /// Match if the values at all of the pointers exist and meet the condition.
+    #[serde(rename = "all_of")]
+    AllOf {
+        pointers: vec::Vec<String>,
+        #[serde(rename = "cond")]
+        condition: Condition,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        on_mismatch: Option<MismatchPolicy>,
+    },
+}
+
+impl QueryCondition {
+    /// Set `policy` to the pairs without [`MismatchPolicy`].
+    pub fn set_default_on_mismatch(&mut self, policy: MismatchPolicy) {
+        match self {
+            QueryCondition::Raw { pair } => {
+                pair.on_mismatch.get_or_insert(policy);
+            }
+            QueryCondition::Not { pair } => pair.set_default_on_mismatch(policy),
+            QueryCondition::And { pair } | QueryCondition::Or { pair } => {
+                for x in pair {
+                    x.set_default_on_mismatch(policy);
+                }
+            }
+            QueryCondition::AnyOf { on_mismatch, .. }
+            | QueryCondition::AllOf { on_mismatch, .. } => {
+                on_mismatch.get_or_insert(policy);
+            }
+            // exists never mismatches
+            QueryCondition::Exists { .. } => (),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Query {
     pub query: QueryCondition,
+    /// Default [`MismatchPolicy`] of the pairs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_mismatch: Option<MismatchPolicy>,
 }
 
+/// Parse the json, which may have the `//` and `/* */` comments and the trailing commas.
 impl convert::TryFrom<&str> for Query {
     type Error = error::Error;
     fn try_from(v: &str) -> Result<Self, Self::Error> {
-        from_str(v).map_err(|x| error::Error::new(error::ErrorCode::Json(x)))
+        from_str(&util::strip_jsonc(v)).map_err(|x| error::Error::new(error::ErrorCode::Json(x)))
     }
 }
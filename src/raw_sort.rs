@@ -1,10 +1,13 @@
 use crate::error;
+use crate::raw_query::JsonType;
+use crate::util;
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
 use std::convert;
 use std::vec;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Order {
     #[serde(rename = "asc")]
     Asc,
@@ -12,22 +15,91 @@ pub enum Order {
     Desc,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// How to read the sort key from the value at the pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Parse {
+    /// Date and time, sorted chronologically, see `formats`.
+    #[serde(rename = "datetime")]
+    Datetime,
+    /// Semantic version, e.g. `"1.10.0"` after `"1.9.0"`, may be prefixed by `v`.
+    #[cfg(feature = "semver")]
+    #[serde(rename = "semver")]
+    Semver,
+    /// Number or numeric string, e.g. `"10"` after `"9"`.
+    #[serde(rename = "number")]
+    Number,
+}
+
+/// What to do with the values not parsable by `parse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Unparsable {
+    /// Sort them as null.
+    #[default]
+    #[serde(rename = "null")]
+    Null,
+    /// Reject them as the errors.
+    #[serde(rename = "error")]
+    Error,
+}
+
+/// Format of the datetime sort keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DatetimeFormat {
+    /// `YYYY-MM-DD[Thh:mm[:ss[.fff]][Z|±hh:mm]]` string, UTC without the offset.
+    #[serde(rename = "iso8601")]
+    Iso8601,
+    /// Seconds since the unix epoch, number or numeric string.
+    #[serde(rename = "epoch")]
+    Epoch,
+    /// Milliseconds since the unix epoch, number or numeric string.
+    #[serde(rename = "epoch_millis")]
+    EpochMillis,
+}
+
+impl DatetimeFormat {
+    /// Formats tried when `formats` is not given.
+    pub const DEFAULT: &'static [DatetimeFormat] =
+        &[DatetimeFormat::Iso8601, DatetimeFormat::Epoch];
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct SortPair {
     #[serde(rename = "p")]
     pub pointer: String,
     #[serde(rename = "ord")]
     pub order: Option<Order>,
+    /// Parse the value into the sort key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parse: Option<Parse>,
+    /// What to do with the values not parsable by `parse`, sorted as null by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_unparsable: Option<Unparsable>,
+    /// Formats of `"parse":"datetime"` tried in order, `["iso8601","epoch"]` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formats: Option<vec::Vec<DatetimeFormat>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Sort {
     pub sort: vec::Vec<SortPair>,
+    /// Order of the types of the values at a pointer, e.g. `["number","string"]`.
+    /// The types not listed follow in the default order: null, array, object, bool, number and string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_order: Option<vec::Vec<JsonType>>,
+    /// Reject the values whose type differs from the first one at the same pointer, except null.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_types: bool,
 }
 
+/// Parse the json, which may have the `//` and `/* */` comments and the trailing commas.
 impl convert::TryFrom<&str> for Sort {
     type Error = error::Error;
     fn try_from(v: &str) -> Result<Self, Self::Error> {
-        from_str(v).map_err(|x| error::Error::new(error::ErrorCode::Json(x)))
+        from_str(&util::strip_jsonc(v)).map_err(|x| error::Error::new(error::ErrorCode::Json(x)))
     }
 }
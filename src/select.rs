@@ -1,7 +1,11 @@
 use crate::error::{Error, ErrorCode, Result};
+use crate::eval::document::Document;
+use crate::pointer::Pointer;
 use crate::query;
+use crate::util;
 use serde_json::from_str;
-use serde_json::value::Value;
+use serde_json::value::{RawValue, Value};
+use std::io;
 
 /// JSON filter.
 ///
@@ -9,29 +13,50 @@ use serde_json::value::Value;
 /// # use jsongrep::raw_query;
 /// # use jsongrep::query;
 /// # use jsongrep::select;
-/// # use std::convert::{TryFrom, From};
+/// # use std::convert::TryFrom;
+/// # #[cfg(feature = "regex")]
+/// # {
 ///
 /// const jq: &str = r#"{"query":{"type":"raw","pair":{"p":"/s","cond":{"type":"match","mtype":"regex","value":{"type":"string","value":"[sS]irius"}}}}}"#;
-/// let sel = raw_query::Query::try_from(jq).map(|x| {
-///   let q = query::Query::from(x);
-///   select::Query::new(Box::new(q))
+/// let sel = raw_query::Query::try_from(jq).and_then(|x| {
+///   let q = query::Query::try_from(x)?;
+///   Ok(select::Query::new(Box::new(q)))
 /// }).unwrap();
 ///
 /// /// matched
 /// const sirius: &str = r#"{"s":"Sirius at the starry night in the winter"}"#;
-/// assert!(sel.select(sirius).is_ok());
+/// assert!(sel.filter(sirius).unwrap().is_some());
 /// /// unmatched
 /// const spica: &str = r#"{"s":"Spica on the earth"}"#;
-/// assert!(sel.select(spica).err().unwrap().is_filtered());
+/// assert!(sel.filter(spica).unwrap().is_none());
+/// # }
 /// ```
 pub struct Query {
     q: Box<dyn query::EvaluableQuery>,
+    hooks: Vec<Box<Hook>>,
+    pointers: Vec<Pointer>,
+}
+
+/// Callback invoked per evaluated json.
+type Hook = dyn Fn(&Record<'_>) + Send + Sync;
+
+/// Evaluation of a json, passed to the callbacks of [`Query::on_record`].
+pub struct Record<'a> {
+    /// `Ok(true)` if the json meets the condition.
+    pub outcome: std::result::Result<bool, &'a Error>,
+    /// The values at the pointers added by [`Query::watch`] in order,
+    /// `None` if not found.
+    pub values: Vec<Option<Value>>,
 }
 
 impl Query {
     /// Create a new Query.
     pub fn new(q: Box<dyn query::EvaluableQuery>) -> Query {
-        Query { q }
+        Query {
+            q,
+            hooks: Vec::new(),
+            pointers: Vec::new(),
+        }
     }
     /// Create a new Query without condition.
     /// It accepts any json.
@@ -42,20 +67,216 @@ impl Query {
                 Ok(true)
             }
         }
-        let q = Box::new(OkQuery);
-        Query { q }
+        Query::new(Box::new(OkQuery))
+    }
+    /// Add a callback invoked with the outcome every time a json is evaluated.
+    ///
+    /// ```
+    /// # use jsongrep::query;
+    /// # use jsongrep::select;
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::sync::Arc;
+    ///
+    /// let q = query::Query::builder().pointer("/i").gt(1).build().unwrap();
+    /// let matched = Arc::new(AtomicUsize::new(0));
+    /// let m = Arc::clone(&matched);
+    /// let sel = select::Query::new(Box::new(q))
+    ///     .watch("/s")
+    ///     .on_record(move |r| {
+    ///         if let Ok(true) = r.outcome {
+    ///             assert_eq!(Some("sirius"), r.values[0].as_ref().and_then(|x| x.as_str()));
+    ///             m.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     });
+    /// sel.matches(r#"{"i":2,"s":"sirius"}"#).unwrap();
+    /// sel.matches(r#"{"i":1,"s":"spica"}"#).unwrap();
+    /// assert_eq!(1, matched.load(Ordering::Relaxed));
+    /// ```
+    pub fn on_record(mut self, f: impl Fn(&Record<'_>) + Send + Sync + 'static) -> Query {
+        self.hooks.push(Box::new(f));
+        self
+    }
+    /// Add a pointer whose value is passed to the callbacks of [`Query::on_record`].
+    /// The value of a malformed pointer is always `None`.
+    pub fn watch(mut self, pointer: &str) -> Query {
+        self.pointers.push(Pointer::lenient(pointer));
+        self
+    }
+    /// Invoke the callbacks with the outcome of `v`.
+    fn notify<D: Document + ?Sized>(&self, v: &D, outcome: &Result<bool>) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let record = Record {
+            outcome: outcome.as_ref().copied(),
+            values: self
+                .pointers
+                .iter()
+                .map(|p| v.pointed(p).ok().flatten().map(|x| x.into_owned()))
+                .collect(),
+        };
+        for f in &self.hooks {
+            f(&record);
+        }
+    }
+    /// Filter a given json.
+    /// Return `Ok(Some)` if a given json meets the condition,
+    /// `Ok(None)` if a given json does not meet the condition.
+    pub fn filter(&self, v: &str) -> Result<Option<Value>> {
+        let v = from_str(v).map_err(|x| invalid_json(v, x))?;
+        self.select_value(&v)
+            .map(|x| if x { Some(v) } else { None })
     }
     /// Filter a given json.
     /// Return `Ok` if a given json meet the condition.
     /// Return `Err` with `ErrorCode::FilteredByQuery` if a given json does not meet the condition.
     pub fn select(&self, v: &str) -> Result<Value> {
-        let v = from_str(v).map_err(|x| Error::new(ErrorCode::Json(x)))?;
-        match self.q.eval(&v) {
+        let v = from_str(v).map_err(|x| invalid_json(v, x))?;
+        match self.select_value(&v) {
             Ok(true) => Ok(v),
             Ok(false) => Err(Error::new(ErrorCode::FilteredByQuery)),
             Err(x) => Err(x),
         }
     }
+    /// Report whether a given parsed json meets the condition.
+    pub fn select_value(&self, v: &Value) -> Result<bool> {
+        let r = self.q.eval(v);
+        util::event!(trace, outcome = ?r.as_ref().ok(), "evaluated json");
+        self.notify(v, &r);
+        r
+    }
+    /// Report whether a given json meets the condition.
+    pub fn matches(&self, v: &str) -> Result<bool> {
+        let v = from_str(v).map_err(|x| invalid_json(v, x))?;
+        self.select_value(&v)
+    }
+    /// Report whether a given unparsed json meets the condition.
+    pub fn select_raw_value(&self, v: &RawValue) -> Result<bool> {
+        let r = self.q.eval_raw(v);
+        util::event!(trace, outcome = ?r.as_ref().ok(), "evaluated json");
+        self.notify(v, &r);
+        r
+    }
+    /// Same as [`Query::matches`] but parse only the values referred by the query.
+    pub fn matches_raw(&self, v: &str) -> Result<bool> {
+        let r: &RawValue = from_str(v).map_err(|x| invalid_json(v, x))?;
+        self.select_raw_value(r)
+    }
+}
+
+/// A line to be filtered by [`SelectIter`].
+pub trait SelectInput {
+    type Line: AsRef<str>;
+    fn into_line(self) -> Result<Self::Line>;
+}
+
+impl<'a> SelectInput for &'a str {
+    type Line = &'a str;
+    fn into_line(self) -> Result<Self::Line> {
+        Ok(self)
+    }
+}
+
+impl SelectInput for String {
+    type Line = String;
+    fn into_line(self) -> Result<Self::Line> {
+        Ok(self)
+    }
+}
+
+impl SelectInput for io::Result<String> {
+    type Line = String;
+    fn into_line(self) -> Result<Self::Line> {
+        self.map_err(|x| {
+            util::event!(warn, error = %x, "failed to read a line");
+            Error::new(ErrorCode::Io(x))
+        })
+    }
+}
+
+/// Iterator that yields the jsons meeting the condition of [`Query`] and the errors,
+/// skips the jsons that do not meet the condition.
+///
+/// ```
+/// # use jsongrep::query;
+/// # use jsongrep::select::{self, SelectExt};
+///
+/// let q = query::Query::builder().pointer("/i").gt(1).build().unwrap();
+/// let sel = select::Query::new(Box::new(q));
+/// let lines = vec![r#"{"i":1}"#, r#"{"i":2}"#, "three", r#"{"i":4}"#];
+/// let got: Vec<_> = lines.into_iter().filter_selected(&sel).collect();
+/// assert_eq!(3, got.len());
+/// assert_eq!(2, got[0].as_ref().unwrap()["i"]);
+/// assert!(got[1].as_ref().err().unwrap().is_json());
+/// assert_eq!(4, got[2].as_ref().unwrap()["i"]);
+/// ```
+pub struct SelectIter<'a, I> {
+    iter: I,
+    query: &'a Query,
+}
+
+impl<I> Iterator for SelectIter<'_, I>
+where
+    I: Iterator,
+    I::Item: SelectInput,
+{
+    type Item = Result<Value>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let query = self.query;
+        for x in self.iter.by_ref() {
+            let r = x
+                .into_line()
+                .and_then(|l| query.filter(l.as_ref()))
+                .transpose();
+            if r.is_some() {
+                return r;
+            }
+        }
+        None
+    }
+}
+
+/// Extension of the iterators of the lines to filter them by [`Query`].
+pub trait SelectExt: Iterator + Sized {
+    /// Return an iterator that filters the lines by `query`.
+    fn filter_selected(self, query: &Query) -> SelectIter<'_, Self> {
+        SelectIter { iter: self, query }
+    }
+}
+
+impl<I> SelectExt for I
+where
+    I: Iterator,
+    I::Item: SelectInput,
+{
+}
+
+/// The number of bytes around the error position in the excerpt of [`invalid_json`].
+const EXCERPT_RADIUS: usize = 16;
+
+/// Return a json syntax error with the byte offset of the error position in `v`
+/// and the excerpt around it.
+fn invalid_json(v: &str, err: serde_json::Error) -> Error {
+    let offset = v
+        .split_inclusive('\n')
+        .take(err.line().saturating_sub(1))
+        .map(|x| x.len())
+        .sum::<usize>()
+        + err.column().saturating_sub(1);
+    let offset = offset.min(v.len());
+    let mut begin = offset.saturating_sub(EXCERPT_RADIUS);
+    while !v.is_char_boundary(begin) {
+        begin -= 1;
+    }
+    let mut end = (offset + EXCERPT_RADIUS).min(v.len());
+    while !v.is_char_boundary(end) {
+        end += 1;
+    }
+    Error::new(ErrorCode::InvalidJson {
+        offset,
+        excerpt: v[begin..end].to_owned(),
+        source: err,
+    })
 }
 
 #[cfg(test)]
@@ -76,12 +297,119 @@ mod tests {
         Query::new(Box::new(q)).select("white").unwrap();
     }
     #[test]
+    fn test_query_err_json_position() {
+        let q = MockOkEvaluableQuery(true);
+        let got = Query::new(Box::new(q))
+            .select(r#"{"name":"sirius","magnitude":-1.46,"constellation":CMa}"#)
+            .err()
+            .unwrap();
+        assert!(got.is_json());
+        let s = format!("{}", got);
+        assert!(s.contains("offset: 51"), "{}", s);
+        assert!(s.contains(r#"near: "\"constellation\":CMa}""#), "{}", s);
+    }
+    #[test]
     fn test_query_err_filtered() {
         let q = MockOkEvaluableQuery(false);
         let got = Query::new(Box::new(q)).select(r#"{"x":1}"#);
         assert!(got.err().unwrap().is_filtered());
     }
     #[test]
+    fn test_query_select_value() {
+        let v: Value = from_str(r#"{"x":1}"#).unwrap();
+        assert!(Query::new(Box::new(MockOkEvaluableQuery(true)))
+            .select_value(&v)
+            .unwrap());
+        assert!(!Query::new(Box::new(MockOkEvaluableQuery(false)))
+            .select_value(&v)
+            .unwrap());
+    }
+    #[test]
+    fn test_query_matches_raw() {
+        let q = query::Query::builder().pointer("/i").gt(1).build().unwrap();
+        let q = Query::new(Box::new(q));
+        assert!(q.matches_raw(r#"{"s":{"a":[1,2]},"i":2}"#).unwrap());
+        assert!(!q.matches_raw(r#"{"s":{"a":[1,2]},"i":1}"#).unwrap());
+        assert!(q.matches_raw(r#"{"s":1"#).err().unwrap().is_json());
+        assert!(q.matches_raw(r#"{"s":1}"#).is_err());
+    }
+    #[test]
+    fn test_query_matches() {
+        let q = Query::new(Box::new(MockOkEvaluableQuery(false)));
+        assert!(!q.matches(r#"{"x":1}"#).unwrap());
+        assert!(q.matches("white").err().unwrap().is_json());
+    }
+    #[test]
+    fn test_query_filter() {
+        let got = Query::new(Box::new(MockOkEvaluableQuery(true)))
+            .filter(r#"{"x":1}"#)
+            .unwrap();
+        let want: Value = from_str(r#"{"x":1}"#).unwrap();
+        assert_eq!(Some(want), got);
+        let got = Query::new(Box::new(MockOkEvaluableQuery(false)))
+            .filter(r#"{"x":1}"#)
+            .unwrap();
+        assert_eq!(None, got);
+    }
+    #[test]
+    fn test_query_shared_across_threads() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let q = Arc::new(Query::new(Box::new(MockOkEvaluableQuery(true))));
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let q = Arc::clone(&q);
+                thread::spawn(move || q.matches(&format!(r#"{{"x":{}}}"#, i)).unwrap())
+            })
+            .collect();
+        for h in handles {
+            assert!(h.join().unwrap());
+        }
+    }
+    #[test]
+    fn test_select_iter() {
+        let q = Query::new(Box::new(MockOkEvaluableQuery(true)));
+        let lines = vec![
+            Ok(r#"{"x":1}"#.to_owned()),
+            Err(io::Error::other("broken")),
+            Ok("white".to_owned()),
+        ];
+        let got: Vec<_> = lines.into_iter().filter_selected(&q).collect();
+        assert_eq!(3, got.len());
+        assert!(got[0].is_ok());
+        assert!(got[1].as_ref().err().unwrap().is_io());
+        assert!(got[2].as_ref().err().unwrap().is_json());
+    }
+    #[test]
+    fn test_select_iter_skip_filtered() {
+        let q = Query::new(Box::new(MockOkEvaluableQuery(false)));
+        let lines = vec![r#"{"x":1}"#.to_owned(), r#"{"x":2}"#.to_owned()];
+        assert_eq!(0, lines.into_iter().filter_selected(&q).count());
+    }
+    #[test]
+    fn test_query_on_record() {
+        use std::sync::{Arc, Mutex};
+
+        let got = Arc::new(Mutex::new(Vec::new()));
+        let g = Arc::clone(&got);
+        let q = Query::new(Box::new(MockOkEvaluableQuery(true)))
+            .watch("/x")
+            .watch("/y")
+            .on_record(move |r| {
+                g.lock()
+                    .unwrap()
+                    .push((r.outcome.is_ok(), r.values.clone()))
+            });
+        q.matches(r#"{"x":1}"#).unwrap();
+        q.matches_raw(r#"{"y":"2"}"#).unwrap();
+        let want: Vec<(bool, Vec<Option<Value>>)> = vec![
+            (true, vec![Some(Value::from(1)), None]),
+            (true, vec![None, Some(Value::from("2"))]),
+        ];
+        assert_eq!(want, *got.lock().unwrap());
+    }
+    #[test]
     fn test_query_ok() {
         let q = MockOkEvaluableQuery(true);
         let got = Query::new(Box::new(q)).select(r#"{"x":1}"#).unwrap();
@@ -1,6 +1,11 @@
-use crate::compare::sort::{PairsListBuilder, PairsListSettings};
-use crate::raw_sort::{Order, Sort as RawSort};
+use crate::compare::sort::{KeyParser, PairsListBuilder, PairsListSettings};
+use crate::error::Result;
+use crate::pointer::Pointer;
+use crate::raw_query::JsonType;
+use crate::raw_sort::{DatetimeFormat, Order, Parse, Sort as RawSort, Unparsable};
+use crate::util;
 use serde_json::value::Value;
+use std::cmp::Ordering;
 
 /// JSON sorter.
 ///
@@ -24,30 +29,327 @@ use serde_json::value::Value;
 /// }
 /// assert_eq!(vec![2, 0, 1, 3], st.sorted_indexes());
 /// ```
+///
+/// [`Sort`] can also own the values and return them sorted.
+///
+/// ```
+/// # use jsongrep::raw_sort::Sort as RawSort;
+/// # use jsongrep::sort::Sort;
+/// # use std::convert::{From, TryFrom};
+/// # use serde_json::from_str;
+///
+/// const js: &str = r#"{"sort":[{"p":"/i"}]}"#;
+/// let mut st = RawSort::try_from(js).map(Sort::from).unwrap();
+/// for v in vec![r#"{"i":10}"#, r#"{"i":5}"#, r#"{"i":20}"#] {
+///   st.push(from_str(v).unwrap());
+/// }
+/// let got: Vec<_> = st.into_sorted_iter().map(|x| x["i"].as_i64().unwrap()).collect();
+/// assert_eq!(vec![5, 10, 20], got);
+/// ```
+///
+/// The values of the different types are sorted by `type_order`,
+/// or rejected by [`Sort::try_add`] if `strict_types`.
+///
+/// ```
+/// # use jsongrep::raw_sort::Sort as RawSort;
+/// # use jsongrep::sort::Sort;
+/// # use std::convert::{From, TryFrom};
+/// # use serde_json::json;
+///
+/// const js: &str = r#"{"sort":[{"p":"/i"}],"type_order":["string"],"strict_types":true}"#;
+/// let mut st = RawSort::try_from(js).map(Sort::from).unwrap();
+/// st.try_add(json!({"i":"a"})).unwrap();
+/// st.try_add(json!({"i":null})).unwrap();
+/// assert!(st.try_add(json!({"i":1})).is_err());
+/// assert_eq!(vec![0, 1], st.sorted_indexes());
+/// ```
 pub struct Sort {
     builder: PairsListBuilder,
+    /// Values added by [`Sort::push`], `None` if added by [`Sort::add`].
+    values: Vec<Option<Value>>,
+    /// The number of threads to sort.
+    threads: usize,
 }
 
 impl From<RawSort> for Sort {
     fn from(v: RawSort) -> Sort {
         let mut s = PairsListSettings::new();
         for p in v.sort {
-            s.add(p.pointer, p.order.unwrap_or(Order::Asc));
+            // a malformed pointer points nothing, sorted as null
+            let formats = p.formats;
+            let parser = p.parse.map(|x| match x {
+                Parse::Datetime => {
+                    KeyParser::Datetime(formats.unwrap_or_else(|| DatetimeFormat::DEFAULT.to_vec()))
+                }
+                #[cfg(feature = "semver")]
+                Parse::Semver => KeyParser::Semver,
+                Parse::Number => KeyParser::Number,
+            });
+            let on_unparsable = p.on_unparsable.unwrap_or_default();
+            s.add(
+                Pointer::lenient(&p.pointer),
+                p.order.unwrap_or(Order::Asc),
+                parser.map(|x| (x, on_unparsable)),
+            );
+        }
+        if let Some(t) = v.type_order {
+            s.type_order(&t);
+        }
+        if v.strict_types {
+            s.strict_types();
         }
         let builder = s.builder();
-        Sort { builder }
+        Sort {
+            builder,
+            values: Vec::new(),
+            threads: 1,
+        }
     }
 }
 
 impl Sort {
+    /// Return a new [`SortBuilder`].
+    pub fn builder() -> SortBuilder {
+        SortBuilder::default()
+    }
+    /// Return a new [`SortBuilder`], same as [`Sort::builder`].
+    ///
+    /// ```
+    /// # use jsongrep::raw_sort::Order;
+    /// # use jsongrep::sort::Sort;
+    /// # use serde_json::json;
+    ///
+    /// let mut st = Sort::new()
+    ///     .by("/i", Order::Desc)
+    ///     .by("/s", Order::Asc)
+    ///     .build()
+    ///     .unwrap();
+    /// st.add(json!({"i":1,"s":"a"}));
+    /// st.add(json!({"i":2,"s":"b"}));
+    /// st.add(json!({"i":2,"s":"a"}));
+    /// assert_eq!(vec![2, 1, 0], st.sorted_indexes());
+    /// ```
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> SortBuilder {
+        Self::builder()
+    }
+    /// Sort on `threads` threads in parallel if more than one.
+    #[cfg(feature = "parallel")]
+    pub fn with_threads(mut self, threads: usize) -> Sort {
+        self.threads = threads;
+        self
+    }
     /// Add a value to be sorted.
+    ///
+    /// Same as [`Sort::try_add`] but ignore the rejected value.
     pub fn add(&mut self, value: Value) {
-        self.builder.add(value);
+        let _ = self.try_add(value);
+    }
+    /// Add a value to be sorted.
+    /// Return `Err` and add nothing if the types are strict and a sort key has another type,
+    /// or a sort key is not parsable and `"on_unparsable":"error"`.
+    pub fn try_add(&mut self, value: Value) -> Result<()> {
+        self.builder.add(&value)?;
+        self.values.push(None);
+        Ok(())
+    }
+    /// Add a value to be sorted and keep it to be returned by [`Sort::into_sorted_iter`].
+    ///
+    /// Same as [`Sort::try_push`] but ignore the rejected value.
+    pub fn push(&mut self, value: Value) {
+        let _ = self.try_push(value);
+    }
+    /// Same as [`Sort::try_add`] but keep the value to be returned by [`Sort::into_sorted_iter`].
+    pub fn try_push(&mut self, value: Value) -> Result<()> {
+        self.builder.add(&value)?;
+        self.values.push(Some(value));
+        Ok(())
+    }
+    /// Compare two values by the sort keys, `Less` if `a` comes first.
+    ///
+    /// The sort is stable, so the equal values keep their order.
+    ///
+    /// ```
+    /// # use jsongrep::raw_sort::Order;
+    /// # use jsongrep::sort::Sort;
+    /// # use serde_json::json;
+    /// # use std::cmp::Ordering;
+    ///
+    /// let st = Sort::builder().by("/i", Order::Desc).build().unwrap();
+    /// assert_eq!(Ordering::Less, st.compare(&json!({"i":2}), &json!({"i":1})));
+    /// ```
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        self.builder.compare(a, b)
     }
     /// Return the indexes of the sorted values.
     pub fn sorted_indexes(self) -> Vec<usize> {
-        let mut list = self.builder.build();
-        list.sort();
-        list.indexes()
+        Self::sort(self.builder, self.threads)
+    }
+    /// Return the sorted values added by [`Sort::push`].
+    pub fn into_sorted_values(self) -> Vec<Value> {
+        self.into_sorted_iter().collect()
+    }
+    /// Return an iterator over the sorted values added by [`Sort::push`].
+    pub fn into_sorted_iter(self) -> impl Iterator<Item = Value> {
+        let mut values = self.values;
+        Self::sort(self.builder, self.threads)
+            .into_iter()
+            .filter_map(move |i| values[i].take())
+    }
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(builder))
+    )]
+    fn sort(builder: PairsListBuilder, threads: usize) -> Vec<usize> {
+        let mut list = builder.build();
+        match threads {
+            #[cfg(feature = "parallel")]
+            n if n > 1 => list.par_sort(n),
+            _ => list.sort(),
+        }
+        let r = list.indexes();
+        util::event!(debug, values = r.len(), "sorted");
+        r
+    }
+}
+
+/// [`Sort`] builder.
+///
+/// ```
+/// # use jsongrep::raw_sort::Order;
+/// # use jsongrep::sort::Sort;
+/// # use serde_json::from_str;
+///
+/// /// sort by /i desc, then by /s asc
+/// let mut st = Sort::builder()
+///     .by("/i", Order::Desc)
+///     .by("/s", Order::Asc)
+///     .build()
+///     .unwrap();
+/// for v in vec![r#"{"i":1,"s":"b"}"#, r#"{"i":2,"s":"c"}"#, r#"{"i":1,"s":"a"}"#] {
+///   st.add(from_str(v).unwrap());
+/// }
+/// assert_eq!(vec![1, 2, 0], st.sorted_indexes());
+///
+/// assert!(Sort::builder().by("i", Order::Asc).build().is_err());
+/// ```
+#[derive(Default)]
+pub struct SortBuilder {
+    pairs: Vec<(String, Order, Option<KeyParser>)>,
+    type_order: Option<Vec<JsonType>>,
+    strict_types: bool,
+    on_unparsable: Unparsable,
+}
+
+impl SortBuilder {
+    /// Add a sort key, the value at `pointer`.
+    ///
+    /// The key added first takes precedence, the following ones break the ties in order.
+    /// Note that it is the reverse of the pairs of [`RawSort`], sorted by the keys sequentially.
+    pub fn by(mut self, pointer: impl Into<String>, order: Order) -> SortBuilder {
+        self.pairs.push((pointer.into(), order, None));
+        self
+    }
+    /// Add a sort key, the datetime at `pointer` parsed by the first matching format of `formats`.
+    ///
+    /// ```
+    /// # use jsongrep::raw_sort::{DatetimeFormat, Order};
+    /// # use jsongrep::sort::Sort;
+    /// # use serde_json::json;
+    ///
+    /// let mut st = Sort::builder()
+    ///     .by_datetime("/t", Order::Asc, DatetimeFormat::DEFAULT.to_vec())
+    ///     .build()
+    ///     .unwrap();
+    /// st.add(json!({"t":"2000-01-01T09:00:00+09:00"}));
+    /// st.add(json!({"t":"1999-12-31T23:00:00Z"}));
+    /// assert_eq!(vec![1, 0], st.sorted_indexes());
+    /// ```
+    pub fn by_datetime(
+        mut self,
+        pointer: impl Into<String>,
+        order: Order,
+        formats: Vec<DatetimeFormat>,
+    ) -> SortBuilder {
+        self.pairs
+            .push((pointer.into(), order, Some(KeyParser::Datetime(formats))));
+        self
+    }
+    /// Sort the values of the different types by `types` first, then the others in the default order.
+    pub fn type_order(mut self, types: Vec<JsonType>) -> SortBuilder {
+        self.type_order = Some(types);
+        self
+    }
+    /// Reject the values whose type differs from the first non-null one at the same pointer.
+    pub fn strict_types(mut self) -> SortBuilder {
+        self.strict_types = true;
+        self
+    }
+    /// Add a sort key, the semantic version at `pointer`.
+    ///
+    /// ```
+    /// # use jsongrep::raw_sort::Order;
+    /// # use jsongrep::sort::Sort;
+    /// # use serde_json::json;
+    ///
+    /// let mut st = Sort::builder().by_semver("/v", Order::Asc).build().unwrap();
+    /// st.add(json!({"v":"1.10.0"}));
+    /// st.add(json!({"v":"1.9.0"}));
+    /// assert_eq!(vec![1, 0], st.sorted_indexes());
+    /// ```
+    #[cfg(feature = "semver")]
+    pub fn by_semver(mut self, pointer: impl Into<String>, order: Order) -> SortBuilder {
+        self.pairs
+            .push((pointer.into(), order, Some(KeyParser::Semver)));
+        self
+    }
+    /// Add a sort key, the number or the numeric string at `pointer`.
+    ///
+    /// ```
+    /// # use jsongrep::raw_sort::{Order, Unparsable};
+    /// # use jsongrep::sort::Sort;
+    /// # use serde_json::json;
+    ///
+    /// let mut st = Sort::builder()
+    ///     .by_number("/n", Order::Asc)
+    ///     .on_unparsable(Unparsable::Error)
+    ///     .build()
+    ///     .unwrap();
+    /// st.try_add(json!({"n":"10"})).unwrap();
+    /// st.try_add(json!({"n":9})).unwrap();
+    /// assert!(st.try_add(json!({"n":"ten"})).is_err());
+    /// assert_eq!(vec![1, 0], st.sorted_indexes());
+    /// ```
+    pub fn by_number(mut self, pointer: impl Into<String>, order: Order) -> SortBuilder {
+        self.pairs
+            .push((pointer.into(), order, Some(KeyParser::Number)));
+        self
+    }
+    /// Set what to do with the values not parsable by the keys of `by_datetime`, `by_number`, etc.
+    /// They are sorted as null by default.
+    pub fn on_unparsable(mut self, on_unparsable: Unparsable) -> SortBuilder {
+        self.on_unparsable = on_unparsable;
+        self
+    }
+    /// Return the sort.
+    /// Return `Err` if a pointer is malformed.
+    pub fn build(self) -> Result<Sort> {
+        let mut s = PairsListSettings::new();
+        let on_unparsable = self.on_unparsable;
+        // the settings sort sequentially, the key added last takes precedence
+        for (p, o, k) in self.pairs.into_iter().rev() {
+            s.add(Pointer::parse(&p)?, o, k.map(|x| (x, on_unparsable)));
+        }
+        if let Some(t) = self.type_order {
+            s.type_order(&t);
+        }
+        if self.strict_types {
+            s.strict_types();
+        }
+        Ok(Sort {
+            builder: s.builder(),
+            values: Vec::new(),
+            threads: 1,
+        })
     }
 }
@@ -0,0 +1,149 @@
+//! Async adapters of [`select::Query`] and [`Sort`] over [`Stream`].
+//!
+//! ```
+//! # use futures::executor::block_on;
+//! # use futures::stream::{self, StreamExt};
+//! # use jsongrep::query;
+//! # use jsongrep::select;
+//! # use jsongrep::sort::Sort;
+//! # use jsongrep::raw_sort::Sort as RawSort;
+//! # use jsongrep::stream::{collect_sorted, SelectStreamExt};
+//! # use std::convert::TryFrom;
+//!
+//! let q = query::Query::builder().pointer("/i").gt(1).build().unwrap();
+//! let sel = select::Query::new(Box::new(q));
+//! let st = RawSort::try_from(r#"{"sort":[{"p":"/i","ord":"desc"}]}"#).map(Sort::from).unwrap();
+//! let lines = stream::iter(vec![r#"{"i":2}"#, r#"{"i":1}"#, r#"{"i":3}"#]);
+//! let got = block_on(collect_sorted(
+//!     lines.filter_selected(&sel).filter_map(|x| async { x.ok() }).boxed(),
+//!     st,
+//! ));
+//! assert_eq!(vec![3, 2], got.iter().map(|x| x["i"].as_i64().unwrap()).collect::<Vec<_>>());
+//! ```
+use crate::error::Result;
+use crate::select::{Query, SelectInput};
+#[cfg(feature = "sort")]
+use crate::sort::Sort;
+use futures::stream::Stream;
+#[cfg(feature = "sort")]
+use futures::stream::StreamExt;
+use futures::task::{Context, Poll};
+use pin_project_lite::pin_project;
+use serde_json::value::Value;
+use std::borrow::Borrow;
+use std::pin::Pin;
+
+pin_project! {
+    /// Stream that yields the jsons meeting the condition of [`Query`] and the errors,
+    /// skips the jsons that do not meet the condition.
+    ///
+    /// `Q` is the query or a reference to it, e.g. `Arc<Query>` to spawn the stream as a task.
+    pub struct SelectStream<S, Q> {
+        #[pin]
+        stream: S,
+        query: Q,
+    }
+}
+
+impl<S, Q> Stream for SelectStream<S, Q>
+where
+    S: Stream,
+    S::Item: SelectInput,
+    Q: Borrow<Query>,
+{
+    type Item = Result<Value>;
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let query = (*this.query).borrow();
+        loop {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(x)) => {
+                    let r = x
+                        .into_line()
+                        .and_then(|l| query.filter(l.as_ref()))
+                        .transpose();
+                    if r.is_some() {
+                        return Poll::Ready(r);
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Extension of the streams of the lines to filter them by [`Query`].
+pub trait SelectStreamExt: Stream + Sized {
+    /// Return a stream that filters the lines by `query`.
+    fn filter_selected<Q: Borrow<Query>>(self, query: Q) -> SelectStream<Self, Q> {
+        SelectStream {
+            stream: self,
+            query,
+        }
+    }
+}
+
+impl<S> SelectStreamExt for S
+where
+    S: Stream,
+    S::Item: SelectInput,
+{
+}
+
+/// Collect the values of `stream` and sort them by `sort`.
+#[cfg(feature = "sort")]
+pub async fn collect_sorted<S>(mut stream: S, mut sort: Sort) -> Vec<Value>
+where
+    S: Stream<Item = Value> + Unpin,
+{
+    while let Some(v) = stream.next().await {
+        sort.push(v);
+    }
+    sort.into_sorted_values()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::EvaluableQuery;
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+    use std::sync::Arc;
+    use std::thread;
+
+    struct MockOkEvaluableQuery(bool);
+    impl EvaluableQuery for MockOkEvaluableQuery {
+        fn eval(&self, _: &Value) -> Result<bool> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_select_stream() {
+        let q = Query::new(Box::new(MockOkEvaluableQuery(true)));
+        let lines = stream::iter(vec![r#"{"x":1}"#, "white"]);
+        let got: Vec<_> = block_on(lines.filter_selected(&q).collect());
+        assert_eq!(2, got.len());
+        assert!(got[0].is_ok());
+        assert!(got[1].as_ref().err().unwrap().is_json());
+    }
+    #[test]
+    fn test_select_stream_skip_filtered() {
+        let q = Query::new(Box::new(MockOkEvaluableQuery(false)));
+        let lines = stream::iter(vec![r#"{"x":1}"#, r#"{"x":2}"#]);
+        assert_eq!(0, block_on(lines.filter_selected(&q).count()));
+    }
+    #[test]
+    fn test_select_stream_owned_not_unpin() {
+        let q = Arc::new(Query::new(Box::new(MockOkEvaluableQuery(true))));
+        let lines = stream::once(async { r#"{"x":1}"# }).chain(stream::once(async { "white" }));
+        let st = lines.filter_selected(Arc::clone(&q));
+        let got: Vec<_> = thread::spawn(move || block_on(st.collect()))
+            .join()
+            .unwrap();
+        assert_eq!(2, got.len());
+        assert!(got[0].is_ok());
+        assert!(got[1].as_ref().err().unwrap().is_json());
+    }
+}
@@ -0,0 +1,106 @@
+//! Helpers to test the queries, enabled by the `testing` feature.
+//!
+//! ```
+//! use jsongrep::query::builder::Cond;
+//! use jsongrep::testing::{assert_matches, assert_not_matches, pair, query};
+//!
+//! let q = query(r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}"#);
+//! assert_matches(&q, r#"{"i":2}"#);
+//! assert_not_matches(&q, r#"{"i":1}"#);
+//!
+//! let q = pair("/s", Cond::contain("ri"));
+//! assert_matches(&q, r#"{"s":"sirius"}"#);
+//! ```
+use crate::query::builder::Cond;
+use crate::query::{EvaluableQuery, Query};
+use crate::raw_query as raw;
+use serde_json::{from_str, to_string};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+/// Compile the json of the raw query.
+/// Panic if the query is invalid.
+#[track_caller]
+pub fn query(json: &str) -> Query {
+    match raw::Query::try_from(json).and_then(Query::try_from) {
+        Ok(x) => x,
+        Err(x) => panic!("invalid query: {}\nquery: {}", x, json),
+    }
+}
+
+/// Compile the raw query in the file at `path`.
+/// Panic if the file cannot be read or the query is invalid.
+#[track_caller]
+pub fn query_file(path: impl AsRef<Path>) -> Query {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(x) => query(&x),
+        Err(x) => panic!("cannot read {}: {}", path.display(), x),
+    }
+}
+
+/// Return the query with a single pair, `cond` against the value at `pointer`.
+/// Panic if the query is invalid.
+#[track_caller]
+pub fn pair(pointer: &str, cond: Cond) -> Query {
+    match Query::builder().pointer(pointer).cond(cond).build() {
+        Ok(x) => x,
+        Err(x) => panic!("invalid pair: {}", x),
+    }
+}
+
+/// Evaluate `json` by `q`.
+/// Panic if `json` is invalid or the evaluation failed.
+#[track_caller]
+fn eval(q: &Query, json: &str) -> bool {
+    let v = match from_str(json) {
+        Ok(x) => x,
+        Err(x) => panic!("invalid json: {}\njson: {}", x, json),
+    };
+    match q.eval(&v) {
+        Ok(x) => x,
+        Err(x) => panic!("{}\nquery: {}\njson: {}", x, describe(q), json),
+    }
+}
+
+fn describe(q: &Query) -> String {
+    to_string(q).unwrap_or_else(|_| format!("{:?}", q))
+}
+
+/// Assert that `json` meets the condition of `q`.
+#[track_caller]
+pub fn assert_matches(q: &Query, json: &str) {
+    if !eval(q, json) {
+        panic!("want match\nquery: {}\njson: {}", describe(q), json);
+    }
+}
+
+/// Assert that `json` does not meet the condition of `q`.
+#[track_caller]
+pub fn assert_not_matches(q: &Query, json: &str) {
+    if eval(q, json) {
+        panic!("want no match\nquery: {}\njson: {}", describe(q), json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "want no match")]
+    fn test_assert_not_matches_panic() {
+        assert_not_matches(&pair("/n", Cond::null()), r#"{"n":null}"#);
+    }
+    #[test]
+    #[should_panic(expected = "want match")]
+    fn test_assert_matches_panic() {
+        assert_matches(&pair("/n", !Cond::null()), r#"{"n":null}"#);
+    }
+    #[test]
+    #[should_panic(expected = "invalid query")]
+    fn test_query_invalid() {
+        query("{}");
+    }
+}
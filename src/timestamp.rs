@@ -0,0 +1,134 @@
+//! Timestamps shared by the conditions, the sort keys and the command.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parse `YYYY-MM-DD[(T| )hh:mm[:ss[.fff]][Z|±hh:mm|±hhmm]]` into the seconds since the unix epoch,
+/// UTC without the offset.
+///
+/// It accepts RFC 3339 timestamps and the common ISO 8601 variants.
+///
+/// ```
+/// use jsongrep::timestamp;
+///
+/// assert_eq!(Some(0.0), timestamp::parse("1970-01-01T09:00:00+09:00"));
+/// assert_eq!(None, timestamp::parse("1970-13-01"));
+/// ```
+pub fn parse(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let b = s.as_bytes();
+    let num = |from: usize, len: usize| -> Option<i64> {
+        let x = b.get(from..from + len)?;
+        if !x.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        std::str::from_utf8(x).ok()?.parse().ok()
+    };
+    if b.len() < 10 || b[4] != b'-' || b[7] != b'-' {
+        return None;
+    }
+    let (year, month, day) = (num(0, 4)?, num(5, 2)?, num(8, 2)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let mut secs = (days_from_civil(year, month, day) * 86400) as f64;
+    if b.len() == 10 {
+        return Some(secs);
+    }
+    if !matches!(b[10], b'T' | b't' | b' ') || b.get(13) != Some(&b':') {
+        return None;
+    }
+    let (hour, min) = (num(11, 2)?, num(14, 2)?);
+    let mut i = 16;
+    let mut sec = 0.0;
+    if b.get(i) == Some(&b':') {
+        let mut end = i + 3;
+        if b.get(end) == Some(&b'.') {
+            end += 1;
+            while b.get(end).is_some_and(u8::is_ascii_digit) {
+                end += 1;
+            }
+        }
+        num(i + 1, 2)?;
+        sec = s.get(i + 1..end)?.parse::<f64>().ok()?;
+        i = end;
+    }
+    if hour > 23 || min > 59 || sec >= 61.0 {
+        return None;
+    }
+    secs += (hour * 3600 + min * 60) as f64 + sec;
+    let offset = match &s[i..] {
+        "" | "Z" | "z" => 0,
+        z if z.starts_with(['+', '-']) => {
+            let (h, m) = match z.len() {
+                6 if z.as_bytes()[3] == b':' => (num(i + 1, 2)?, num(i + 4, 2)?),
+                5 => (num(i + 1, 2)?, num(i + 3, 2)?),
+                _ => return None,
+            };
+            let x = h * 3600 + m * 60;
+            if z.starts_with('-') {
+                -x
+            } else {
+                x
+            }
+        }
+        _ => return None,
+    };
+    Some(secs - offset as f64)
+}
+
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Return the days since 1970-01-01 of the proleptic gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = (month + 9) % 12;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Return the time of the seconds since the unix epoch, `None` if out of range.
+pub fn to_system_time(secs: f64) -> Option<SystemTime> {
+    let d = Duration::try_from_secs_f64(secs.abs()).ok()?;
+    if secs < 0.0 {
+        UNIX_EPOCH.checked_sub(d)
+    } else {
+        UNIX_EPOCH.checked_add(d)
+    }
+}
+
+/// Parse the timestamp of [`parse`] into the time.
+pub fn parse_system_time(s: &str) -> Option<SystemTime> {
+    parse(s).and_then(to_system_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_time() {
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_millis(1500)),
+            parse_system_time("1970-01-01T00:00:01.5Z")
+        );
+        assert_eq!(
+            Some(UNIX_EPOCH - Duration::from_secs(3600)),
+            parse_system_time("1970-01-01T00:00:00+01:00")
+        );
+        assert_eq!(None, to_system_time(1e30));
+        assert_eq!(None, to_system_time(f64::NAN));
+    }
+}
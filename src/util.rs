@@ -1,6 +1,114 @@
 use std::any;
 
+/// Emit the [`tracing`] event at the level if the `tracing` feature is enabled.
+///
+/// e.g. `event!(trace, pointer = %p, "evaluated")`.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+pub(crate) use event;
+
 /// Return the name of the type `T`.
 pub(crate) fn type_name<T>(_: T) -> &'static str {
     any::type_name::<T>()
 }
+
+/// Blank out the `//` and `/* */` comments and the trailing commas of the jsonc `v`,
+/// so that it can be parsed as json.
+///
+/// The removed characters are replaced by spaces except the newlines,
+/// to keep the positions of the errors.
+pub(crate) fn strip_jsonc(v: &str) -> String {
+    let mut out: Vec<u8> = v.as_bytes().to_vec();
+    let s = v.as_bytes();
+    // position of the last comma that may be trailing
+    let mut comma: Option<usize> = None;
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'"' => {
+                comma = None;
+                i += 1;
+                while i < s.len() && s[i] != b'"' {
+                    if s[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'/' if s.get(i + 1) == Some(&b'/') => {
+                while i < s.len() && s[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if s.get(i + 1) == Some(&b'*') => {
+                let end = v[i + 2..].find("*/").map_or(s.len(), |x| i + 2 + x + 2);
+                for x in &mut out[i..end] {
+                    if *x != b'\n' {
+                        *x = b' ';
+                    }
+                }
+                i = end;
+                continue;
+            }
+            b',' => comma = Some(i),
+            b'}' | b']' => {
+                if let Some(x) = comma.take() {
+                    out[x] = b' ';
+                }
+            }
+            x if x.is_ascii_whitespace() => {}
+            _ => comma = None,
+        }
+        i += 1;
+    }
+    // only ascii bytes are replaced by ascii
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test_strip_jsonc {
+        ($name:ident, $input:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($want, strip_jsonc($input));
+            }
+        };
+    }
+
+    test_strip_jsonc!(strip_jsonc_json, r#"{"a":[1,2]}"#, r#"{"a":[1,2]}"#);
+    test_strip_jsonc!(
+        strip_jsonc_line_comment,
+        "{\"a\":1 // one\n}",
+        "{\"a\":1       \n}"
+    );
+    test_strip_jsonc!(
+        strip_jsonc_block_comment,
+        "{/* a\nb */\"a\":1}",
+        "{    \n    \"a\":1}"
+    );
+    test_strip_jsonc!(
+        strip_jsonc_trailing_comma,
+        "{\"a\":[1,2,],}",
+        "{\"a\":[1,2 ] }"
+    );
+    test_strip_jsonc!(
+        strip_jsonc_trailing_comma_before_comment,
+        "[1, // x\n]",
+        "[1      \n]"
+    );
+    test_strip_jsonc!(
+        strip_jsonc_in_string,
+        r#"{"a":"// /* ,}","b":"\"//"}"#,
+        r#"{"a":"// /* ,}","b":"\"//"}"#
+    );
+    test_strip_jsonc!(strip_jsonc_unicode, "{\"é\":1,/*é*/}", "{\"é\":1       }");
+}
@@ -0,0 +1,54 @@
+//! JS bindings of the query engine, built with `--features wasm` for `wasm32-unknown-unknown`.
+//!
+//! ```js
+//! import { compile_query, select } from "jsongrep";
+//!
+//! const q = compile_query('{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}');
+//! select(q, '{"i":2}'); // true
+//! ```
+use crate::error::Result;
+use crate::query;
+use crate::raw_query as raw;
+use crate::select;
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+
+/// Compiled query, returned by [`compile_query`].
+#[wasm_bindgen]
+pub struct QueryHandle(select::Query);
+
+/// Compile the json of the raw query.
+/// Throw an error if the query is invalid.
+#[wasm_bindgen]
+pub fn compile_query(json: &str) -> std::result::Result<QueryHandle, JsValue> {
+    compile(json).map_err(to_js)
+}
+
+/// Report whether `line` meets the condition of `handle`.
+/// Throw an error if `line` is not a valid json or the evaluation failed.
+#[wasm_bindgen]
+pub fn select(handle: &QueryHandle, line: &str) -> std::result::Result<bool, JsValue> {
+    handle.0.matches_raw(line).map_err(to_js)
+}
+
+fn compile(json: &str) -> Result<QueryHandle> {
+    let q = raw::Query::try_from(json).and_then(query::Query::try_from)?;
+    Ok(QueryHandle(select::Query::new(Box::new(q))))
+}
+
+fn to_js(err: crate::error::Error) -> JsValue {
+    JsValue::from_str(&format!("{}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_select() {
+        let q = compile(r#"{"query":{"type":"raw","pair":{"p":"/i","cond":{"type":"gt","value":{"type":"number","value":1}}}}}"#).unwrap();
+        assert!(q.0.matches_raw(r#"{"i":2}"#).unwrap());
+        assert!(!q.0.matches_raw(r#"{"i":1}"#).unwrap());
+        assert!(compile("{}").is_err());
+    }
+}